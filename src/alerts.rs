@@ -1,122 +1,377 @@
-use crate::settings::AlertConfig;
-use reqwest::blocking::Client;
+use crate::metrics;
+use crate::secrets;
+use crate::settings::{AlertChannel, AlertConfig, Severity, WebhookEncoding, WebhookMethod, WebhookOptions};
+use crate::templates::{self, AlertContext};
+use hmac::{Hmac, Mac};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use sha2::Sha256;
 
-pub fn send_alert(config: &AlertConfig, message: &str) {
+/// A message rendered for one channel: plain text, or a full JSON body from a structured template.
+enum Rendered {
+    Text(String),
+    Json(serde_json::Value),
+}
+
+/// Outcome of sending one alert through one channel.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeliveryResult {
+    pub channel: AlertChannel,
+    pub success: bool,
+    /// HTTP status returned by the service, if a response was received
+    pub status: Option<u16>,
+    /// Transport error or response body of a failed request
+    pub error: Option<String>,
+}
+
+/// Sends the alert to the given channels, or to every configured channel when `channels` is empty.
+pub fn send_alert(config: &AlertConfig, ctx: &AlertContext, severity: Severity, channels: &[AlertChannel]) -> Vec<DeliveryResult> {
     if !config.enabled {
-        return;
+        return Vec::new();
     }
+    deliver(config, "Volumetrik Alert", severity, channels, |channel| render_for(config, channel, ctx))
+}
 
+/// Sends a prepared message (e.g. a digest report) as-is, bypassing the alert templates.
+/// Unlike `send_alert` this ignores `config.enabled`, reports have their own switch.
+pub fn send_message(config: &AlertConfig, title: &str, message: &str, channels: &[AlertChannel]) -> Vec<DeliveryResult> {
+    deliver(config, title, Severity::Info, channels, |_| Rendered::Text(message.to_string()))
+}
+
+/// Sends a test message, even when alerts are disabled, so credentials can be checked before enabling them.
+/// A requested channel that is not configured is reported as a failure instead of being skipped.
+pub fn send_test_alert(config: &AlertConfig, channel: Option<AlertChannel>, message: Option<String>) -> Vec<DeliveryResult> {
+    let ctx = AlertContext::test(message);
+    let channels: Vec<AlertChannel> = channel.into_iter().collect();
+    let mut results = deliver(config, "Volumetrik Alert", Severity::Info, &channels, |channel| render_for(config, channel, &ctx));
+
+    if let Some(channel) = channel {
+        if results.is_empty() {
+            results.push(DeliveryResult {
+                channel,
+                success: false,
+                status: None,
+                error: Some("Channel is not configured".to_string()),
+            });
+        }
+    }
+    results
+}
+
+fn deliver(
+    config: &AlertConfig,
+    title: &str,
+    severity: Severity,
+    channels: &[AlertChannel],
+    render: impl Fn(AlertChannel) -> Rendered,
+) -> Vec<DeliveryResult> {
+    let config = &secrets::resolve(config);
     let client = Client::new();
+    let mut results = Vec::new();
 
-    // Telegram
-    if let (Some(token), Some(chat_id)) = (&config.telegram_bot_token, &config.telegram_chat_id) {
-        if !token.is_empty() && !chat_id.is_empty() {
-            let url = format!("https://api.telegram.org/bot{}/sendMessage", token);
-            let params = [("chat_id", chat_id), ("text", &message.to_string())];
-            match client.post(&url).form(&params).send() {
-                Ok(resp) => {
-                    if !resp.status().is_success() {
-                        println!("Failed to send Telegram alert: {:?}", resp.text());
-                    }
-                }
-                Err(e) => println!("Error sending Telegram alert: {}", e),
-            }
+    for channel in AlertChannel::ALL {
+        if !channels.is_empty() && !channels.contains(&channel) {
+            continue;
+        }
+        if let Some(result) = send_to_channel(&client, config, channel, title, severity, render(channel)) {
+            metrics::record_delivery(&result);
+            results.push(result);
         }
     }
+    results
+}
 
-    // Webhook (Generic)
-    if let Some(url) = &config.webhook_url {
-        if !url.is_empty() {
-            let payload = json!({
-                "text": message,
-                "alert": "Volumetrik Disk Usage Warning"
-            });
-            send_request(&client, url, &payload, "Generic Webhook");
+/// Channels whose API takes a JSON body that a template may replace entirely.
+fn accepts_json(channel: AlertChannel) -> bool {
+    matches!(
+        channel,
+        AlertChannel::Webhook | AlertChannel::Gotify | AlertChannel::Slack | AlertChannel::Discord | AlertChannel::Teams
+    )
+}
+
+/// Picks the channel template, then the global custom message, then the default text.
+fn render_for(config: &AlertConfig, channel: AlertChannel, ctx: &AlertContext) -> Rendered {
+    if let Some(template) = config.templates.get(&channel).filter(|t| !t.trim().is_empty()) {
+        let structured = accepts_json(channel) && template.trim_start().starts_with(['{', '[']);
+        match templates::render(template, ctx, structured) {
+            Ok(out) if structured => match serde_json::from_str(&out) {
+                Ok(value) => return Rendered::Json(value),
+                Err(e) => println!("{} template did not produce valid JSON: {}", channel.label(), e),
+            },
+            Ok(out) => return Rendered::Text(out),
+            Err(e) => println!("{} template: {}", channel.label(), e),
+        }
+    }
+
+    if let Some(custom) = config.custom_message.as_ref().filter(|c| !c.is_empty()) {
+        match templates::render(custom, ctx, false) {
+            Ok(out) => return Rendered::Text(out),
+            Err(e) => println!("Custom alert message: {}", e),
         }
     }
 
-    // Pushover
-    if let (Some(user), Some(token)) = (&config.pushover_user_key, &config.pushover_api_token) {
-        if !user.is_empty() && !token.is_empty() {
+    Rendered::Text(ctx.message.clone())
+}
+
+/// Sends to one channel. Returns `None` when the channel is not configured.
+fn send_to_channel(
+    client: &Client,
+    config: &AlertConfig,
+    channel: AlertChannel,
+    title: &str,
+    severity: Severity,
+    rendered: Rendered,
+) -> Option<DeliveryResult> {
+    let title = match severity {
+        Severity::Info => title.to_string(),
+        _ => format!("{} [{}]", title, severity.label()),
+    };
+
+    let message = match &rendered {
+        Rendered::Text(text) => text.clone(),
+        Rendered::Json(value) => value.to_string(),
+    };
+    // Structured templates replace the whole body, otherwise each service gets its usual payload
+    let payload_or = |default: serde_json::Value| match &rendered {
+        Rendered::Json(value) => value.clone(),
+        Rendered::Text(_) => default,
+    };
+
+    let request = match channel {
+        AlertChannel::Telegram => {
+            let token = non_empty(&config.telegram_bot_token)?;
+            let chat_id = non_empty(&config.telegram_chat_id)?;
+            let url = format!("https://api.telegram.org/bot{}/sendMessage", token);
+            let params = [("chat_id", chat_id), ("text", message.as_str())];
+            client.post(&url).form(&params)
+        }
+
+        AlertChannel::Webhook => {
+            let url = non_empty(&config.webhook_url)?;
+            let payload = payload_or(json!({
+                "text": message,
+                "alert": "Volumetrik Disk Usage Warning",
+                "title": title,
+                "severity": severity
+            }));
+            webhook_request(client, &config.webhook, url, &payload, &message)
+        }
+
+        AlertChannel::Pushover => {
+            let user = non_empty(&config.pushover_user_key)?;
+            let token = non_empty(&config.pushover_api_token)?;
             let url = "https://api.pushover.net/1/messages.json";
+            let priority = match severity {
+                Severity::Info => "-1",
+                Severity::Warning => "0",
+                Severity::Critical => "1",
+            };
             let params = [
-                ("token", token.as_str()),
-                ("user", user.as_str()),
-                ("message", message),
+                ("token", token),
+                ("user", user),
+                ("title", title.as_str()),
+                ("message", message.as_str()),
+                ("priority", priority),
             ];
-            match client.post(url).form(&params).send() {
-                Ok(resp) => if !resp.status().is_success() { println!("Failed to send Pushover alert: {:?}", resp.text()); },
-                Err(e) => println!("Error sending Pushover alert: {}", e),
-            }
+            client.post(url).form(&params)
         }
-    }
 
-    // Gotify
-    if let (Some(url), Some(token)) = (&config.gotify_url, &config.gotify_token) {
-        if !url.is_empty() && !token.is_empty() {
+        AlertChannel::Gotify => {
+            let url = non_empty(&config.gotify_url)?;
+            let token = non_empty(&config.gotify_token)?;
             let full_url = format!("{}/message?token={}", url.trim_end_matches('/'), token);
-            let payload = json!({
+            let priority = match severity {
+                Severity::Info => 2,
+                Severity::Warning => 5,
+                Severity::Critical => 8,
+            };
+            let payload = payload_or(json!({
                 "message": message,
-                "title": "Volumetrik Alert",
-                "priority": 5
-            });
-            send_request(&client, &full_url, &payload, "Gotify");
+                "title": title,
+                "priority": priority
+            }));
+            client.post(&full_url).json(&payload)
         }
-    }
 
-    // Slack
-    if let Some(url) = &config.slack_webhook_url {
-        if !url.is_empty() {
-            let payload = json!({ "text": message });
-            send_request(&client, url, &payload, "Slack");
+        AlertChannel::Slack => {
+            let url = non_empty(&config.slack_webhook_url)?;
+            client.post(url).json(&payload_or(json!({ "text": message })))
         }
-    }
 
-    // Discord
-    if let Some(url) = &config.discord_webhook_url {
-        if !url.is_empty() {
-            let payload = json!({ "content": message });
-            send_request(&client, url, &payload, "Discord");
+        AlertChannel::Discord => {
+            let url = non_empty(&config.discord_webhook_url)?;
+            client.post(url).json(&payload_or(json!({ "content": message })))
         }
-    }
 
-    // Microsoft Teams
-    if let Some(url) = &config.teams_webhook_url {
-        if !url.is_empty() {
-            let payload = json!({ "text": message });
-            send_request(&client, url, &payload, "Microsoft Teams");
+        AlertChannel::Teams => {
+            let url = non_empty(&config.teams_webhook_url)?;
+            client.post(url).json(&payload_or(json!({ "text": message })))
         }
-    }
 
-    // Ntfy
-    if let Some(url) = &config.ntfy_url {
-        if !url.is_empty() {
-            let mut req = client.post(url).body(message.to_string());
-            
-            if let Some(token) = &config.ntfy_token {
-                if !token.is_empty() {
-                    req = req.header("Authorization", format!("Bearer {}", token));
-                }
+        AlertChannel::Ntfy => {
+            let url = non_empty(&config.ntfy_url)?;
+            let mut req = client.post(url).body(message);
+
+            if let Some(token) = non_empty(&config.ntfy_token) {
+                req = req.header("Authorization", format!("Bearer {}", token));
             }
-            
-            req = req.header("Title", "Volumetrik Alert");
-            
-            match req.send() {
-                 Ok(resp) => if !resp.status().is_success() { println!("Failed to send Ntfy alert: {:?}", resp.text()); },
-                 Err(e) => println!("Error sending Ntfy alert: {}", e),
+
+            let priority = match severity {
+                Severity::Info => "2",
+                Severity::Warning => "3",
+                Severity::Critical => "5",
+            };
+            req.header("Title", title).header("Priority", priority)
+        }
+    };
+
+    Some(send_request(request, channel))
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|v| !v.is_empty())
+}
+
+fn send_request(request: RequestBuilder, channel: AlertChannel) -> DeliveryResult {
+    let service = channel.label();
+    match request.send() {
+        Ok(resp) => {
+            let status = resp.status();
+            if status.is_success() {
+                DeliveryResult { channel, success: true, status: Some(status.as_u16()), error: None }
+            } else {
+                let body = resp.text().unwrap_or_default();
+                println!("Failed to send {} alert: {} {}", service, status, body);
+                DeliveryResult { channel, success: false, status: Some(status.as_u16()), error: Some(body) }
             }
         }
+        Err(e) => {
+            // The URL can hold credentials, like the Telegram bot token or the Gotify token
+            let e = e.without_url();
+            println!("Error sending {} alert: {}", service, e);
+            DeliveryResult { channel, success: false, status: None, error: Some(e.to_string()) }
+        }
     }
 }
 
-fn send_request(client: &Client, url: &str, payload: &serde_json::Value, service: &str) {
-    match client.post(url).json(payload).send() {
-        Ok(resp) => {
-            if !resp.status().is_success() {
-                println!("Failed to send {} alert: {:?}", service, resp.text());
+/// Generic webhook with configurable method, headers, encoding and an optional HMAC signature.
+fn webhook_request(client: &Client, options: &WebhookOptions, url: &str, payload: &serde_json::Value, message: &str) -> RequestBuilder {
+    let (body, content_type) = match options.encoding {
+        WebhookEncoding::Json => (payload.to_string(), "application/json"),
+        WebhookEncoding::Form => {
+            let fields: Vec<(String, String)> = match payload.as_object() {
+                Some(map) => map
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.as_str().map(str::to_string).unwrap_or_else(|| v.to_string())))
+                    .collect(),
+                None => vec![("text".to_string(), message.to_string())],
+            };
+            let encoded = fields
+                .iter()
+                .map(|(k, v)| format!("{}={}", urlencode(k), urlencode(v)))
+                .collect::<Vec<_>>()
+                .join("&");
+            (encoded, "application/x-www-form-urlencoded")
+        }
+        WebhookEncoding::Text => (message.to_string(), "text/plain; charset=utf-8"),
+    };
+
+    let mut req = match options.method {
+        WebhookMethod::Post => client.post(url),
+        WebhookMethod::Put => client.put(url),
+        WebhookMethod::Patch => client.patch(url),
+    };
+
+    // Inserted into one map so a custom header, `Content-Type` included, replaces the default
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    for (name, value) in &options.headers {
+        match (HeaderName::try_from(name.as_str()), HeaderValue::try_from(value.as_str())) {
+            (Ok(name), Ok(value)) => {
+                headers.insert(name, value);
             }
+            _ => println!("Ignoring invalid webhook header '{}'", name),
         }
-        Err(e) => println!("Error sending {} alert: {}", service, e),
+    }
+    req = req.headers(headers);
+
+    if let Some(secret) = options.hmac_secret.as_ref().filter(|s| !s.is_empty()) {
+        match Hmac::<Sha256>::new_from_slice(secret.as_bytes()) {
+            Ok(mut mac) => {
+                mac.update(body.as_bytes());
+                let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+                let header = options.signature_header.as_deref().unwrap_or("X-Volumetrik-Signature");
+                req = req.header(header, signature);
+            }
+            Err(e) => println!("Invalid webhook HMAC secret: {}", e),
+        }
+    }
+
+    req.body(body)
+}
+
+fn urlencode(value: &str) -> String {
+    let mut out = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            b' ' => out.push('+'),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(options: &WebhookOptions) -> HeaderMap {
+        webhook_request(&Client::new(), options, "http://localhost/hook", &json!({ "text": "hi" }), "hi")
+            .build()
+            .unwrap()
+            .headers()
+            .clone()
+    }
+
+    #[test]
+    fn default_content_type() {
+        let sent = headers(&WebhookOptions::default());
+        assert_eq!(sent.get_all(CONTENT_TYPE).iter().collect::<Vec<_>>(), ["application/json"]);
+
+        let form = WebhookOptions { encoding: WebhookEncoding::Form, ..Default::default() };
+        assert_eq!(headers(&form)[CONTENT_TYPE], "application/x-www-form-urlencoded");
+    }
+
+    #[test]
+    fn custom_headers_replace_the_default() {
+        let options = WebhookOptions {
+            headers: [
+                ("content-type".to_string(), "application/vnd.custom+json".to_string()),
+                ("X-Api-Key".to_string(), "key".to_string()),
+                ("Bad Header".to_string(), "ignored".to_string()),
+            ]
+            .into(),
+            ..Default::default()
+        };
+        let sent = headers(&options);
+        assert_eq!(sent.get_all(CONTENT_TYPE).iter().collect::<Vec<_>>(), ["application/vnd.custom+json"]);
+        assert_eq!(sent["x-api-key"], "key");
+        assert_eq!(sent.len(), 2);
+    }
+
+    #[test]
+    fn signs_the_body() {
+        let options = WebhookOptions {
+            hmac_secret: Some("secret".to_string()),
+            signature_header: Some("X-Signature".to_string()),
+            ..Default::default()
+        };
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(br#"{"text":"hi"}"#);
+        let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+        assert_eq!(headers(&options)["x-signature"], expected.as_str());
     }
 }
@@ -0,0 +1,538 @@
+use crate::cleanup::{self, AuditAction, AuditEntry, Target};
+use crate::jobs::JobHandle;
+use crate::models::FileStats;
+use crate::pathmap::mapper;
+use crate::tree::{self, EntryKind, TreeNode};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use tar::EntryType;
+
+pub const EXTENSION: &str = "tar.zst";
+pub const DEFAULT_LEVEL: i32 = 3;
+pub const MAX_LEVEL: i32 = 19;
+
+const CHUNK: usize = 64 * 1024;
+// Progress is reported at least this often while copying a file
+const PROGRESS_BYTES: u64 = 8 * 1024 * 1024;
+const CANCELLED: &str = "Cancelled";
+
+/// Sizes of a directory and of its archive, and what happened to the original.
+#[derive(Debug, Serialize)]
+pub struct ArchiveReport {
+    /// UI path of the archive
+    pub archive: String,
+    pub before: FileStats,
+    pub after: FileStats,
+    pub saved_bytes: u64,
+    /// Archive size over original size
+    pub ratio: f64,
+    pub entries: u64,
+    pub verified: bool,
+    pub original_removed: bool,
+    /// Why the original was kept although it was meant to be removed
+    pub warning: Option<String>,
+}
+
+/// Archive written next to a directory, `dir.tar.zst` for `dir`.
+pub fn destination(dir: &Path) -> PathBuf {
+    let name = dir.file_name().unwrap_or_default().to_string_lossy();
+    dir.with_file_name(format!("{}.{}", name, EXTENSION))
+}
+
+/// What was written for an entry, checked again when verifying.
+#[derive(Debug, PartialEq)]
+struct ManifestEntry {
+    path: String,
+    kind: EntryType,
+    size: u64,
+    link: Option<String>,
+    digest: Option<[u8; 32]>,
+}
+
+/// Packs a directory into a zstd compressed tar, verifies it by reading it back and,
+/// unless `keep_original`, removes the directory once the archive matches it.
+pub fn run(
+    job: &JobHandle,
+    user: &str,
+    ui_path: &str,
+    dir: &Path,
+    level: i32,
+    keep_original: bool,
+) -> Result<ArchiveReport, String> {
+    job.progress(|p| p.phase = "measuring".to_string());
+    let tree = tree::scan_tree(dir).map_err(|e| format!("Error scanning path: {}", e))?;
+    if !tree.is_dir() {
+        return Err("Only directories can be archived".to_string());
+    }
+    if has_special_entries(&tree) {
+        return Err("The directory holds unreadable directories, devices, sockets or pipes, which can't be archived".to_string());
+    }
+    job.progress(|p| {
+        p.bytes_total = tree.size;
+        p.files_total = tree.file_count;
+    });
+
+    let archive = destination(dir);
+    let partial = archive.with_file_name(format!("{}.partial", archive.file_name().unwrap_or_default().to_string_lossy()));
+    let root_name = dir.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+    job.progress(|p| p.phase = "compressing".to_string());
+    let manifest = match write_archive(job, &tree, dir, &root_name, &partial, level.clamp(1, MAX_LEVEL)) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+    };
+    if fs::symlink_metadata(&archive).is_ok() {
+        let _ = fs::remove_file(&partial);
+        return Err(format!("{} appeared while archiving", archive.display()));
+    }
+    fs::rename(&partial, &archive).map_err(|e| format!("Failed to write archive: {}", e))?;
+
+    job.progress(|p| {
+        p.phase = "verifying".to_string();
+        p.bytes_done = 0;
+        p.files_done = 0;
+    });
+    if let Err(e) = verify(job, &archive, &manifest) {
+        let _ = fs::remove_file(&archive);
+        return Err(format!("Verification failed, archive removed: {}", e));
+    }
+    job.progress(|p| {
+        p.bytes_done = p.bytes_total;
+        p.files_done = p.files_total;
+    });
+
+    let archive_size = fs::metadata(&archive).map(|m| m.len()).unwrap_or(0);
+    let archive_ui = mapper().to_ui(&archive.to_string_lossy());
+    let mut report = ArchiveReport {
+        archive: archive_ui.clone(),
+        before: FileStats {
+            path: ui_path.to_string(),
+            name: root_name,
+            is_dir: true,
+            size: tree.size,
+            file_count: tree.file_count,
+            modified: tree.modified,
+        },
+        after: FileStats {
+            path: archive_ui.clone(),
+            name: archive.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            is_dir: false,
+            size: archive_size,
+            file_count: 1,
+            modified: chrono::Utc::now().timestamp().max(0) as u64,
+        },
+        saved_bytes: tree.size.saturating_sub(archive_size),
+        ratio: if tree.size > 0 { archive_size as f64 / tree.size as f64 } else { 0.0 },
+        entries: manifest.len() as u64,
+        verified: true,
+        original_removed: false,
+        warning: None,
+    };
+    if keep_original {
+        return Ok(report);
+    }
+
+    // Checked again, a parent may have been replaced by a symlink while archiving
+    job.progress(|p| p.phase = "removing".to_string());
+    let same_path = match cleanup::resolve_target(&dir.to_string_lossy()) {
+        Ok(path) => path == dir && fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir()),
+        Err(_) => false,
+    };
+    if !same_path {
+        report.warning = Some("The path changed while it was archived, the original was kept".to_string());
+        return Ok(report);
+    }
+
+    // Anything written to the directory since it was archived would be lost
+    let unchanged = tree::scan_tree(dir).is_ok_and(|now| fingerprint(&now) == fingerprint(&tree));
+    if !unchanged {
+        report.warning = Some("The directory changed while it was archived, the original was kept".to_string());
+        return Ok(report);
+    }
+
+    let target = Target {
+        path: ui_path.to_string(),
+        system_path: dir.to_path_buf(),
+        is_dir: true,
+        size: tree.size,
+        files: tree.file_count,
+    };
+    let removed = fs::remove_dir_all(dir)
+        .map(|_| Some(archive.clone()))
+        .map_err(|e| format!("Failed to remove the original: {}", e));
+    cleanup::record(&AuditEntry::new(user, AuditAction::Archive, &target, &removed));
+    match removed {
+        Ok(_) => report.original_removed = true,
+        Err(e) => report.warning = Some(e),
+    }
+    Ok(report)
+}
+
+fn has_special_entries(node: &TreeNode) -> bool {
+    node.kind == EntryKind::Other || node.read_error || node.children.iter().any(has_special_entries)
+}
+
+/// Paths, sizes and modification times of a tree, to notice changes.
+fn fingerprint(tree: &TreeNode) -> Vec<(String, u64, u64)> {
+    let mut entries = Vec::new();
+    tree.walk("", &mut |path, node| entries.push((path.to_string(), node.size, node.modified)));
+    entries.sort();
+    entries
+}
+
+fn sorted_children(node: &TreeNode) -> Vec<&TreeNode> {
+    let mut children: Vec<&TreeNode> = node.children.iter().collect();
+    children.sort_by(|a, b| a.name.cmp(&b.name));
+    children
+}
+
+fn write_archive(
+    job: &JobHandle,
+    tree: &TreeNode,
+    dir: &Path,
+    root_name: &str,
+    partial: &Path,
+    level: i32,
+) -> Result<Vec<ManifestEntry>, String> {
+    let file = File::create(partial).map_err(|e| format!("Failed to create archive: {}", e))?;
+    let encoder = zstd::Encoder::new(BufWriter::new(file), level).map_err(|e| e.to_string())?;
+    let mut builder = tar::Builder::new(encoder);
+    builder.follow_symlinks(false);
+
+    let mut manifest = Vec::new();
+    let mut done = (0u64, 0u64);
+    append(job, &mut builder, tree, dir, Path::new(root_name), &mut manifest, &mut done)?;
+
+    let file = builder
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .and_then(|writer| writer.into_inner().map_err(|e| e.into_error()))
+        .map_err(|e| format!("Failed to write archive: {}", e))?;
+    file.sync_all().map_err(|e| format!("Failed to write archive: {}", e))?;
+    Ok(manifest)
+}
+
+fn append<W: Write>(
+    job: &JobHandle,
+    builder: &mut tar::Builder<W>,
+    node: &TreeNode,
+    system_path: &Path,
+    name: &Path,
+    manifest: &mut Vec<ManifestEntry>,
+    done: &mut (u64, u64),
+) -> Result<(), String> {
+    if job.is_cancelled() {
+        return Err(CANCELLED.to_string());
+    }
+    let io_error = |e: io::Error| {
+        if job.is_cancelled() {
+            CANCELLED.to_string()
+        } else {
+            format!("{}: {}", system_path.display(), e)
+        }
+    };
+    let mut header = tar::Header::new_gnu();
+    let mut entry = ManifestEntry {
+        path: name.to_string_lossy().to_string(),
+        kind: EntryType::Regular,
+        size: 0,
+        link: None,
+        digest: None,
+    };
+
+    match node.kind {
+        EntryKind::Dir => {
+            header.set_metadata(&fs::symlink_metadata(system_path).map_err(io_error)?);
+            header.set_entry_type(EntryType::Directory);
+            header.set_size(0);
+            builder.append_data(&mut header, name, io::empty()).map_err(io_error)?;
+            entry.kind = EntryType::Directory;
+            manifest.push(entry);
+            for child in sorted_children(node) {
+                append(job, builder, child, &system_path.join(&child.name), &name.join(&child.name), manifest, done)?;
+            }
+        }
+        EntryKind::Symlink => {
+            let link = fs::read_link(system_path).map_err(io_error)?;
+            header.set_metadata(&fs::symlink_metadata(system_path).map_err(io_error)?);
+            header.set_entry_type(EntryType::Symlink);
+            header.set_size(0);
+            builder.append_link(&mut header, name, &link).map_err(io_error)?;
+            entry.kind = EntryType::Symlink;
+            entry.link = Some(link.to_string_lossy().to_string());
+            manifest.push(entry);
+        }
+        EntryKind::File => {
+            let file = File::open(system_path).map_err(io_error)?;
+            // The size when opened is what gets archived, the tree may be outdated
+            let metadata = file.metadata().map_err(io_error)?;
+            header.set_metadata(&metadata);
+            header.set_entry_type(EntryType::Regular);
+            header.set_size(metadata.len());
+
+            let mut reader = FileReader {
+                file,
+                remaining: metadata.len(),
+                hasher: Sha256::new(),
+                job,
+                done: done.0,
+                unreported: 0,
+            };
+            builder.append_data(&mut header, name, &mut reader).map_err(io_error)?;
+            done.0 = reader.done + reader.unreported;
+            done.1 += 1;
+            entry.size = metadata.len();
+            entry.digest = Some(reader.hasher.finalize().into());
+            manifest.push(entry);
+
+            let (bytes, files) = *done;
+            job.progress(|p| {
+                p.bytes_done = bytes;
+                p.files_done = files;
+            });
+        }
+        EntryKind::Other => return Err(format!("{}: special files can't be archived", system_path.display())),
+    }
+    Ok(())
+}
+
+/// Reads exactly the size a file had when opened, hashing what it reads.
+/// A file that shrinks meanwhile fails rather than leaving a short entry.
+struct FileReader<'a> {
+    file: File,
+    remaining: u64,
+    hasher: Sha256,
+    job: &'a JobHandle,
+    done: u64,
+    unreported: u64,
+}
+
+impl Read for FileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            return Ok(0);
+        }
+        let max = self.remaining.min(buf.len() as u64) as usize;
+        let read = self.file.read(&mut buf[..max])?;
+        if read == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the file shrank while it was archived"));
+        }
+        self.hasher.update(&buf[..read]);
+        self.remaining -= read as u64;
+        self.unreported += read as u64;
+        if self.unreported >= PROGRESS_BYTES {
+            if self.job.is_cancelled() {
+                return Err(io::Error::other(CANCELLED));
+            }
+            self.done += self.unreported;
+            self.unreported = 0;
+            let bytes = self.done;
+            self.job.progress(|p| p.bytes_done = bytes);
+        }
+        Ok(read)
+    }
+}
+
+/// Reads the archive back and checks it holds exactly the manifest, file contents included.
+fn verify(job: &JobHandle, archive: &Path, manifest: &[ManifestEntry]) -> Result<(), String> {
+    let file = File::open(archive).map_err(|e| e.to_string())?;
+    let mut archive = tar::Archive::new(zstd::Decoder::new(file).map_err(|e| e.to_string())?);
+
+    let mut expected = manifest.iter();
+    let mut buffer = vec![0u8; CHUNK];
+    let (mut bytes_done, mut files_done) = (0u64, 0u64);
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        if job.is_cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        let mut entry = entry.map_err(|e| format!("Unreadable entry: {}", e))?;
+        let kind = entry.header().entry_type();
+        let path = entry.path().map_err(|e| e.to_string())?.to_string_lossy().to_string();
+        let link = entry.link_name().map_err(|e| e.to_string())?.map(|l| l.to_string_lossy().to_string());
+
+        let mut size = 0u64;
+        let mut hasher = Sha256::new();
+        loop {
+            let read = entry.read(&mut buffer).map_err(|e| format!("Unreadable entry {}: {}", path, e))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+
+        let found = ManifestEntry {
+            digest: (kind == EntryType::Regular).then(|| hasher.finalize().into()),
+            path,
+            kind,
+            size,
+            link,
+        };
+        match expected.next() {
+            Some(written) if *written == found => {}
+            Some(written) => return Err(format!("Entry {} doesn't match what was written", written.path)),
+            None => return Err(format!("Unexpected entry {}", found.path)),
+        }
+        if kind == EntryType::Regular {
+            bytes_done += size;
+            files_done += 1;
+            job.progress(|p| {
+                p.bytes_done = bytes_done;
+                p.files_done = files_done;
+            });
+        }
+    }
+
+    match expected.next() {
+        Some(missing) => Err(format!("Entry {} is missing", missing.path)),
+        None => Ok(()),
+    }
+}
+
+// The fixtures hold symlinks and are checked against the system tar
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use std::process::Command;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("volumetrik-archive-{:08x}", rand::random::<u32>()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn fixture(root: &Path) -> PathBuf {
+        let project = root.join("project");
+        let long_dir = project.join("d".repeat(120));
+        fs::create_dir_all(&long_dir).unwrap();
+        fs::create_dir_all(project.join("empty")).unwrap();
+        fs::create_dir_all(project.join("sub")).unwrap();
+        fs::write(long_dir.join(format!("{}.txt", "f".repeat(150))), "long name").unwrap();
+        fs::write(project.join("empty.txt"), "").unwrap();
+        fs::write(project.join("data.bin"), (0..200_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>()).unwrap();
+        symlink("../data.bin", project.join("sub/link")).unwrap();
+        symlink(format!("../{}/{}.txt", "d".repeat(120), "f".repeat(150)), project.join("sub/long-link")).unwrap();
+        project
+    }
+
+    /// Relative paths of a directory with their contents or link targets.
+    fn listing(dir: &Path) -> Vec<(String, String)> {
+        let mut entries = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            for entry in fs::read_dir(&current).unwrap() {
+                let path = entry.unwrap().path();
+                let relative = path.strip_prefix(dir).unwrap().to_string_lossy().to_string();
+                let metadata = fs::symlink_metadata(&path).unwrap();
+                let content = if metadata.is_symlink() {
+                    format!("link {}", fs::read_link(&path).unwrap().display())
+                } else if metadata.is_dir() {
+                    pending.push(path);
+                    "dir".to_string()
+                } else {
+                    format!("{:x}", Sha256::digest(fs::read(&path).unwrap()))
+                };
+                entries.push((relative, content));
+            }
+        }
+        entries.sort();
+        entries
+    }
+
+    fn pack(root: &Path) -> (PathBuf, PathBuf, Vec<ManifestEntry>) {
+        let project = fixture(root);
+        let tree = tree::scan_tree(&project).unwrap();
+        let archive = root.join("project.tar.zst");
+        let manifest = write_archive(&JobHandle::detached(), &tree, &project, "project", &archive, DEFAULT_LEVEL).unwrap();
+        (project, archive, manifest)
+    }
+
+    #[test]
+    fn round_trip() {
+        let root = temp_dir();
+        let (project, archive, manifest) = pack(&root);
+        assert_eq!(manifest.len(), 9);
+        verify(&JobHandle::detached(), &archive, &manifest).unwrap();
+
+        // Unpacked by the tar crate's own extraction
+        let out = root.join("unpacked");
+        tar::Archive::new(zstd::Decoder::new(File::open(&archive).unwrap()).unwrap())
+            .unpack(&out)
+            .unwrap();
+        assert_eq!(listing(&out.join("project")), listing(&project));
+
+        // And by the system tar when it reads zstd
+        let system = root.join("system");
+        fs::create_dir_all(&system).unwrap();
+        let extracted = Command::new("tar")
+            .arg("--zstd")
+            .arg("-xf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&system)
+            .status();
+        if extracted.is_ok_and(|status| status.success()) {
+            assert_eq!(listing(&system.join("project")), listing(&project));
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn verify_rejects_mismatches() {
+        let root = temp_dir();
+        let (_, archive, mut manifest) = pack(&root);
+        let job = JobHandle::detached();
+
+        let file = manifest.iter_mut().find(|e| e.kind == EntryType::Regular && e.size > 0).unwrap();
+        file.digest = Some([0; 32]);
+        assert!(verify(&job, &archive, &manifest).is_err());
+
+        let (_, archive, mut manifest) = pack(&temp_dir_in(&root));
+        manifest.pop();
+        assert!(verify(&job, &archive, &manifest).unwrap_err().starts_with("Unexpected entry"));
+
+        // Truncated archive
+        let (_, archive, manifest) = pack(&temp_dir_in(&root));
+        let bytes = fs::read(&archive).unwrap();
+        fs::write(&archive, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(verify(&job, &archive, &manifest).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    fn temp_dir_in(root: &Path) -> PathBuf {
+        let dir = root.join(format!("{:08x}", rand::random::<u32>()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn destination_is_a_sibling() {
+        assert_eq!(destination(Path::new("/srv/projects/old")), PathBuf::from("/srv/projects/old.tar.zst"));
+    }
+
+    #[test]
+    fn removes_the_original_only_at_the_same_path() {
+        let data = temp_dir_in(&crate::cleanup::test_root().join("data"));
+        let project = fixture(&data.join("plain"));
+        let report = run(&JobHandle::detached(), "admin", "/plain/project", &project, 3, false).unwrap();
+        assert!(report.original_removed, "{:?}", report.warning);
+        assert!(!project.exists());
+        assert!(destination(&project).exists());
+
+        // The parent was swapped for a symlink once the request resolved the path
+        fixture(&data.join("moved"));
+        symlink(data.join("moved"), data.join("swapped")).unwrap();
+        let swapped = data.join("swapped/project");
+        let report = run(&JobHandle::detached(), "admin", "/swapped/project", &swapped, 3, false).unwrap();
+        assert!(!report.original_removed);
+        assert_eq!(report.warning.as_deref(), Some("The path changed while it was archived, the original was kept"));
+        assert!(data.join("moved/project/data.bin").exists());
+    }
+}
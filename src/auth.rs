@@ -0,0 +1,550 @@
+use crate::settings::data_file;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::ErrorUnauthorized;
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::future::{ready, Ready};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+// Kept apart from settings.json: the UI reads and rewrites that file as a whole
+const AUTH_FILE: &str = "auth.json";
+pub const SESSION_COOKIE: &str = "volumetrik_session";
+const TOKEN_PREFIX: &str = "vtk_";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Default, clap::ValueEnum)]
+pub enum Role {
+    /// Can scan and browse
+    Viewer,
+    /// Can also change settings, monitoring and alert channels.
+    /// Default for entries created before roles existed, they had full access
+    #[default]
+    Admin,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct User {
+    pub username: String,
+    /// Argon2 hash in PHC string format
+    pub password_hash: String,
+    #[serde(default)]
+    pub role: Role,
+    /// Path prefixes the user is restricted to, overrides the prefixes of the role
+    #[serde(default)]
+    pub paths: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApiToken {
+    pub name: String,
+    /// SHA-256 of the token, the token itself is only shown when it is created
+    pub hash: String,
+    #[serde(default)]
+    pub role: Role,
+    #[serde(default)]
+    pub paths: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProxyAuth {
+    /// Header holding the authenticated user name, e.g. `X-Forwarded-User`
+    pub header: String,
+    /// Peers allowed to set the header. Requests over a Unix socket are always trusted
+    #[serde(default = "default_trusted_proxies")]
+    pub trusted_proxies: Vec<String>,
+    /// Role of proxy users without a local account of the same name
+    #[serde(default = "default_proxy_role")]
+    pub default_role: Role,
+}
+
+fn default_proxy_role() -> Role {
+    Role::Viewer
+}
+
+fn default_trusted_proxies() -> Vec<String> {
+    vec!["127.0.0.1".to_string(), "::1".to_string()]
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuthConfig {
+    #[serde(default)]
+    pub users: Vec<User>,
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+    #[serde(default)]
+    pub proxy: Option<ProxyAuth>,
+    /// Path prefixes each role is restricted to, a role without an entry sees everything
+    #[serde(default)]
+    pub role_paths: HashMap<Role, Vec<String>>,
+    #[serde(default = "default_session_hours")]
+    pub session_hours: u64,
+}
+
+fn default_session_hours() -> u64 {
+    24
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            users: Vec::new(),
+            tokens: Vec::new(),
+            proxy: None,
+            role_paths: HashMap::new(),
+            session_hours: default_session_hours(),
+        }
+    }
+}
+
+impl AuthConfig {
+    /// Authentication is required as soon as any way to authenticate is configured.
+    pub fn enabled(&self) -> bool {
+        !self.users.is_empty() || !self.tokens.is_empty() || self.proxy.is_some()
+    }
+
+    fn identity(&self, username: &str, method: AuthMethod, role: Role, paths: &[String]) -> Identity {
+        let paths = if paths.is_empty() {
+            self.role_paths.get(&role).cloned().unwrap_or_default()
+        } else {
+            paths.to_vec()
+        };
+        Identity {
+            username: username.to_string(),
+            method,
+            role,
+            paths,
+        }
+    }
+}
+
+/// Reads the auth file. A missing file means authentication is not configured, any other
+/// error must not be mistaken for that.
+pub fn load_config() -> Result<AuthConfig, String> {
+    read_config(&data_file(AUTH_FILE))
+}
+
+fn read_config(path: &Path) -> Result<AuthConfig, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| format!("Error parsing auth file '{}': {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(AuthConfig::default()),
+        Err(e) => Err(format!("Error reading auth file '{}': {}", path.display(), e)),
+    }
+}
+
+pub fn save_config(config: &AuthConfig) -> std::io::Result<()> {
+    write_config(&data_file(AUTH_FILE), config)
+}
+
+/// Writes the file next to its final place then renames it, the running server must never
+/// read a half-written file.
+fn write_config(path: &Path, config: &AuthConfig) -> std::io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let temp = path.with_file_name(format!(".{}.tmp", name));
+    fs::write(&temp, serde_json::to_string_pretty(config)?)?;
+
+    // Password hashes and token digests, keep them away from other local users
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&temp, fs::Permissions::from_mode(0o600))?;
+    }
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+}
+
+fn random_hex(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    OsRng.fill_bytes(&mut buf);
+    hex::encode(buf)
+}
+
+fn sha256_hex(value: &str) -> String {
+    hex::encode(Sha256::digest(value.as_bytes()))
+}
+
+/// Creates a new API token, returning the token to hand out and the entry to store.
+pub fn generate_token(name: &str, role: Role, paths: Vec<String>) -> (String, ApiToken) {
+    let token = format!("{}{}", TOKEN_PREFIX, random_hex(32));
+    let entry = ApiToken {
+        name: name.to_string(),
+        hash: sha256_hex(&token),
+        role,
+        paths,
+        created_at: Utc::now(),
+    };
+    (token, entry)
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    Session,
+    Token,
+    Proxy,
+    /// Authentication is not configured
+    Anonymous,
+}
+
+/// Who made the request, stored in the request extensions by the middleware.
+#[derive(Debug, Serialize, Clone)]
+pub struct Identity {
+    pub username: String,
+    pub method: AuthMethod,
+    pub role: Role,
+    /// UI path prefixes the identity is restricted to, empty for no restriction
+    pub paths: Vec<String>,
+}
+
+impl Identity {
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    /// Whether the UI path is inside one of the allowed prefixes.
+    pub fn can_access(&self, path: &str) -> bool {
+        if self.paths.is_empty() {
+            return true;
+        }
+        let Some(path) = normalize(path) else {
+            return false;
+        };
+        self.paths.iter().filter_map(|p| normalize(p)).any(|prefix| path.starts_with(prefix))
+    }
+
+    /// Whether the UI path is an ancestor of an allowed prefix, so it can be browsed to reach it.
+    pub fn can_traverse(&self, path: &str) -> bool {
+        let Some(path) = normalize(path) else {
+            return false;
+        };
+        self.paths.iter().filter_map(|p| normalize(p)).any(|prefix| prefix.starts_with(&path))
+    }
+}
+
+/// Lexical form of a UI path for prefix checks, `None` when it climbs with `..`.
+fn normalize(path: &str) -> Option<PathBuf> {
+    let path = path.replace('\\', "/");
+    let path = Path::new(&path);
+    if path.components().any(|c| c == Component::ParentDir) {
+        return None;
+    }
+    Some(path.components().filter(|c| *c != Component::CurDir).collect())
+}
+
+impl FromRequest for Identity {
+    type Error = Error;
+    type Future = Ready<Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(req.extensions().get::<Identity>().cloned().ok_or_else(|| ErrorUnauthorized("Authentication required")))
+    }
+}
+
+struct Session {
+    username: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Auth configuration and the sessions of logged-in users.
+pub struct AuthService {
+    // The file is edited by the CLI while the server runs, reloaded when it changes
+    config: Mutex<(AuthConfig, Option<SystemTime>)>,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+pub type SharedAuth = Arc<AuthService>;
+
+impl AuthService {
+    pub fn new() -> Result<SharedAuth, String> {
+        Ok(Arc::new(Self {
+            config: Mutex::new((load_config()?, modified_time())),
+            sessions: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    /// The current configuration, reloaded when the file changes. A file that can't be read
+    /// keeps the last good configuration rather than turning authentication off.
+    pub fn config(&self) -> AuthConfig {
+        let mut config = self.config.lock().unwrap();
+        let modified = modified_time();
+        if modified != config.1 {
+            config.1 = modified;
+            match load_config() {
+                Ok(loaded) => config.0 = loaded,
+                Err(e) => println!("{}, keeping the previous authentication settings", e),
+            }
+        }
+        config.0.clone()
+    }
+
+    /// Checks the credentials and opens a session, returning its id.
+    pub fn login(&self, username: &str, password: &str) -> Option<String> {
+        let config = self.config();
+        let user = config.users.iter().find(|u| u.username == username)?;
+        if !verify_password(password, &user.password_hash) {
+            return None;
+        }
+
+        let id = random_hex(32);
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Utc::now();
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(
+            id.clone(),
+            Session {
+                username: user.username.clone(),
+                expires_at: now + Duration::hours(config.session_hours as i64),
+            },
+        );
+        Some(id)
+    }
+
+    pub fn logout(&self, session_id: &str) {
+        self.sessions.lock().unwrap().remove(session_id);
+    }
+
+    fn session_user<'a>(&self, session_id: &str, config: &'a AuthConfig) -> Option<&'a User> {
+        let sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(session_id).filter(|s| s.expires_at > Utc::now())?;
+        // Removing a user ends their sessions, role changes apply right away
+        config.users.iter().find(|u| u.username == session.username)
+    }
+
+    /// Identifies the request, `None` when authentication is required and missing.
+    pub fn identify(&self, req: &ServiceRequest) -> Option<Identity> {
+        let config = self.config();
+        if !config.enabled() {
+            return Some(config.identity("anonymous", AuthMethod::Anonymous, Role::Admin, &[]));
+        }
+
+        if let Some(token) = req
+            .headers()
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+        {
+            let hash = sha256_hex(token.trim());
+            return config
+                .tokens
+                .iter()
+                .find(|t| t.hash == hash)
+                .map(|t| config.identity(&t.name, AuthMethod::Token, t.role, &t.paths));
+        }
+
+        if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+            if let Some(user) = self.session_user(cookie.value(), &config) {
+                return Some(config.identity(&user.username, AuthMethod::Session, user.role, &user.paths));
+            }
+        }
+
+        if let Some(proxy) = &config.proxy {
+            let trusted = match req.peer_addr() {
+                Some(addr) => proxy.trusted_proxies.iter().any(|p| p.parse() == Ok(addr.ip())),
+                None => true,
+            };
+            let user = req.headers().get(proxy.header.as_str()).and_then(|v| v.to_str().ok());
+            if let Some(user) = user.map(str::trim).filter(|u| trusted && !u.is_empty()) {
+                // A local account of the same name gives the proxy user its role and paths
+                return Some(match config.users.iter().find(|u| u.username == user) {
+                    Some(local) => config.identity(user, AuthMethod::Proxy, local.role, &local.paths),
+                    None => config.identity(user, AuthMethod::Proxy, proxy.default_role, &[]),
+                });
+            }
+        }
+
+        None
+    }
+}
+
+fn modified_time() -> Option<SystemTime> {
+    fs::metadata(data_file(AUTH_FILE)).and_then(|m| m.modified()).ok()
+}
+
+/// Paths reachable without authentication: the web interface assets, the login endpoint
+/// and the health check.
+fn is_public(path: &str) -> bool {
+    let api = path.starts_with("/api/") || path == "/metrics";
+    !api || matches!(path, "/api/health" | "/api/auth/login" | "/api/auth/me")
+}
+
+/// API calls that change something, only administrators may make them.
+fn requires_admin(req: &ServiceRequest) -> bool {
+    let path = req.path();
+    if !path.starts_with("/api/") || path.starts_with("/api/auth/") {
+        return false;
+    }
+    // The digest preview lists every monitored path
+    req.method() != Method::GET || path == "/api/digest/preview"
+}
+
+/// Middleware rejecting unauthenticated API requests and recording the identity of the others.
+pub async fn require_auth(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody + 'static>>, Error> {
+    let identity = req
+        .app_data::<web::Data<SharedAuth>>()
+        .and_then(|auth| auth.identify(&req));
+
+    match identity {
+        Some(identity) => {
+            if requires_admin(&req) && !identity.is_admin() {
+                let response = HttpResponse::Forbidden().json("Administrator role required");
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+            req.extensions_mut().insert(identity);
+        }
+        None if is_public(req.path()) => {}
+        None => {
+            let response = HttpResponse::Unauthorized().json("Authentication required");
+            return Ok(req.into_response(response).map_into_right_body());
+        }
+    }
+
+    next.call(req).await.map(|res| res.map_into_left_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restricted(paths: &[&str]) -> Identity {
+        Identity {
+            username: "user".to_string(),
+            method: AuthMethod::Token,
+            role: Role::Viewer,
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn normalizes_lexically() {
+        assert_eq!(normalize("/data/./a/"), Some(PathBuf::from("/data/a")));
+        assert_eq!(normalize("/data//a"), Some(PathBuf::from("/data/a")));
+        assert_eq!(normalize("\\data\\a"), Some(PathBuf::from("/data/a")));
+        assert_eq!(normalize("/data/a/../b"), None);
+        assert_eq!(normalize("/data/a..b"), Some(PathBuf::from("/data/a..b")));
+    }
+
+    #[test]
+    fn prefixes_match_whole_components() {
+        let identity = restricted(&["/data/a"]);
+        assert!(identity.can_access("/data/a"));
+        assert!(identity.can_access("/data/a/b/c"));
+        assert!(!identity.can_access("/data/ab"));
+        assert!(!identity.can_access("/data/ab/c"));
+        assert!(!identity.can_access("/data"));
+        assert!(!identity.can_access("/"));
+    }
+
+    #[test]
+    fn parent_components_are_refused() {
+        let identity = restricted(&["/data/a"]);
+        assert!(!identity.can_access("/data/a/../b"));
+        assert!(!identity.can_access("/data/a/.."));
+        assert!(!identity.can_traverse("/data/a/../.."));
+        // A prefix climbing out is ignored rather than allowing everything
+        assert!(!restricted(&["/data/../"]).can_access("/etc"));
+    }
+
+    #[test]
+    fn trailing_slashes_and_dots() {
+        let identity = restricted(&["/data/a/"]);
+        assert!(identity.can_access("/data/a"));
+        assert!(identity.can_access("/data/a/"));
+        assert!(identity.can_access("/data/./a/b/"));
+        assert!(!identity.can_access("/data/ab/"));
+    }
+
+    #[test]
+    fn root_prefix_and_no_prefix() {
+        let root = restricted(&["/"]);
+        assert!(root.can_access("/"));
+        assert!(root.can_access("/anything/below"));
+        assert!(!root.can_access("/data/../etc"));
+        assert!(restricted(&[]).can_access("/anything"));
+    }
+
+    #[test]
+    fn traverses_only_towards_the_prefixes() {
+        let identity = restricted(&["/data/a/b", "/srv/x"]);
+        assert!(identity.can_traverse("/"));
+        assert!(identity.can_traverse("/data"));
+        assert!(identity.can_traverse("/data/a/"));
+        assert!(identity.can_traverse("/srv"));
+        assert!(!identity.can_traverse("/data/ab"));
+        assert!(!identity.can_traverse("/data/a/c"));
+        assert!(!identity.can_traverse("/etc"));
+        // Inside a prefix is access, not traversal
+        assert!(!identity.can_traverse("/data/a/b/c"));
+        assert!(identity.can_access("/data/a/b/c"));
+    }
+
+    #[test]
+    fn config_file_errors_fail_closed() {
+        let dir = std::env::temp_dir().join(format!("volumetrik-auth-{:08x}", rand::random::<u32>()));
+        let path = dir.join("auth.json");
+        // Only a missing file means no authentication
+        assert!(!read_config(&path).unwrap().enabled());
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "{\"users\": [").unwrap();
+        assert!(read_config(&path).unwrap_err().starts_with("Error parsing auth file"));
+        fs::create_dir(dir.join("folder.json")).unwrap();
+        assert!(read_config(&dir.join("folder.json")).unwrap_err().starts_with("Error reading auth file"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn config_file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("volumetrik-auth-{:08x}", rand::random::<u32>()));
+        let path = dir.join("auth.json");
+        let mut config = AuthConfig::default();
+        config.tokens.push(generate_token("ci", Role::Viewer, vec!["/data".to_string()]).1);
+        write_config(&path, &config).unwrap();
+        config.tokens.push(generate_token("backup", Role::Admin, Vec::new()).1);
+        write_config(&path, &config).unwrap();
+
+        let loaded = read_config(&path).unwrap();
+        assert!(loaded.enabled());
+        let names: Vec<&str> = loaded.tokens.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["ci", "backup"]);
+        // The temporary file is renamed over the original
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
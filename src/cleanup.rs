@@ -0,0 +1,474 @@
+use crate::pathmap::mapper;
+use crate::roots::{self, PathError};
+use crate::scan::calculate_size;
+use crate::settings::{config_file, data_file};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+const AUDIT_FILE: &str = "cleanup_audit.jsonl";
+
+/// How long a preview token can be confirmed
+const TOKEN_LIFETIME_MINUTES: i64 = 10;
+
+// Serializes writers, the API handlers and the retention policies share the file
+static AUDIT_LOCK: Mutex<()> = Mutex::new(());
+
+static CONFIG: OnceLock<CleanupConfig> = OnceLock::new();
+
+/// Write support, off unless the server is started with `--allow-writes`.
+#[derive(Debug, Clone, Default)]
+pub struct CleanupConfig {
+    pub enabled: bool,
+    /// Where trashed entries are moved, system path
+    pub trash_dir: Option<PathBuf>,
+}
+
+pub fn configure(config: CleanupConfig) {
+    let _ = CONFIG.set(config);
+}
+
+pub fn config() -> &'static CleanupConfig {
+    CONFIG.get_or_init(CleanupConfig::default)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CleanupAction {
+    /// Removes the entry for good
+    #[default]
+    Delete,
+    /// Moves the entry to the trash directory
+    Trash,
+}
+
+/// What an audit entry records: a cleanup action, or a directory replaced by its archive.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Delete,
+    Trash,
+    Archive,
+}
+
+impl From<CleanupAction> for AuditAction {
+    fn from(action: CleanupAction) -> Self {
+        match action {
+            CleanupAction::Delete => AuditAction::Delete,
+            CleanupAction::Trash => AuditAction::Trash,
+        }
+    }
+}
+
+/// An entry to remove, measured when previewed.
+#[derive(Debug, Serialize, Clone)]
+pub struct Target {
+    /// UI path
+    pub path: String,
+    #[serde(skip)]
+    pub system_path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+    pub files: u64,
+}
+
+/// Resolves the entry at a system path for removal. Only its parent is canonicalized,
+/// so a symlink is removed itself rather than what it points to.
+pub fn resolve_target(system_path: &str) -> Result<PathBuf, PathError> {
+    let path = Path::new(system_path);
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(PathError::Forbidden("Refusing to remove a root directory".to_string()));
+    };
+    let target = roots::resolve(&parent.to_string_lossy())?.join(name);
+    if fs::symlink_metadata(&target).is_err() {
+        return Err(PathError::NotFound(format!("Path not found: {}", system_path)));
+    }
+    if !roots::is_allowed(&target) {
+        return Err(PathError::Forbidden("Access to this path is not allowed".to_string()));
+    }
+
+    let protected = roots::allowed_roots()
+        .iter()
+        .cloned()
+        .chain(mapper().mappings().iter().map(|m| PathBuf::from(&m.system)));
+    for root in protected {
+        if roots::canonicalize(&root).is_ok_and(|root| root.starts_with(&target)) {
+            return Err(PathError::Forbidden("Refusing to remove an allowed root or a directory holding one".to_string()));
+        }
+    }
+    let data_dir = config_file().parent().and_then(|dir| roots::canonicalize(dir).ok());
+    if data_dir.is_some_and(|dir| dir.starts_with(&target)) {
+        return Err(PathError::Forbidden("Refusing to remove Volumetrik's data directory".to_string()));
+    }
+    let trash_dir = config().trash_dir.as_ref().and_then(|dir| roots::canonicalize(dir).ok());
+    if trash_dir.is_some_and(|dir| dir.starts_with(&target)) {
+        return Err(PathError::Forbidden("Refusing to remove the trash directory".to_string()));
+    }
+    Ok(target)
+}
+
+/// Size and file count of the entry, the way a scan counts them.
+pub fn measure(ui_path: &str, system_path: PathBuf) -> io::Result<Target> {
+    let metadata = fs::symlink_metadata(&system_path)?;
+    let (size, files) = if metadata.is_dir() { calculate_size(&system_path) } else { (metadata.len(), 1) };
+    Ok(Target {
+        path: ui_path.to_string(),
+        is_dir: metadata.is_dir(),
+        system_path,
+        size,
+        files,
+    })
+}
+
+/// Removes or trashes an entry, returning where a trashed entry was moved.
+/// Trashed entries of one run share a batch directory named after `batch`.
+pub fn apply(action: CleanupAction, target: &Target, batch: &str) -> Result<Option<PathBuf>, String> {
+    // Checked again, a parent may have been replaced by a symlink since the preview
+    match resolve_target(&target.system_path.to_string_lossy()) {
+        Ok(path) if path == target.system_path => {}
+        Ok(_) => return Err("The path changed since the preview".to_string()),
+        Err(e) => return Err(e.to_string()),
+    }
+    let metadata = fs::symlink_metadata(&target.system_path).map_err(|e| format!("Path not found: {}", e))?;
+    if metadata.is_dir() != target.is_dir {
+        return Err("The entry changed type since the preview".to_string());
+    }
+
+    match action {
+        CleanupAction::Delete => {
+            let removed = if metadata.is_dir() {
+                fs::remove_dir_all(&target.system_path)
+            } else {
+                fs::remove_file(&target.system_path)
+            };
+            removed.map(|_| None).map_err(|e| format!("Failed to delete: {}", e))
+        }
+        CleanupAction::Trash => {
+            let trash_dir = config().trash_dir.as_ref().ok_or("No trash directory configured")?;
+            let batch_dir = trash_dir.join(batch);
+            fs::create_dir_all(&batch_dir).map_err(|e| format!("Failed to create trash directory: {}", e))?;
+
+            let name = target.system_path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let mut destination = batch_dir.join(&name);
+            let mut n = 1;
+            while fs::symlink_metadata(&destination).is_ok() {
+                destination = batch_dir.join(format!("{}.{}", name, n));
+                n += 1;
+            }
+            // A rename can't cross volumes, the trash has to be on the same one
+            fs::rename(&target.system_path, &destination)
+                .map(|_| Some(destination))
+                .map_err(|e| format!("Failed to move to trash: {}", e))
+        }
+    }
+}
+
+/// Name of the trash directory of a run.
+pub fn batch_name() -> String {
+    format!("{}-{:04x}", Utc::now().format("%Y%m%d-%H%M%S"), rand::random::<u16>())
+}
+
+/// A preview waiting for confirmation.
+struct Pending {
+    username: String,
+    action: CleanupAction,
+    targets: Vec<Target>,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Preview {
+    /// Confirms the action, once and before `expires_at`
+    pub token: String,
+    pub action: CleanupAction,
+    pub targets: Vec<Target>,
+    pub total_size: u64,
+    pub total_files: u64,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Previews waiting for confirmation, by token.
+#[derive(Default)]
+pub struct CleanupService {
+    pending: Mutex<HashMap<String, Pending>>,
+}
+
+pub type SharedCleanup = Arc<CleanupService>;
+
+impl CleanupService {
+    pub fn new() -> SharedCleanup {
+        Arc::new(Self::default())
+    }
+
+    pub fn preview(&self, username: &str, action: CleanupAction, targets: Vec<Target>) -> Preview {
+        let token = format!("{:032x}", rand::random::<u128>());
+        let expires_at = Utc::now() + Duration::minutes(TOKEN_LIFETIME_MINUTES);
+        let preview = Preview {
+            token: token.clone(),
+            action,
+            total_size: targets.iter().map(|t| t.size).sum(),
+            total_files: targets.iter().map(|t| t.files).sum(),
+            targets: targets.clone(),
+            expires_at,
+        };
+
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|_, p| p.expires_at > Utc::now());
+        pending.insert(
+            token,
+            Pending {
+                username: username.to_string(),
+                action,
+                targets,
+                expires_at,
+            },
+        );
+        preview
+    }
+
+    /// Takes the preview of a token, which only the user who asked for it can confirm.
+    pub fn confirm(&self, token: &str, username: &str) -> Result<(CleanupAction, Vec<Target>), String> {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.get(token) {
+            Some(p) if p.expires_at > Utc::now() && p.username == username => {}
+            _ => return Err("Unknown or expired confirmation token, preview the action again".to_string()),
+        }
+        let p = pending.remove(token).unwrap();
+        Ok((p.action, p.targets))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CleanupReport {
+    pub action: CleanupAction,
+    pub results: Vec<AuditEntry>,
+    /// Bytes and files removed or trashed successfully
+    pub total_size: u64,
+    pub total_files: u64,
+}
+
+/// Applies the action to every target, recording each one in the audit log.
+pub fn run(user: &str, action: CleanupAction, targets: &[Target]) -> CleanupReport {
+    let batch = batch_name();
+    let results: Vec<AuditEntry> = targets
+        .iter()
+        .map(|target| {
+            let result = apply(action, target, &batch);
+            let entry = AuditEntry::new(user, action.into(), target, &result);
+            match &result {
+                Ok(_) => println!("Cleanup: {} {:?} '{}' ({} bytes)", user, action, target.path, target.size),
+                Err(e) => println!("Cleanup: {} {:?} '{}' failed: {}", user, action, target.path, e),
+            }
+            record(&entry);
+            entry
+        })
+        .collect();
+
+    let succeeded = results.iter().filter(|r| r.success);
+    CleanupReport {
+        action,
+        total_size: succeeded.clone().map(|r| r.size).sum(),
+        total_files: succeeded.map(|r| r.files).sum(),
+        results,
+    }
+}
+
+/// One entry removed or trashed, successfully or not.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// User who confirmed the action, or the policy that ran it
+    pub user: String,
+    pub action: AuditAction,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub files: u64,
+    /// Where a trashed entry was moved or an archived one was written, UI path
+    pub destination: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(user: &str, action: AuditAction, target: &Target, result: &Result<Option<PathBuf>, String>) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            user: user.to_string(),
+            action,
+            path: target.path.clone(),
+            is_dir: target.is_dir,
+            size: target.size,
+            files: target.files,
+            destination: result
+                .as_ref()
+                .ok()
+                .and_then(|d| d.as_ref())
+                .map(|d| mapper().to_ui(&d.to_string_lossy())),
+            success: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+        }
+    }
+}
+
+pub fn record(entry: &AuditEntry) {
+    let _guard = AUDIT_LOCK.lock().unwrap();
+    let path = data_file(AUDIT_FILE);
+
+    let appended = serde_json::to_string(entry).map_err(io::Error::from).and_then(|line| {
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{}", line)
+    });
+    if let Err(e) = appended {
+        println!("Failed to write cleanup audit log '{}': {}", path.display(), e);
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct AuditQuery {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditResponse {
+    pub total: usize,
+    pub entries: Vec<AuditEntry>,
+}
+
+/// Audit entries, newest first.
+pub fn query(q: &AuditQuery) -> AuditResponse {
+    let content = {
+        let _guard = AUDIT_LOCK.lock().unwrap();
+        fs::read_to_string(data_file(AUDIT_FILE)).unwrap_or_default()
+    };
+    let entries: Vec<AuditEntry> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+
+    let total = entries.len();
+    let entries = entries
+        .into_iter()
+        .rev()
+        .skip(q.offset.unwrap_or(0))
+        .take(q.limit.unwrap_or(100))
+        .collect();
+    AuditResponse { total, entries }
+}
+
+/// Allowed root with a trash and a data directory, shared by the tests as the configuration is global.
+#[cfg(test)]
+pub fn test_root() -> &'static Path {
+    static ROOT: OnceLock<PathBuf> = OnceLock::new();
+    ROOT.get_or_init(|| {
+        let base = std::env::temp_dir().join(format!("volumetrik-cleanup-{:08x}", rand::random::<u32>()));
+        let root = base.join("root");
+        for dir in ["data", "trash", "config"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::create_dir_all(base.join("outside")).unwrap();
+        roots::set_allowed_roots(std::slice::from_ref(&root));
+        crate::settings::set_config_file(root.join("config/settings.json"));
+        configure(CleanupConfig {
+            enabled: true,
+            trash_dir: Some(root.join("trash")),
+        });
+        roots::canonicalize(&root).unwrap()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::os::unix::fs::symlink;
+
+    fn entry(name: &str) -> PathBuf {
+        let path = test_root().join("data").join(name);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("file"), "content").unwrap();
+        path
+    }
+
+    fn forbidden(path: &Path) -> bool {
+        matches!(resolve_target(&path.to_string_lossy()), Err(PathError::Forbidden(_)))
+    }
+
+    #[test]
+    fn refuses_protected_paths() {
+        let root = test_root();
+        assert!(forbidden(Path::new("/")));
+        assert!(forbidden(root));
+        assert!(forbidden(root.parent().unwrap()));
+        assert!(forbidden(&root.join("trash")));
+        assert!(forbidden(&root.join("config")));
+        assert!(forbidden(&root.parent().unwrap().join("outside")));
+        assert!(matches!(resolve_target(&root.join("data/missing").to_string_lossy()), Err(PathError::NotFound(_))));
+
+        let dir = entry("plain");
+        assert_eq!(resolve_target(&dir.to_string_lossy()).unwrap(), dir);
+        assert_eq!(resolve_target(&root.join("trash/../data/plain").to_string_lossy()).unwrap(), dir);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn only_the_parent_is_canonicalized() {
+        let root = test_root();
+        let real = entry("real");
+        let link = root.join("data/link");
+        symlink(&real, &link).unwrap();
+        let outside_link = root.join("data/outside-link");
+        symlink(root.parent().unwrap().join("outside"), &outside_link).unwrap();
+
+        // A symlink is removed itself, wherever it points
+        assert_eq!(resolve_target(&link.to_string_lossy()).unwrap(), link);
+        assert_eq!(resolve_target(&outside_link.to_string_lossy()).unwrap(), outside_link);
+        // But a path through it is resolved, and refused when it leads outside the roots
+        assert_eq!(resolve_target(&link.join("file").to_string_lossy()).unwrap(), real.join("file"));
+        assert!(forbidden(&outside_link.join("anything")));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn apply_checks_the_path_again() {
+        let dir = entry("swapped");
+        let target = measure("/data/swapped/file", resolve_target(&dir.join("file").to_string_lossy()).unwrap()).unwrap();
+        assert_eq!((target.size, target.files, target.is_dir), (7, 1, false));
+
+        // The parent is replaced by a symlink after the preview
+        let moved = test_root().join("data/swapped-real");
+        fs::rename(&dir, &moved).unwrap();
+        symlink(&moved, &dir).unwrap();
+        assert!(apply(CleanupAction::Delete, &target, "batch").is_err());
+        assert!(moved.join("file").exists());
+
+        let kept = measure("/data/kept", entry("kept")).unwrap();
+        let destination = apply(CleanupAction::Trash, &kept, "batch").unwrap().unwrap();
+        assert!(destination.starts_with(test_root().join("trash/batch")));
+        assert!(destination.join("file").exists());
+    }
+
+    #[test]
+    fn tokens_are_used_once_by_their_user() {
+        let service = CleanupService::new();
+        let target = measure("/data/token", entry("token")).unwrap();
+
+        let preview = service.preview("alice", CleanupAction::Trash, vec![target.clone()]);
+        assert_eq!((preview.total_size, preview.total_files), (7, 1));
+        assert!(service.confirm(&preview.token, "bob").is_err());
+        let (action, targets) = service.confirm(&preview.token, "alice").unwrap();
+        assert_eq!((action, targets.len()), (CleanupAction::Trash, 1));
+        assert!(service.confirm(&preview.token, "alice").is_err());
+
+        let expired = service.preview("alice", CleanupAction::Delete, vec![target]);
+        service.pending.lock().unwrap().get_mut(&expired.token).unwrap().expires_at = Utc::now() - Duration::seconds(1);
+        assert!(service.confirm(&expired.token, "alice").is_err());
+        assert!(service.confirm("unknown", "alice").is_err());
+    }
+}
@@ -0,0 +1,580 @@
+use crate::alerts::{self, DeliveryResult};
+use crate::auth::{self, Role};
+use crate::digest::format_bytes;
+use crate::export::{self, csv_field, ExportFormat};
+use crate::imports;
+use crate::models::FileStats;
+use crate::monitor;
+use crate::pathmap::{mapper, Mapping, DEFAULT_HOST_ROOT};
+use crate::scan::scan_path;
+use crate::settings::{load_settings, AlertChannel, Severity, DEFAULT_CONFIG_FILE};
+use crate::tree;
+use chrono::{DateTime, Local};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use sysinfo::Disks;
+
+/// Disk usage analyzer and monitor.
+#[derive(Debug, Parser)]
+#[command(name = "volumetrik", version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    /// Settings file. Alert history and digest state are stored in the same directory
+    #[arg(long, global = true, env = "VOLUMETRIK_SETTINGS", default_value = DEFAULT_CONFIG_FILE)]
+    pub settings: PathBuf,
+
+    /// Where the host filesystem is mounted in Docker mode, shown as `/`. Ignored when --path-map is given
+    #[arg(long, global = true, env = "VOLUMETRIK_HOST_ROOT", default_value = DEFAULT_HOST_ROOT)]
+    pub host_root: String,
+
+    /// Shows a system directory under another path, as SYSTEM=DISPLAY (e.g. /mnt/nas=NAS:/).
+    /// Repeat the flag or separate with commas for several
+    #[arg(long = "path-map", global = true, env = "VOLUMETRIK_PATH_MAP", value_delimiter = ',', value_parser = Mapping::parse)]
+    pub path_maps: Vec<Mapping>,
+
+    #[command(flatten)]
+    pub serve: ServeArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ServeArgs {
+    /// Address to listen on: host:port, [ipv6]:port, a bare address (uses --port)
+    /// or unix:/path/to/socket. Repeat the flag or separate with commas for several
+    #[arg(short, long, env = "VOLUMETRIK_LISTEN", value_delimiter = ',', default_value = "0.0.0.0")]
+    pub listen: Vec<String>,
+
+    /// Port used for listen addresses without one
+    #[arg(short, long, env = "VOLUMETRIK_PORT", default_value_t = 8080)]
+    pub port: u16,
+
+    /// Directory of the web interface assets
+    #[arg(long, env = "VOLUMETRIK_STATIC_DIR", default_value = "static")]
+    pub static_dir: PathBuf,
+
+    /// Only allow browsing and scanning inside this directory (system path), can be repeated.
+    /// Defaults to the mapped directories when paths are mapped and to the whole filesystem otherwise
+    #[arg(long = "allowed-root", env = "VOLUMETRIK_ALLOWED_ROOTS", value_delimiter = ',')]
+    pub allowed_roots: Vec<PathBuf>,
+
+    /// Allow administrators to delete entries or move them to the trash directory.
+    /// The filesystem has to be writable, the Docker setup mounts /host read-only by default
+    #[arg(long, env = "VOLUMETRIK_ALLOW_WRITES")]
+    pub allow_writes: bool,
+
+    /// Directory trashed entries are moved to (system path). It must be on the same volume as them
+    #[arg(long, env = "VOLUMETRIK_TRASH_DIR")]
+    pub trash_dir: Option<PathBuf>,
+}
+
+/// A resolved listen address.
+#[derive(Debug, Clone)]
+pub enum Listen {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl ServeArgs {
+    pub fn addresses(&self) -> Vec<Listen> {
+        self.listen.iter().map(|l| parse_listen(l.trim(), self.port)).collect()
+    }
+}
+
+fn parse_listen(value: &str, port: u16) -> Listen {
+    if let Some(path) = value.strip_prefix("unix:") {
+        return Listen::Unix(PathBuf::from(path));
+    }
+    if value.parse::<SocketAddr>().is_ok() {
+        return Listen::Tcp(value.to_string());
+    }
+    // Bare IPv4/IPv6 address, with or without brackets
+    let bare = value.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = bare.parse::<IpAddr>() {
+        return Listen::Tcp(SocketAddr::new(ip, port).to_string());
+    }
+    // Host name, with or without a port
+    if value.contains(':') {
+        Listen::Tcp(value.to_string())
+    } else {
+        Listen::Tcp(format!("{}:{}", value, port))
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Start the web server and background monitoring (default)
+    Serve(ServeArgs),
+    /// Scan a folder and print its contents
+    Scan {
+        /// Folder to scan
+        path: String,
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+        /// Only print the N largest entries
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Export the whole tree below a folder
+    Export {
+        /// Folder to export
+        path: String,
+        #[arg(short, long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Import an ncdu JSON dump (`ncdu -o`) so it can be browsed in the dashboard
+    Import {
+        /// Dump file, `-` for stdin
+        file: PathBuf,
+        /// Name shown in the dashboard, defaults to the root path of the dump
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+    /// Run one monitoring pass and exit with a Nagios-style status code
+    /// (0 OK, 1 warning, 2 critical, 3 unknown)
+    Check {
+        /// Only check this monitored path
+        #[arg(short, long)]
+        path: Option<String>,
+        /// Evaluate thresholds without sending alerts
+        #[arg(long)]
+        no_alerts: bool,
+    },
+    /// Send a test notification through the configured alert channels
+    TestAlert {
+        /// Only test this channel
+        #[arg(short, long, value_enum)]
+        channel: Option<AlertChannel>,
+        /// Message to send instead of the default one
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+    /// Manage the local users of the web interface
+    User {
+        #[command(subcommand)]
+        action: UserAction,
+    },
+    /// Manage API tokens for automation
+    Token {
+        #[command(subcommand)]
+        action: TokenAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UserAction {
+    /// Add a user, or update an existing one. The password is read from stdin
+    Add {
+        username: String,
+        #[arg(short, long, value_enum, default_value_t = Role::Viewer)]
+        role: Role,
+        /// Restrict the user to this path prefix, can be repeated
+        #[arg(long = "path")]
+        paths: Vec<String>,
+    },
+    /// Remove a user and end their sessions
+    Remove { username: String },
+    /// List users
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TokenAction {
+    /// Create a token and print it. It can't be shown again
+    Create {
+        name: String,
+        #[arg(short, long, value_enum, default_value_t = Role::Viewer)]
+        role: Role,
+        /// Restrict the token to this path prefix, can be repeated
+        #[arg(long = "path")]
+        paths: Vec<String>,
+    },
+    /// Revoke a token
+    Revoke { name: String },
+    /// List tokens
+    List,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Table,
+    Csv,
+}
+
+// Nagios plugin exit codes
+const EXIT_OK: i32 = 0;
+const EXIT_WARNING: i32 = 1;
+const EXIT_CRITICAL: i32 = 2;
+const EXIT_UNKNOWN: i32 = 3;
+
+pub fn scan(path: &str, format: OutputFormat, limit: Option<usize>) -> i32 {
+    let system_path = mapper().to_system(path);
+    let (mut files, total_size, total_files) = match scan_path(&system_path) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("Failed to scan '{}': {}", path, e);
+            return 1;
+        }
+    };
+
+    files.sort_by_key(|f| std::cmp::Reverse(f.size));
+    if let Some(limit) = limit {
+        files.truncate(limit);
+    }
+    for file in &mut files {
+        file.path = mapper().to_ui(&file.path);
+    }
+
+    match format {
+        OutputFormat::Json => {
+            let output = serde_json::json!({
+                "path": path,
+                "total_size": total_size,
+                "total_files": total_files,
+                "files": files,
+            });
+            println!("{}", serde_json::to_string_pretty(&output).unwrap_or_default());
+        }
+        OutputFormat::Csv => {
+            println!("path,name,is_dir,size,file_count,modified");
+            for f in &files {
+                println!(
+                    "{},{},{},{},{},{}",
+                    csv_field(&f.path),
+                    csv_field(&f.name),
+                    f.is_dir,
+                    f.size,
+                    f.file_count,
+                    f.modified
+                );
+            }
+        }
+        OutputFormat::Table => print_table(path, &files, total_size, total_files),
+    }
+
+    0
+}
+
+fn print_table(path: &str, files: &[FileStats], total_size: u64, total_files: u64) {
+    let name_width = files.iter().map(|f| f.name.chars().count() + 1).max().unwrap_or(0).clamp(4, 60);
+
+    println!("{:<width$}  {:>12}  {:>10}  {:<16}", "NAME", "SIZE", "FILES", "MODIFIED", width = name_width);
+    for f in files {
+        let mut name = f.name.clone();
+        if f.is_dir {
+            name.push('/');
+        }
+        let modified = DateTime::from_timestamp(f.modified as i64, 0)
+            .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        let files = if f.is_dir { f.file_count.to_string() } else { String::new() };
+        println!("{:<width$}  {:>12}  {:>10}  {:<16}", name, format_bytes(f.size), files, modified, width = name_width);
+    }
+    println!();
+    println!("{}: {} in {} files", path, format_bytes(total_size), total_files);
+}
+
+pub fn export(path: &str, format: ExportFormat, output: Option<&Path>) -> i32 {
+    let system_path = mapper().to_system(path);
+    let tree = match tree::scan_tree(Path::new(&system_path)) {
+        Ok(tree) => tree,
+        Err(e) => {
+            eprintln!("Failed to scan '{}': {}", path, e);
+            return 1;
+        }
+    };
+
+    let root_path = mapper().to_ui(&system_path);
+    let result = match output {
+        Some(file) => File::create(file).and_then(|f| {
+            let mut out = BufWriter::new(f);
+            export::write_tree(&tree, &root_path, format, &mut out)?;
+            out.flush()
+        }),
+        None => export::write_tree(&tree, &root_path, format, &mut BufWriter::new(io::stdout().lock())),
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Failed to write export: {}", e);
+            1
+        }
+    }
+}
+
+pub fn import(file: &Path, name: Option<&str>) -> i32 {
+    let data = if file == Path::new("-") {
+        let mut data = Vec::new();
+        io::stdin().read_to_end(&mut data).map(|_| data)
+    } else {
+        fs::read(file)
+    };
+    let data = match data {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("Failed to read '{}': {}", file.display(), e);
+            return 1;
+        }
+    };
+
+    match imports::save(&data, name) {
+        Ok(info) => {
+            println!(
+                "Imported '{}' as {}: {} in {} files",
+                info.root,
+                info.id,
+                format_bytes(info.total_size),
+                info.total_files
+            );
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to import '{}': {}", file.display(), e);
+            1
+        }
+    }
+}
+
+pub fn check(path: Option<&str>, no_alerts: bool) -> i32 {
+    let settings = load_settings();
+    let mut alert_config = settings.alerts.clone();
+    if no_alerts {
+        alert_config.enabled = false;
+    }
+
+    let items: Vec<_> = settings
+        .monitoring
+        .paths
+        .iter()
+        .filter(|p| path.is_none_or(|path| p.path == path))
+        .collect();
+    if items.is_empty() {
+        match path {
+            Some(path) => println!("VOLUMETRIK UNKNOWN - '{}' is not a monitored path", path),
+            None => println!("VOLUMETRIK UNKNOWN - no monitored paths configured"),
+        }
+        return EXIT_UNKNOWN;
+    }
+
+    let disks = Disks::new_with_refreshed_list();
+    let mut exit = EXIT_OK;
+    let mut lines = Vec::new();
+    let mut problems = Vec::new();
+
+    for item in items {
+        let outcome = monitor::check_path(item, &settings.monitoring, &alert_config, &disks);
+        let measurements: Vec<String> = outcome
+            .measurements
+            .iter()
+            .map(|m| format!("{:.2}{} ({:?})", m.value, m.unit, m.threshold_type))
+            .collect();
+        let mut detail = measurements.join(", ");
+        if let Some(reason) = &outcome.suppressed {
+            detail.push_str(&format!(", alert suppressed: {}", reason));
+        }
+        if let Some(warning) = &outcome.warning {
+            detail.push_str(&format!(", {}", warning));
+        }
+        if let Some(e) = &outcome.scan_error {
+            detail.push_str(&format!(", {}", e));
+        }
+        let status = match outcome.breach {
+            Some(Severity::Critical) => EXIT_CRITICAL,
+            Some(Severity::Warning) => EXIT_WARNING,
+            // Info breaches are reported but don't change the status
+            Some(Severity::Info) | None if outcome.measurements.is_empty() || outcome.scan_error.is_some() => EXIT_UNKNOWN,
+            Some(Severity::Info) | None => EXIT_OK,
+        };
+
+        exit = worse(exit, status);
+        if status != EXIT_OK {
+            problems.push(item.path.clone());
+        }
+        lines.push(format!("{} {}: {}", status_label(status), item.path, detail));
+    }
+
+    if problems.is_empty() {
+        println!("VOLUMETRIK OK - {} path(s) within thresholds", lines.len());
+    } else {
+        println!("VOLUMETRIK {} - {}", status_label(exit), problems.join(", "));
+    }
+    for line in lines {
+        println!("{}", line);
+    }
+
+    exit
+}
+
+/// Orders Nagios statuses so that unknown only wins over OK.
+fn worse(a: i32, b: i32) -> i32 {
+    let rank = |s: i32| match s {
+        EXIT_OK => 0,
+        EXIT_UNKNOWN => 1,
+        EXIT_WARNING => 2,
+        _ => 3,
+    };
+    if rank(b) > rank(a) { b } else { a }
+}
+
+fn status_label(status: i32) -> &'static str {
+    match status {
+        EXIT_OK => "OK",
+        EXIT_WARNING => "WARNING",
+        EXIT_CRITICAL => "CRITICAL",
+        _ => "UNKNOWN",
+    }
+}
+
+pub fn test_alert(channel: Option<AlertChannel>, message: Option<String>) -> i32 {
+    let settings = load_settings();
+    let results = alerts::send_test_alert(&settings.alerts, channel, message);
+
+    if results.is_empty() {
+        eprintln!("No alert channel is configured");
+        return 1;
+    }
+
+    for DeliveryResult { channel, success, status, error } in &results {
+        let status = status.map(|s| format!(" (HTTP {})", s)).unwrap_or_default();
+        match (success, error) {
+            (true, _) => println!("{}: sent{}", channel.label(), status),
+            (false, Some(e)) => println!("{}: failed{}: {}", channel.label(), status, e),
+            (false, None) => println!("{}: failed{}", channel.label(), status),
+        }
+    }
+
+    if results.iter().all(|r| r.success) { 0 } else { 1 }
+}
+
+fn read_password() -> Result<String, String> {
+    eprint!("Password: ");
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password).map_err(|e| e.to_string())?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        return Err("Password must not be empty".to_string());
+    }
+    Ok(password)
+}
+
+pub fn user(action: UserAction) -> i32 {
+    let mut config = match auth::load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    match action {
+        UserAction::Add { username, role, paths } => {
+            let hash = match read_password().and_then(|p| auth::hash_password(&p)) {
+                Ok(hash) => hash,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 1;
+                }
+            };
+            match config.users.iter_mut().find(|u| u.username == username) {
+                Some(user) => {
+                    user.password_hash = hash;
+                    user.role = role;
+                    user.paths = paths;
+                    println!("User '{}' updated ({:?})", username, role);
+                }
+                None => {
+                    config.users.push(auth::User {
+                        username: username.clone(),
+                        password_hash: hash,
+                        role,
+                        paths,
+                        created_at: chrono::Utc::now(),
+                    });
+                    println!("User '{}' added ({:?})", username, role);
+                }
+            }
+        }
+        UserAction::Remove { username } => {
+            let before = config.users.len();
+            config.users.retain(|u| u.username != username);
+            if config.users.len() == before {
+                eprintln!("No user named '{}'", username);
+                return 1;
+            }
+            println!("User '{}' removed", username);
+        }
+        UserAction::List => {
+            for user in &config.users {
+                print_entry(&user.username, user.role, &user.paths, user.created_at);
+            }
+            return 0;
+        }
+    }
+
+    save_auth(&config)
+}
+
+pub fn token(action: TokenAction) -> i32 {
+    let mut config = match auth::load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+
+    match action {
+        TokenAction::Create { name, role, paths } => {
+            if config.tokens.iter().any(|t| t.name == name) {
+                eprintln!("A token named '{}' already exists", name);
+                return 1;
+            }
+            let (token, entry) = auth::generate_token(&name, role, paths);
+            config.tokens.push(entry);
+            let code = save_auth(&config);
+            if code == 0 {
+                println!("{}", token);
+            }
+            code
+        }
+        TokenAction::Revoke { name } => {
+            let before = config.tokens.len();
+            config.tokens.retain(|t| t.name != name);
+            if config.tokens.len() == before {
+                eprintln!("No token named '{}'", name);
+                return 1;
+            }
+            println!("Token '{}' revoked", name);
+            save_auth(&config)
+        }
+        TokenAction::List => {
+            for token in &config.tokens {
+                print_entry(&token.name, token.role, &token.paths, token.created_at);
+            }
+            0
+        }
+    }
+}
+
+fn print_entry(name: &str, role: Role, paths: &[String], created_at: DateTime<chrono::Utc>) {
+    let paths = if paths.is_empty() { "all paths".to_string() } else { paths.join(", ") };
+    println!("{}\t{:?}\t{}\tcreated {}", name, role, paths, created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M"));
+}
+
+fn save_auth(config: &auth::AuthConfig) -> i32 {
+    match auth::save_config(config) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Failed to save auth file: {}", e);
+            1
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use crate::alerts::{self, DeliveryResult};
+use crate::disks;
+use crate::pathmap::mapper;
+use crate::scan::scan_path;
+use crate::settings::{data_file, DigestConfig, DigestFormat, DigestFrequency, Settings, SharedSettings};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use sysinfo::Disks;
+
+const STATE_FILE: &str = "digest_state.json";
+
+/// Sizes recorded by the previous report, used to compute changes and forecasts.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct DigestState {
+    pub last_run: Option<DateTime<Utc>>,
+    pub paths: HashMap<String, PathSnapshot>,
+    pub volumes: HashMap<String, u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct PathSnapshot {
+    pub size: u64,
+    pub children: HashMap<String, u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DigestReport {
+    pub generated_at: DateTime<Utc>,
+    pub previous_report: Option<DateTime<Utc>>,
+    pub paths: Vec<PathReport>,
+    pub volumes: Vec<VolumeReport>,
+    pub top_growing: Vec<GrowthEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PathReport {
+    pub path: String,
+    pub size: Option<u64>,
+    pub change: Option<i64>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VolumeReport {
+    pub mount_point: String,
+    pub total: u64,
+    pub available: u64,
+    pub change: Option<i64>,
+    /// Days until the volume is full at the rate observed since the last report
+    pub days_until_full: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GrowthEntry {
+    pub path: String,
+    pub size: u64,
+    pub growth: i64,
+}
+
+pub fn load_state() -> DigestState {
+    fs::read_to_string(data_file(STATE_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_state(state: &DigestState) {
+    let path = data_file(STATE_FILE);
+    let result = serde_json::to_string_pretty(state)
+        .map_err(std::io::Error::from)
+        .and_then(|content| fs::write(&path, content));
+    if let Err(e) = result {
+        println!("Failed to write digest state '{}': {}", path.display(), e);
+    }
+}
+
+/// Most recent scheduled time at or before `now`.
+fn last_slot(config: &DigestConfig, now: DateTime<Local>) -> DateTime<Local> {
+    let hour = config.hour.min(23);
+    let today = now.date_naive();
+    let at_hour = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_hms_opt(hour, 0, 0).unwrap())
+            .earliest()
+            .unwrap_or(now)
+    };
+
+    match config.frequency {
+        DigestFrequency::Daily => {
+            let slot = at_hour(today);
+            if slot > now { at_hour(today - Duration::days(1)) } else { slot }
+        }
+        DigestFrequency::Weekly => {
+            let days_back = (today.weekday().num_days_from_monday() + 7 - config.weekday.num_days_from_monday()) % 7;
+            let slot = at_hour(today - Duration::days(days_back as i64));
+            if slot > now { at_hour(today - Duration::days(days_back as i64 + 7)) } else { slot }
+        }
+        DigestFrequency::Monthly => {
+            let first = today.with_day(1).unwrap();
+            let slot = at_hour(first);
+            if slot > now {
+                let previous = (first - Duration::days(1)).with_day(1).unwrap();
+                at_hour(previous)
+            } else {
+                slot
+            }
+        }
+    }
+}
+
+/// Whether a report is due. The first run only records a baseline, see `run`.
+pub fn is_due(config: &DigestConfig, state: &DigestState, now: DateTime<Local>) -> bool {
+    match state.last_run {
+        Some(last) => last < last_slot(config, now).with_timezone(&Utc),
+        None => true,
+    }
+}
+
+/// Checks every minute whether a digest is due and sends it.
+pub fn spawn_scheduler(settings: SharedSettings) {
+    tokio::spawn(async move {
+        // Initial delay to let server start
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+
+        loop {
+            let settings = settings.lock().unwrap().clone();
+
+            if settings.digest.enabled && is_due(&settings.digest, &load_state(), Local::now()) {
+                let result = tokio::task::spawn_blocking(move || run(&settings, false)).await;
+                if let Err(e) = result {
+                    println!("Digest task failed: {}", e);
+                }
+            }
+
+            tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        }
+    });
+}
+
+/// Measures the monitored paths and their volumes and compares them with the previous state.
+pub fn build_report(settings: &Settings, state: &DigestState) -> (DigestReport, DigestState) {
+    let now = Utc::now();
+    let mut new_state = DigestState {
+        last_run: Some(now),
+        ..Default::default()
+    };
+    let mut paths = Vec::new();
+    let mut growing = Vec::new();
+    let mut mounts: Vec<String> = Vec::new();
+    let disks = Disks::new_with_refreshed_list();
+
+    let mut seen = HashSet::new();
+    let monitored: Vec<String> = settings
+        .monitoring
+        .paths
+        .iter()
+        .map(|p| p.path.clone())
+        .filter(|p| seen.insert(p.clone()))
+        .collect();
+
+    for path in monitored {
+        let system_path = mapper().to_system(&path);
+
+        // Remember the volume holding this path
+        if let Some(disk) = disks::disk_for_path(&disks, Path::new(&system_path)) {
+            let mount = disk.mount_point().to_string_lossy().to_string();
+            if !mounts.contains(&mount) {
+                mounts.push(mount);
+            }
+        }
+
+        match scan_path(&system_path) {
+            Ok((files, total_size, _)) => {
+                let previous = state.paths.get(&path);
+                let mut snapshot = PathSnapshot { size: total_size, children: HashMap::new() };
+
+                for file in files.iter().filter(|f| f.is_dir) {
+                    if let Some(before) = previous.and_then(|p| p.children.get(&file.name)) {
+                        growing.push(GrowthEntry {
+                            path: mapper().ui_child(&path, &file.name),
+                            size: file.size,
+                            growth: file.size as i64 - *before as i64,
+                        });
+                    }
+                    snapshot.children.insert(file.name.clone(), file.size);
+                }
+
+                paths.push(PathReport {
+                    path: path.clone(),
+                    size: Some(total_size),
+                    change: previous.map(|p| total_size as i64 - p.size as i64),
+                    error: None,
+                });
+                new_state.paths.insert(path, snapshot);
+            }
+            Err(e) => paths.push(PathReport { path, size: None, change: None, error: Some(e.to_string()) }),
+        }
+    }
+
+    let elapsed_days = state
+        .last_run
+        .map(|last| (now - last).num_seconds() as f64 / 86_400.0)
+        .filter(|days| *days > 0.0);
+
+    let mut volumes = Vec::new();
+    for disk in disks.list() {
+        let mount = disk.mount_point().to_string_lossy().to_string();
+        if !mounts.contains(&mount) || new_state.volumes.contains_key(&mount) {
+            continue;
+        }
+        let available = disk.available_space();
+        let change = state.volumes.get(&mount).map(|before| available as i64 - *before as i64);
+        // Linear forecast from the free space consumed since the previous report
+        let days_until_full = match (change, elapsed_days) {
+            (Some(change), Some(days)) if change < 0 => Some(available as f64 / (-change as f64 / days)),
+            _ => None,
+        };
+
+        volumes.push(VolumeReport {
+            mount_point: mapper().to_ui(&mount),
+            total: disk.total_space(),
+            available,
+            change,
+            days_until_full,
+        });
+        new_state.volumes.insert(mount, available);
+    }
+
+    growing.retain(|g| g.growth > 0);
+    growing.sort_by_key(|g| std::cmp::Reverse(g.growth));
+    growing.truncate(settings.digest.top_growing);
+
+    let report = DigestReport {
+        generated_at: now,
+        previous_report: state.last_run,
+        paths,
+        volumes,
+        top_growing: growing,
+    };
+    (report, new_state)
+}
+
+/// Runs a scheduled or manual digest. The very first run has nothing to compare with,
+/// so unless `force` is set it only records a baseline.
+pub fn run(settings: &Settings, force: bool) -> Vec<DeliveryResult> {
+    let state = load_state();
+    let first_run = state.last_run.is_none();
+    let (report, new_state) = build_report(settings, &state);
+    save_state(&new_state);
+
+    if first_run && !force {
+        println!("Digest: recorded baseline, first report will be sent at the next scheduled time");
+        return Vec::new();
+    }
+
+    let message = render(&report, settings.digest.format);
+    println!("Digest: sending usage report");
+    alerts::send_message(&settings.alerts, "Volumetrik Usage Report", &message, &settings.digest.channels)
+}
+
+pub fn render(report: &DigestReport, format: DigestFormat) -> String {
+    match format {
+        DigestFormat::Text => render_text(report, false),
+        DigestFormat::Markdown => render_text(report, true),
+        DigestFormat::Html => render_html(report),
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB", "PB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", value, units[unit])
+}
+
+fn format_change(change: Option<i64>) -> String {
+    match change {
+        Some(c) if c >= 0 => format!("+{}", format_bytes(c as u64)),
+        Some(c) => format!("-{}", format_bytes(c.unsigned_abs())),
+        None => "n/a".to_string(),
+    }
+}
+
+fn format_forecast(days: Option<f64>) -> String {
+    match days {
+        Some(d) if d < 3650.0 => format!("full in ~{:.0} days", d),
+        _ => "no growth".to_string(),
+    }
+}
+
+fn period(report: &DigestReport) -> String {
+    let generated = report.generated_at.with_timezone(&Local).format("%Y-%m-%d %H:%M");
+    match report.previous_report {
+        Some(previous) => format!("{} → {}", previous.with_timezone(&Local).format("%Y-%m-%d %H:%M"), generated),
+        None => generated.to_string(),
+    }
+}
+
+fn render_text(report: &DigestReport, markdown: bool) -> String {
+    let heading = |title: &str| if markdown { format!("\n## {}\n", title) } else { format!("\n{}\n", title.to_uppercase()) };
+    let mut out = if markdown {
+        format!("# Volumetrik Usage Report\n_{}_\n", period(report))
+    } else {
+        format!("Volumetrik Usage Report ({})\n", period(report))
+    };
+
+    out.push_str(&heading("Monitored paths"));
+    for p in &report.paths {
+        let line = match (&p.error, p.size) {
+            (Some(e), _) => format!("{}: error: {}", p.path, e),
+            (None, Some(size)) => format!("{}: {} ({})", p.path, format_bytes(size), format_change(p.change)),
+            (None, None) => format!("{}: unknown", p.path),
+        };
+        out.push_str(&format!("- {}\n", line));
+    }
+
+    out.push_str(&heading("Volumes"));
+    for v in &report.volumes {
+        out.push_str(&format!(
+            "- {}: {} free of {} ({}), {}\n",
+            v.mount_point,
+            format_bytes(v.available),
+            format_bytes(v.total),
+            format_change(v.change),
+            format_forecast(v.days_until_full)
+        ));
+    }
+
+    if !report.top_growing.is_empty() {
+        out.push_str(&heading("Top growing directories"));
+        for g in &report.top_growing {
+            out.push_str(&format!("- {}: {} ({})\n", g.path, format_bytes(g.size), format_change(Some(g.growth))));
+        }
+    }
+    out
+}
+
+fn escape_html(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn render_html(report: &DigestReport) -> String {
+    let mut out = format!("<h1>Volumetrik Usage Report</h1>\n<p>{}</p>\n", escape_html(&period(report)));
+
+    out.push_str("<h2>Monitored paths</h2>\n<table>\n<tr><th>Path</th><th>Size</th><th>Change</th></tr>\n");
+    for p in &report.paths {
+        let (size, change) = match (&p.error, p.size) {
+            (Some(e), _) => (format!("error: {}", e), String::new()),
+            (None, Some(size)) => (format_bytes(size), format_change(p.change)),
+            (None, None) => ("unknown".to_string(), String::new()),
+        };
+        out.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&p.path),
+            escape_html(&size),
+            change
+        ));
+    }
+    out.push_str("</table>\n");
+
+    out.push_str("<h2>Volumes</h2>\n<table>\n<tr><th>Mount</th><th>Free</th><th>Total</th><th>Change</th><th>Forecast</th></tr>\n");
+    for v in &report.volumes {
+        out.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&v.mount_point),
+            format_bytes(v.available),
+            format_bytes(v.total),
+            format_change(v.change),
+            format_forecast(v.days_until_full)
+        ));
+    }
+    out.push_str("</table>\n");
+
+    if !report.top_growing.is_empty() {
+        out.push_str("<h2>Top growing directories</h2>\n<ol>\n");
+        for g in &report.top_growing {
+            out.push_str(&format!(
+                "<li>{}: {} ({})</li>\n",
+                escape_html(&g.path),
+                format_bytes(g.size),
+                format_change(Some(g.growth))
+            ));
+        }
+        out.push_str("</ol>\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Weekday;
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn config(frequency: DigestFrequency, weekday: Weekday, hour: u32) -> DigestConfig {
+        DigestConfig { frequency, weekday, hour, ..Default::default() }
+    }
+
+    #[test]
+    fn daily_slots() {
+        let daily = config(DigestFrequency::Daily, Weekday::Mon, 8);
+        assert_eq!(last_slot(&daily, local(2026, 1, 5, 7, 59)), local(2026, 1, 4, 8, 0));
+        assert_eq!(last_slot(&daily, local(2026, 1, 5, 8, 0)), local(2026, 1, 5, 8, 0));
+        assert_eq!(last_slot(&daily, local(2026, 1, 1, 0, 0)), local(2025, 12, 31, 8, 0));
+        // Hours past the end of the day mean the last hour
+        let late = config(DigestFrequency::Daily, Weekday::Mon, 30);
+        assert_eq!(last_slot(&late, local(2026, 1, 5, 12, 0)), local(2026, 1, 4, 23, 0));
+    }
+
+    #[test]
+    fn weekly_slots() {
+        // 2026-01-05 is a Monday
+        let monday = config(DigestFrequency::Weekly, Weekday::Mon, 8);
+        assert_eq!(last_slot(&monday, local(2026, 1, 5, 7, 59)), local(2025, 12, 29, 8, 0));
+        assert_eq!(last_slot(&monday, local(2026, 1, 5, 8, 0)), local(2026, 1, 5, 8, 0));
+        assert_eq!(last_slot(&monday, local(2026, 1, 11, 23, 59)), local(2026, 1, 5, 8, 0));
+        assert_eq!(last_slot(&monday, local(2026, 1, 12, 8, 0)), local(2026, 1, 12, 8, 0));
+
+        let sunday = config(DigestFrequency::Weekly, Weekday::Sun, 20);
+        assert_eq!(last_slot(&sunday, local(2026, 1, 3, 12, 0)), local(2025, 12, 28, 20, 0));
+        assert_eq!(last_slot(&sunday, local(2026, 1, 4, 19, 0)), local(2025, 12, 28, 20, 0));
+        assert_eq!(last_slot(&sunday, local(2026, 1, 5, 0, 0)), local(2026, 1, 4, 20, 0));
+    }
+
+    #[test]
+    fn monthly_slots() {
+        let monthly = config(DigestFrequency::Monthly, Weekday::Mon, 8);
+        assert_eq!(last_slot(&monthly, local(2026, 3, 31, 23, 59)), local(2026, 3, 1, 8, 0));
+        assert_eq!(last_slot(&monthly, local(2026, 3, 1, 7, 0)), local(2026, 2, 1, 8, 0));
+        assert_eq!(last_slot(&monthly, local(2026, 3, 1, 8, 0)), local(2026, 3, 1, 8, 0));
+        assert_eq!(last_slot(&monthly, local(2024, 2, 29, 12, 0)), local(2024, 2, 1, 8, 0));
+        assert_eq!(last_slot(&monthly, local(2026, 1, 1, 7, 0)), local(2025, 12, 1, 8, 0));
+    }
+
+    fn state(last_run: Option<DateTime<Local>>) -> DigestState {
+        DigestState { last_run: last_run.map(|t| t.with_timezone(&Utc)), ..Default::default() }
+    }
+
+    #[test]
+    fn due_once_per_slot() {
+        let monday = config(DigestFrequency::Weekly, Weekday::Mon, 8);
+        let now = local(2026, 1, 7, 12, 0);
+        assert!(is_due(&monday, &state(None), now));
+        // Sent after this week's slot already
+        assert!(!is_due(&monday, &state(Some(local(2026, 1, 5, 8, 0))), now));
+        assert!(!is_due(&monday, &state(Some(local(2026, 1, 6, 9, 30))), now));
+        // Last sent the week before, or just before the slot
+        assert!(is_due(&monday, &state(Some(local(2025, 12, 29, 8, 1))), now));
+        assert!(is_due(&monday, &state(Some(local(2026, 1, 5, 7, 59))), now));
+
+        let monthly = config(DigestFrequency::Monthly, Weekday::Mon, 8);
+        assert!(!is_due(&monthly, &state(Some(local(2026, 1, 1, 8, 0))), local(2026, 1, 31, 23, 59)));
+        assert!(is_due(&monthly, &state(Some(local(2026, 1, 1, 8, 0))), local(2026, 2, 1, 8, 0)));
+    }
+
+    fn report() -> DigestReport {
+        DigestReport {
+            generated_at: Utc::now(),
+            previous_report: None,
+            paths: vec![
+                PathReport { path: "/data".to_string(), size: Some(3 * 1024 * 1024), change: Some(-1024), error: None },
+                PathReport { path: "/new".to_string(), size: Some(10), change: None, error: None },
+                PathReport { path: "/gone".to_string(), size: None, change: None, error: Some("No such <file>".to_string()) },
+            ],
+            volumes: vec![VolumeReport {
+                mount_point: "/mnt/a&b".to_string(),
+                total: 100 * 1024 * 1024 * 1024,
+                available: 10 * 1024 * 1024 * 1024,
+                change: Some(-1024 * 1024 * 1024),
+                days_until_full: Some(12.4),
+            }],
+            top_growing: vec![GrowthEntry { path: "/data/logs".to_string(), size: 2048, growth: 1024 }],
+        }
+    }
+
+    #[test]
+    fn renders_text_and_markdown() {
+        let text = render(&report(), DigestFormat::Text);
+        assert!(text.starts_with("Volumetrik Usage Report ("));
+        assert!(text.contains("\nMONITORED PATHS\n- /data: 3.00 MB (-1.00 KB)\n- /new: 10.00 B (n/a)\n- /gone: error: No such <file>\n"));
+        assert!(text.contains("- /mnt/a&b: 10.00 GB free of 100.00 GB (-1.00 GB), full in ~12 days\n"));
+        assert!(text.contains("\nTOP GROWING DIRECTORIES\n- /data/logs: 2.00 KB (+1.00 KB)\n"));
+
+        let markdown = render(&report(), DigestFormat::Markdown);
+        assert!(markdown.starts_with("# Volumetrik Usage Report\n_"));
+        assert!(markdown.contains("\n## Volumes\n"));
+
+        let quiet = DigestReport { top_growing: Vec::new(), ..report() };
+        assert!(!render(&quiet, DigestFormat::Text).contains("TOP GROWING"));
+    }
+
+    #[test]
+    fn renders_escaped_html() {
+        let html = render(&report(), DigestFormat::Html);
+        assert!(html.contains("<tr><td>/gone</td><td>error: No such &lt;file&gt;</td><td></td></tr>"));
+        assert!(html.contains("<tr><td>/mnt/a&amp;b</td>"));
+        assert!(html.contains("<li>/data/logs: 2.00 KB (+1.00 KB)</li>"));
+        assert!(!html.contains("<file>"));
+    }
+
+    #[test]
+    fn formats_changes_and_forecasts() {
+        assert_eq!(format_bytes(0), "0.00 B");
+        assert_eq!(format_bytes(1536), "1.50 KB");
+        assert_eq!(format_change(Some(0)), "+0.00 B");
+        assert_eq!(format_change(Some(i64::MIN)), "-8192.00 PB");
+        assert_eq!(format_forecast(Some(0.4)), "full in ~0 days");
+        assert_eq!(format_forecast(Some(5000.0)), "no growth");
+        assert_eq!(format_forecast(None), "no growth");
+    }
+}
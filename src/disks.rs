@@ -0,0 +1,204 @@
+use crate::pathmap::mapper;
+use crate::roots;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use sysinfo::{Disk, Disks};
+
+/// Filesystems without storage of their own: memory, kernel interfaces and image layers.
+const PSEUDO_FILESYSTEMS: &[&str] = &[
+    "tmpfs", "devtmpfs", "ramfs", "overlay", "overlayfs", "aufs", "squashfs", "proc", "sysfs", "cgroup", "cgroup2",
+    "devpts", "mqueue", "debugfs", "tracefs", "securityfs", "pstore", "bpf", "configfs", "fusectl", "hugetlbfs",
+    "autofs", "binfmt_misc", "nsfs", "efivarfs", "rpc_pipefs",
+];
+
+/// A mounted volume. The mount point is a UI path, the system path is kept for lookups.
+#[derive(Debug, Serialize, Clone)]
+pub struct VolumeInfo {
+    pub mount_point: String,
+    #[serde(skip)]
+    pub system_mount_point: PathBuf,
+    pub device: String,
+    pub file_system: String,
+    pub total: u64,
+    pub used: u64,
+    pub available: u64,
+    pub inodes_total: Option<u64>,
+    pub inodes_free: Option<u64>,
+    pub read_only: bool,
+    /// tmpfs, overlay, proc and the like
+    pub pseudo: bool,
+}
+
+pub fn is_pseudo_filesystem(file_system: &str) -> bool {
+    PSEUDO_FILESYSTEMS.contains(&file_system.to_lowercase().as_str())
+}
+
+/// Lists mounted volumes, one entry per mount point.
+pub fn list_volumes() -> Vec<VolumeInfo> {
+    let disks = Disks::new_with_refreshed_list();
+    let mut volumes: Vec<VolumeInfo> = Vec::new();
+
+    for disk in disks.list() {
+        let system_mount_point = disk.mount_point().to_path_buf();
+        if volumes.iter().any(|v| v.system_mount_point == system_mount_point) {
+            continue;
+        }
+
+        let (inodes_total, inodes_free) = match inode_stats(disk.mount_point()) {
+            Some((total, free)) => (Some(total), Some(free)),
+            None => (None, None),
+        };
+
+        let file_system = disk.file_system().to_string_lossy().to_string();
+        volumes.push(VolumeInfo {
+            mount_point: mapper().to_ui(&system_mount_point.to_string_lossy()),
+            system_mount_point,
+            device: disk.name().to_string_lossy().to_string(),
+            pseudo: is_pseudo_filesystem(&file_system),
+            file_system,
+            total: disk.total_space(),
+            used: disk.total_space().saturating_sub(disk.available_space()),
+            available: disk.available_space(),
+            inodes_total,
+            inodes_free,
+            read_only: disk.is_read_only(),
+        });
+    }
+
+    volumes
+}
+
+/// Mount point reported when the volume of a path can't be determined.
+pub const UNKNOWN_VOLUME: &str = "unknown";
+
+/// Finds the mount holding `path` among `mounts`: the deepest mount point that is a whole-component
+/// prefix of the canonical path and, on Unix, is on the same device. A path under a mount missing
+/// from the list (a bind mount in a container) falls back to any mount of its device.
+/// `None` when no mount holds the path.
+pub fn resolve_mount<'a, T>(mounts: &'a [T], mount_point: impl Fn(&T) -> &Path, path: &Path) -> Option<&'a T> {
+    let canonical = roots::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let device = device_id(&canonical);
+    let same_device = |item: &T| match (device, device_id(mount_point(item))) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    };
+
+    mounts
+        .iter()
+        .filter(|m| canonical.starts_with(mount_point(m)) && same_device(m))
+        .max_by_key(|m| mount_point(m).components().count())
+        .or_else(|| {
+            device?;
+            mounts.iter().find(|m| device_id(mount_point(m)) == device)
+        })
+}
+
+/// Disk holding `path`, see [`resolve_mount`].
+pub fn disk_for_path<'a>(disks: &'a Disks, path: &Path) -> Option<&'a Disk> {
+    resolve_mount(disks.list(), |d| d.mount_point(), path)
+}
+
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| m.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Volumes inside the allowed roots, plus the ones holding a root. Others (the container's own
+/// filesystem in Docker) are hidden, their mount points would clash with mapped UI paths.
+pub fn within_allowed_roots(volumes: Vec<VolumeInfo>) -> Vec<VolumeInfo> {
+    let holding_roots: Vec<PathBuf> = roots::allowed_roots()
+        .iter()
+        .filter_map(|root| resolve_mount(&volumes, |v| &v.system_mount_point, root))
+        .map(|v| v.system_mount_point.clone())
+        .collect();
+
+    volumes
+        .into_iter()
+        .filter(|v| roots::is_allowed(&v.system_mount_point) || holding_roots.contains(&v.system_mount_point))
+        .collect()
+}
+
+/// Total and free inodes of the filesystem holding `path`.
+#[cfg(unix)]
+pub fn inode_stats(path: &Path) -> Option<(u64, u64)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid NUL-terminated string and stat is a writable statvfs
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some((stat.f_files as u64, stat.f_ffree as u64))
+}
+
+#[cfg(not(unix))]
+pub fn inode_stats(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Missing paths have no device, only the prefixes decide
+    const BASE: &str = "/volumetrik-missing";
+
+    fn mounts(points: &[&str]) -> Vec<PathBuf> {
+        points.iter().map(|p| PathBuf::from(format!("{}{}", BASE, p))).collect()
+    }
+
+    fn mount_of<'a>(mounts: &'a [PathBuf], path: &str) -> Option<&'a str> {
+        resolve_mount(mounts, |m| m.as_path(), Path::new(&format!("{}{}", BASE, path)))
+            .map(|m| m.to_str().unwrap().strip_prefix(BASE).unwrap())
+    }
+
+    #[test]
+    fn sibling_prefixes_dont_match() {
+        let list = mounts(&["", "/mnt/data"]);
+        assert_eq!(mount_of(&list, "/mnt/data2/file"), Some(""));
+        assert_eq!(mount_of(&list, "/mnt/data-old"), Some(""));
+        assert_eq!(mount_of(&list, "/mnt/data/file"), Some("/mnt/data"));
+    }
+
+    #[test]
+    fn deepest_mount_wins() {
+        let list = mounts(&["/mnt/data/nested", "", "/mnt/data"]);
+        assert_eq!(mount_of(&list, "/mnt/data"), Some("/mnt/data"));
+        assert_eq!(mount_of(&list, "/mnt/data/"), Some("/mnt/data"));
+        assert_eq!(mount_of(&list, "/mnt/data/nested/deep/file"), Some("/mnt/data/nested"));
+        assert_eq!(mount_of(&list, "/mnt/data/nested2"), Some("/mnt/data"));
+    }
+
+    #[test]
+    fn root_mount_and_no_match() {
+        let root = vec![PathBuf::from("/")];
+        let missing = format!("{}/anything", BASE);
+        assert_eq!(resolve_mount(&root, |m| m.as_path(), Path::new(&missing)), Some(&root[0]));
+
+        let list = mounts(&["/mnt/data"]);
+        assert_eq!(mount_of(&list, "/srv/file"), None);
+        assert_eq!(mount_of(&list, "/mnt"), None);
+        assert_eq!(mount_of(&[], "/mnt/data"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unlisted_mounts_fall_back_to_the_device() {
+        // A bind mount shows up under another mount point of the same device
+        let base = std::env::temp_dir().join(format!("volumetrik-disks-{:08x}", rand::random::<u32>()));
+        let (listed, bound) = (base.join("listed"), base.join("bound"));
+        std::fs::create_dir_all(&listed).unwrap();
+        std::fs::create_dir_all(&bound).unwrap();
+        let list = vec![listed.clone()];
+        assert_eq!(resolve_mount(&list, |m| m.as_path(), &bound), Some(&listed));
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+use crate::tree::{EntryKind, TreeNode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::io::{self, Write};
+
+// ncdu JSON export format version written and accepted
+const NCDU_MAJOR: u64 = 1;
+const NCDU_MINOR: u64 = 2;
+
+// File type bits of st_mode
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFLNK: u32 = 0o120000;
+const S_IFREG: u32 = 0o100000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One row per entry
+    #[default]
+    Csv,
+    /// One JSON object per line and entry
+    Jsonl,
+    /// ncdu's JSON export, readable with `ncdu -f`
+    Ncdu,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Ncdu => "json",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Ncdu => "application/json",
+        }
+    }
+}
+
+/// A line of the CSV and JSON Lines exports.
+#[derive(Debug, Serialize)]
+struct Row<'a> {
+    path: &'a str,
+    kind: EntryKind,
+    size: u64,
+    disk_usage: u64,
+    file_count: u64,
+    modified: u64,
+    accessed: u64,
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+impl<'a> Row<'a> {
+    fn new(path: &'a str, node: &TreeNode) -> Self {
+        Self {
+            path,
+            kind: node.kind,
+            size: node.size,
+            disk_usage: node.disk_usage,
+            file_count: node.file_count,
+            modified: node.modified,
+            accessed: node.accessed,
+            uid: node.uid,
+            gid: node.gid,
+        }
+    }
+}
+
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Writes the whole tree. `root_path` is the path shown for the root, entries below it are joined to it.
+pub fn write_tree(tree: &TreeNode, root_path: &str, format: ExportFormat, out: &mut impl Write) -> io::Result<()> {
+    match format {
+        ExportFormat::Csv => {
+            writeln!(out, "path,kind,size,disk_usage,file_count,modified,accessed,uid,gid")?;
+            let mut result = Ok(());
+            tree.walk(root_path, &mut |path, node| {
+                if result.is_ok() {
+                    let row = Row::new(path, node);
+                    result = writeln!(
+                        out,
+                        "{},{},{},{},{},{},{},{},{}",
+                        csv_field(row.path),
+                        kind_label(row.kind),
+                        row.size,
+                        row.disk_usage,
+                        row.file_count,
+                        row.modified,
+                        row.accessed,
+                        optional(row.uid),
+                        optional(row.gid)
+                    );
+                }
+            });
+            result
+        }
+        ExportFormat::Jsonl => {
+            let mut result = Ok(());
+            tree.walk(root_path, &mut |path, node| {
+                if result.is_ok() {
+                    result = serde_json::to_writer(&mut *out, &Row::new(path, node))
+                        .map_err(io::Error::other)
+                        .and_then(|_| writeln!(out));
+                }
+            });
+            result
+        }
+        ExportFormat::Ncdu => {
+            let meta = json!({
+                "progname": "volumetrik",
+                "progver": env!("CARGO_PKG_VERSION"),
+                "timestamp": chrono::Utc::now().timestamp(),
+            });
+            write!(out, "[{},{},{},", NCDU_MAJOR, NCDU_MINOR, meta)?;
+            write_ncdu(tree, root_path, out)?;
+            writeln!(out, "]")
+        }
+    }
+}
+
+fn kind_label(kind: EntryKind) -> &'static str {
+    match kind {
+        EntryKind::File => "file",
+        EntryKind::Dir => "dir",
+        EntryKind::Symlink => "symlink",
+        EntryKind::Other => "other",
+    }
+}
+
+/// ncdu stores a directory as an array of its own info followed by its entries.
+/// Directory sizes are left out, ncdu sums them from the entries like Volumetrik does.
+fn write_ncdu(node: &TreeNode, name: &str, out: &mut impl Write) -> io::Result<()> {
+    let mut info = Map::new();
+    info.insert("name".into(), name.into());
+    if !node.is_dir() {
+        info.insert("asize".into(), node.size.into());
+        info.insert("dsize".into(), node.disk_usage.into());
+    }
+    for (key, value) in [("dev", node.dev), ("ino", node.ino)] {
+        if let Some(value) = value {
+            info.insert(key.into(), value.into());
+        }
+    }
+    for (key, value) in [("uid", node.uid), ("gid", node.gid), ("mode", node.mode)] {
+        if let Some(value) = value {
+            info.insert(key.into(), value.into());
+        }
+    }
+    info.insert("mtime".into(), node.modified.into());
+    if matches!(node.kind, EntryKind::Symlink | EntryKind::Other) {
+        info.insert("notreg".into(), true.into());
+    }
+    if node.read_error {
+        info.insert("read_error".into(), true.into());
+    }
+
+    let info = Value::Object(info);
+    if !node.is_dir() {
+        return write!(out, "{}", info);
+    }
+    write!(out, "[{}", info)?;
+    for child in &node.children {
+        write!(out, ",")?;
+        write_ncdu(child, &child.name, out)?;
+    }
+    write!(out, "]")
+}
+
+/// Reads an ncdu JSON export. The root keeps the name it has in the dump, usually an absolute path.
+pub fn read_ncdu(data: &[u8]) -> Result<TreeNode, String> {
+    let value: Value = serde_json::from_slice(data).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let parts = value.as_array().ok_or("Not an ncdu export: expected a JSON array")?;
+
+    let major = parts.first().and_then(Value::as_u64);
+    if major != Some(NCDU_MAJOR) {
+        return Err(format!("Unsupported ncdu export version {:?}, expected {}", major, NCDU_MAJOR));
+    }
+    let root = parts.get(3).ok_or("Not an ncdu export: missing root directory")?;
+
+    let mut tree = read_ncdu_entry(root)?;
+    if !tree.is_dir() {
+        return Err("Not an ncdu export: the root is not a directory".to_string());
+    }
+    tree.compute_totals();
+    Ok(tree)
+}
+
+fn read_ncdu_entry(value: &Value) -> Result<TreeNode, String> {
+    let (info, children) = match value {
+        Value::Array(items) => {
+            let info = items.first().and_then(Value::as_object).ok_or("Directory without info object")?;
+            (info, Some(&items[1..]))
+        }
+        Value::Object(info) => (info, None),
+        _ => return Err("Unexpected value in ncdu export".to_string()),
+    };
+
+    let number = |key: &str| info.get(key).and_then(Value::as_u64);
+    let flag = |key: &str| info.get(key).and_then(Value::as_bool).unwrap_or(false);
+    let mode = number("mode").map(|m| m as u32);
+
+    let kind = match (children.is_some(), mode.map(|m| m & S_IFMT)) {
+        (true, _) => EntryKind::Dir,
+        (false, Some(S_IFLNK)) => EntryKind::Symlink,
+        (false, Some(S_IFREG)) => EntryKind::File,
+        // Excluded directories are written without their entries
+        (false, Some(S_IFDIR)) => EntryKind::Dir,
+        (false, _) if flag("notreg") => EntryKind::Other,
+        (false, _) => EntryKind::File,
+    };
+
+    let mut node = TreeNode {
+        name: info.get("name").and_then(Value::as_str).ok_or("Entry without a name")?.to_string(),
+        kind,
+        size: number("asize").unwrap_or(0),
+        disk_usage: number("dsize").unwrap_or(0),
+        file_count: 1,
+        modified: number("mtime").unwrap_or(0),
+        accessed: 0,
+        uid: number("uid").map(|v| v as u32),
+        gid: number("gid").map(|v| v as u32),
+        mode,
+        dev: number("dev"),
+        ino: number("ino"),
+        read_error: flag("read_error"),
+        children: Vec::new(),
+    };
+
+    if let Some(children) = children {
+        node.children = children.iter().map(read_ncdu_entry).collect::<Result<_, _>>()?;
+    }
+    Ok(node)
+}
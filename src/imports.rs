@@ -0,0 +1,173 @@
+use crate::export;
+use crate::models::{FileStats, ScanResponse};
+use crate::pathmap::{self, mapper};
+use crate::settings::data_file;
+use crate::tree::TreeNode;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+const IMPORTS_DIR: &str = "imports";
+const INDEX_FILE: &str = "index.json";
+
+// Serializes changes to the index, the CLI and API handlers share it
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// A tree imported from an ncdu dump, browsable like a live scan.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportInfo {
+    pub id: String,
+    pub name: String,
+    /// Path of the scanned root on the machine the dump comes from
+    pub root: String,
+    pub imported_at: DateTime<Utc>,
+    pub total_size: u64,
+    pub total_files: u64,
+}
+
+fn imports_dir() -> PathBuf {
+    data_file(IMPORTS_DIR)
+}
+
+/// File holding the dump of an import. Ids are generated, anything else is refused.
+fn dump_file(id: &str) -> Result<PathBuf, String> {
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(format!("Invalid import id '{}'", id));
+    }
+    Ok(imports_dir().join(format!("{}.json", id)))
+}
+
+pub fn list() -> Vec<ImportInfo> {
+    fs::read_to_string(imports_dir().join(INDEX_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn write_index(imports: &[ImportInfo]) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(imports).map_err(|e| e.to_string())?;
+    fs::write(imports_dir().join(INDEX_FILE), content).map_err(|e| format!("Failed to write import index: {}", e))
+}
+
+/// Checks and stores an ncdu JSON dump.
+pub fn save(data: &[u8], name: Option<&str>) -> Result<ImportInfo, String> {
+    let tree = export::read_ncdu(data)?;
+
+    let _guard = INDEX_LOCK.lock().unwrap();
+    fs::create_dir_all(imports_dir()).map_err(|e| format!("Failed to create imports directory: {}", e))?;
+
+    let now = Utc::now();
+    let info = ImportInfo {
+        id: format!("{}-{:04x}", now.format("%Y%m%d%H%M%S"), rand::random::<u16>()),
+        name: name.filter(|n| !n.trim().is_empty()).unwrap_or(&tree.name).trim().to_string(),
+        root: tree.name.clone(),
+        imported_at: now,
+        total_size: tree.size,
+        total_files: tree.file_count,
+    };
+    fs::write(dump_file(&info.id)?, data).map_err(|e| format!("Failed to store import: {}", e))?;
+
+    let mut imports = list();
+    imports.push(info.clone());
+    write_index(&imports)?;
+    Ok(info)
+}
+
+pub fn remove(id: &str) -> Result<bool, String> {
+    let file = dump_file(id)?;
+
+    let _guard = INDEX_LOCK.lock().unwrap();
+    let mut imports = list();
+    let before = imports.len();
+    imports.retain(|i| i.id != id);
+    if imports.len() == before {
+        return Ok(false);
+    }
+    write_index(&imports)?;
+    let _ = fs::remove_file(file);
+    Ok(true)
+}
+
+/// Keeps the last browsed import parsed, dashboards browse one dump at a time.
+#[derive(Default)]
+pub struct ImportStore {
+    cache: Mutex<Option<(String, Arc<TreeNode>)>>,
+}
+
+pub type SharedImports = Arc<ImportStore>;
+
+impl ImportStore {
+    pub fn new() -> SharedImports {
+        Arc::new(Self::default())
+    }
+
+    pub fn tree(&self, id: &str) -> Result<Arc<TreeNode>, String> {
+        if let Some((cached_id, tree)) = self.cache.lock().unwrap().as_ref() {
+            if cached_id == id {
+                return Ok(tree.clone());
+            }
+        }
+
+        if !list().iter().any(|i| i.id == id) {
+            return Err(format!("Import '{}' not found", id));
+        }
+        let data = fs::read(dump_file(id)?).map_err(|e| format!("Failed to read import '{}': {}", id, e))?;
+        let tree = Arc::new(export::read_ncdu(&data)?);
+        *self.cache.lock().unwrap() = Some((id.to_string(), tree.clone()));
+        Ok(tree)
+    }
+
+    pub fn forget(&self, id: &str) {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.as_ref().is_some_and(|(cached_id, _)| cached_id == id) {
+            *cache = None;
+        }
+    }
+}
+
+/// Entry of an imported tree at `path`, a path of the dump. The root when `path` is empty.
+pub fn find<'a>(tree: &'a TreeNode, path: &str) -> Option<&'a TreeNode> {
+    if path.is_empty() {
+        return Some(tree);
+    }
+    let relative = path.strip_prefix(tree.name.trim_end_matches(['/', '\\']))?;
+    if !relative.is_empty() && !relative.starts_with(['/', '\\']) {
+        return None;
+    }
+    tree.find(relative)
+}
+
+/// Lists a directory of an imported tree like a live scan. Paths are those of the dump.
+/// `None` when the path is not in the tree.
+pub fn scan(tree: &TreeNode, path: &str) -> Option<ScanResponse> {
+    let node = find(tree, path).filter(|n| n.is_dir())?;
+    let path = if path.is_empty() { tree.name.as_str() } else { path };
+
+    let files = node
+        .children
+        .iter()
+        .map(|child| FileStats {
+            path: mapper().ui_child(path, &child.name),
+            name: child.name.clone(),
+            is_dir: child.is_dir(),
+            size: child.size,
+            file_count: child.file_count,
+            modified: child.modified,
+        })
+        .collect();
+
+    Some(ScanResponse {
+        parent: if std::ptr::eq(node, tree) { None } else { pathmap::parent(path) },
+        current: path.to_string(),
+        files,
+        total_size: node.size,
+        total_files: node.file_count,
+        total_entries: node.children.len() as u64,
+        matched_entries: node.children.len() as u64,
+        disk_total: None,
+        disk_available: None,
+        disk_mount_point: None,
+    })
+}
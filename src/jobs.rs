@@ -0,0 +1,293 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Finished jobs kept for the API, the oldest are forgotten first
+const MAX_FINISHED_JOBS: usize = 50;
+
+pub const BUSY: &str = "Another operation is running on this path";
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct Progress {
+    /// Current step, e.g. `compressing` or `verifying`
+    pub phase: String,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_done: u64,
+    pub files_total: u64,
+}
+
+/// A long-running operation on a path, run in the background and polled through the API.
+#[derive(Debug, Serialize, Clone)]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    /// UI path the job works on
+    pub path: String,
+    pub user: String,
+    pub state: JobState,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub progress: Progress,
+    /// What the job returned, set once it succeeded
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+}
+
+struct Entry {
+    job: Job,
+    cancel: Arc<AtomicBool>,
+}
+
+/// Background jobs, newest last, and the paths removals are working on.
+#[derive(Default)]
+pub struct JobRegistry {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: Vec<Entry>,
+    claims: Vec<(u64, String)>,
+    next_claim: u64,
+}
+
+impl Inner {
+    fn is_busy(&self, path: &str) -> bool {
+        let running = self.entries.iter().filter(|e| e.job.state == JobState::Running).map(|e| e.job.path.as_str());
+        let claimed = self.claims.iter().map(|(_, claimed)| claimed.as_str());
+        running.chain(claimed).any(|other| overlaps(path, other))
+    }
+}
+
+/// Whether one path is the other or holds it.
+fn overlaps(a: &str, b: &str) -> bool {
+    let (a, b) = (Path::new(a), Path::new(b));
+    a.starts_with(b) || b.starts_with(a)
+}
+
+pub type SharedJobs = Arc<JobRegistry>;
+
+/// Given to the work of a job to report progress and notice cancellation.
+pub struct JobHandle {
+    id: String,
+    registry: SharedJobs,
+    cancel: Arc<AtomicBool>,
+}
+
+impl JobHandle {
+    pub fn progress(&self, update: impl FnOnce(&mut Progress)) {
+        self.registry.update(&self.id, |job| update(&mut job.progress));
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(all(test, unix))]
+impl JobHandle {
+    /// A handle outside any registry, for the archive tests.
+    pub fn detached() -> Self {
+        Self {
+            id: String::new(),
+            registry: JobRegistry::new(),
+            cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+/// Paths held by a removal in progress, released when dropped.
+pub struct Claim {
+    id: u64,
+    registry: SharedJobs,
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        self.registry.inner.lock().unwrap().claims.retain(|(id, _)| *id != self.id);
+    }
+}
+
+impl JobRegistry {
+    pub fn new() -> SharedJobs {
+        Arc::new(Self::default())
+    }
+
+    /// Runs `work` on its own thread unless another job or a removal is busy with the path.
+    /// What `work` returns becomes the result or error of the job.
+    pub fn try_spawn<T, F>(self: &Arc<Self>, kind: &str, path: &str, user: &str, work: F) -> Result<Job, String>
+    where
+        T: Serialize,
+        F: FnOnce(&JobHandle) -> Result<T, String> + Send + 'static,
+    {
+        let job = Job {
+            id: format!("{}-{:08x}", Utc::now().format("%Y%m%d%H%M%S"), rand::random::<u32>()),
+            kind: kind.to_string(),
+            path: path.to_string(),
+            user: user.to_string(),
+            state: JobState::Running,
+            created_at: Utc::now(),
+            finished_at: None,
+            progress: Progress::default(),
+            result: None,
+            error: None,
+        };
+        let handle = JobHandle {
+            id: job.id.clone(),
+            registry: self.clone(),
+            cancel: Arc::new(AtomicBool::new(false)),
+        };
+
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.is_busy(path) {
+                return Err(BUSY.to_string());
+            }
+            let entries = &mut inner.entries;
+            let finished = entries.iter().filter(|e| e.job.state != JobState::Running).count();
+            if finished >= MAX_FINISHED_JOBS {
+                if let Some(oldest) = entries.iter().position(|e| e.job.state != JobState::Running) {
+                    entries.remove(oldest);
+                }
+            }
+            entries.push(Entry {
+                job: job.clone(),
+                cancel: handle.cancel.clone(),
+            });
+        }
+
+        println!("Job {}: {} {} started by {}", job.id, kind, path, user);
+        std::thread::spawn(move || {
+            let outcome = work(&handle).and_then(|value| serde_json::to_value(value).map_err(|e| e.to_string()));
+            let cancelled = handle.is_cancelled();
+            handle.registry.update(&handle.id, |job| {
+                job.finished_at = Some(Utc::now());
+                match outcome {
+                    Ok(value) => {
+                        job.state = JobState::Succeeded;
+                        job.result = Some(value);
+                    }
+                    Err(e) => {
+                        job.state = if cancelled { JobState::Cancelled } else { JobState::Failed };
+                        job.error = Some(e);
+                    }
+                }
+                println!("Job {}: {:?}{}", job.id, job.state, job.error.as_ref().map(|e| format!(" ({})", e)).unwrap_or_default());
+            });
+        });
+        Ok(job)
+    }
+
+    /// Marks paths as busy for a removal, so no job starts on them until the claim is dropped.
+    /// Fails when a job is already busy with one of them.
+    pub fn claim(self: &Arc<Self>, paths: &[String]) -> Result<Claim, String> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(path) = paths.iter().find(|p| inner.is_busy(p)) {
+            return Err(format!("{}: {}", path, BUSY));
+        }
+        inner.next_claim += 1;
+        let id = inner.next_claim;
+        inner.claims.extend(paths.iter().map(|p| (id, p.clone())));
+        Ok(Claim { id, registry: self.clone() })
+    }
+
+    fn update(&self, id: &str, update: impl FnOnce(&mut Job)) {
+        if let Some(entry) = self.inner.lock().unwrap().entries.iter_mut().find(|e| e.job.id == id) {
+            update(&mut entry.job);
+        }
+    }
+
+    /// Jobs, newest first.
+    pub fn list(&self) -> Vec<Job> {
+        self.inner.lock().unwrap().entries.iter().rev().map(|e| e.job.clone()).collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        self.inner.lock().unwrap().entries.iter().find(|e| e.job.id == id).map(|e| e.job.clone())
+    }
+
+    /// Whether a job or a removal is busy with the path, inside it or on a directory holding it.
+    pub fn is_busy(&self, path: &str) -> bool {
+        self.inner.lock().unwrap().is_busy(path)
+    }
+
+    /// Asks a running job to stop. It stops at its next check, leaving nothing half done.
+    pub fn cancel(&self, id: &str) -> Option<Job> {
+        let inner = self.inner.lock().unwrap();
+        let entry = inner.entries.iter().find(|e| e.job.id == id)?;
+        if entry.job.state == JobState::Running {
+            entry.cancel.store(true, Ordering::Relaxed);
+        }
+        Some(entry.job.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn wait_for_cancel(handle: &JobHandle) -> Result<(), String> {
+        while !handle.is_cancelled() {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        Err("Cancelled".to_string())
+    }
+
+    #[test]
+    fn busy_paths_overlap() {
+        let registry = JobRegistry::new();
+        let job = registry.try_spawn("archive", "/srv/projects/old", "admin", wait_for_cancel).unwrap();
+
+        assert!(registry.is_busy("/srv/projects/old"));
+        assert!(registry.is_busy("/srv/projects/old/src"));
+        assert!(registry.is_busy("/srv/projects"));
+        assert!(registry.is_busy("/"));
+        assert!(!registry.is_busy("/srv/projects/old2"));
+        assert!(!registry.is_busy("/srv/projects/ol"));
+        assert!(!registry.is_busy("/srv/other"));
+
+        registry.cancel(&job.id);
+        while registry.get(&job.id).unwrap().state == JobState::Running {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(registry.get(&job.id).unwrap().state, JobState::Cancelled);
+        assert!(!registry.is_busy("/srv/projects/old"));
+    }
+
+    #[test]
+    fn jobs_and_claims_exclude_each_other() {
+        let registry = JobRegistry::new();
+        let job = registry.try_spawn("archive", "/srv/a", "admin", wait_for_cancel).unwrap();
+        assert_eq!(
+            registry.try_spawn("archive", "/srv/a/b", "admin", wait_for_cancel).unwrap_err(),
+            BUSY
+        );
+        assert!(registry.claim(&["/srv/b".to_string(), "/srv".to_string()]).is_err());
+
+        // A claim holds all its paths until dropped, a failed claim holds none
+        let claim = registry.claim(&["/srv/b".to_string(), "/srv/c".to_string()]).unwrap();
+        assert!(registry.try_spawn("archive", "/srv/c/d", "admin", wait_for_cancel).is_err());
+        assert!(registry.try_spawn("archive", "/srv", "admin", wait_for_cancel).is_err());
+        assert!(registry.claim(&["/srv/b".to_string()]).is_err());
+        drop(claim);
+        assert!(!registry.is_busy("/srv/b"));
+        assert!(!registry.is_busy("/srv/c"));
+
+        registry.cancel(&job.id);
+        let job = registry.try_spawn("archive", "/srv/c", "admin", wait_for_cancel).unwrap();
+        registry.cancel(&job.id);
+    }
+}
@@ -1,141 +1,141 @@
 mod alerts;
+mod archive;
+mod auth;
+mod cleanup;
+mod cli;
+mod digest;
+mod disks;
+mod export;
+mod history;
+mod imports;
+mod jobs;
+mod metrics;
 mod models;
+mod monitor;
+mod pathmap;
+mod retention;
+mod roots;
 mod scan;
+mod search;
+mod schedule;
+mod secrets;
 mod server;
 mod settings;
+mod templates;
+mod tree;
+mod treemap;
 
 use actix_cors::Cors;
 use actix_files as fs;
-use actix_web::{web, App, HttpServer};
-use settings::{load_settings, SharedSettings, ThresholdType};
+use actix_web::{middleware, web, App, HttpServer};
+use auth::AuthService;
+use clap::Parser;
+use cli::{Cli, Command, Listen, ServeArgs};
+use monitor::MonitorService;
+use settings::{load_settings, SharedSettings};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use sysinfo::Disks;
-use tokio::time;
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
+// ncdu dumps of large trees easily exceed the default 256 KiB payload limit
+const MAX_IMPORT_SIZE: usize = 1024 * 1024 * 1024;
+
+fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
+
+    settings::set_config_file(cli.settings);
+    let is_docker = std::env::var("APP_ENV").unwrap_or_default() == "docker";
+    let mappings = if cli.path_maps.is_empty() && is_docker {
+        vec![pathmap::Mapping::new(&cli.host_root, "/")]
+    } else {
+        cli.path_maps
+    };
+    pathmap::set_mapper(pathmap::PathMapper::new(mappings));
+
+    let code = match cli.command.unwrap_or(Command::Serve(cli.serve)) {
+        Command::Serve(args) => return actix_web::rt::System::new().block_on(serve(args)),
+        Command::Scan { path, format, limit } => cli::scan(&path, format, limit),
+        Command::Export { path, format, output } => cli::export(&path, format, output.as_deref()),
+        Command::Import { file, name } => cli::import(&file, name.as_deref()),
+        Command::Check { path, no_alerts } => cli::check(path.as_deref(), no_alerts),
+        Command::TestAlert { channel, message } => cli::test_alert(channel, message),
+        Command::User { action } => cli::user(action),
+        Command::Token { action } => cli::token(action),
+    };
+    std::process::exit(code);
+}
+
+async fn serve(args: ServeArgs) -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
     let is_docker = std::env::var("APP_ENV").unwrap_or_default() == "docker";
-    println!("Starting Volumetrik server at http://localhost:{}", 8080);
+    println!("Starting Volumetrik server");
     println!("Docker mode: {}", is_docker);
+    for m in pathmap::mapper().mappings() {
+        println!("Path mapping: {} -> {}", m.system, m.display);
+    }
+    println!("Settings file: {}", settings::config_file().display());
+
+    // Mapped directories are the default jail, their display paths mean nothing outside of them
+    let allowed_roots = if args.allowed_roots.is_empty() {
+        pathmap::mapper().mappings().iter().map(|m| std::path::PathBuf::from(&m.system)).collect()
+    } else {
+        args.allowed_roots.clone()
+    };
+    roots::set_allowed_roots(&allowed_roots);
+    if roots::allowed_roots().is_empty() {
+        println!("Allowed roots: entire filesystem");
+    } else {
+        let list: Vec<String> = roots::allowed_roots().iter().map(|r| r.display().to_string()).collect();
+        println!("Allowed roots: {}", list.join(", "));
+    }
+
+    let auth = AuthService::new().map_err(std::io::Error::other)?;
+    if !auth.config().enabled() {
+        // Without authentication every request is an admin, anyone could delete files
+        if args.allow_writes {
+            return Err(std::io::Error::other(
+                "--allow-writes requires authentication, create a user with `volumetrik user add <name>` first",
+            ));
+        }
+        println!("Warning: authentication is not configured, anyone who can reach the server has full access");
+        println!("Create a user with `volumetrik user add <name>`");
+    }
+
+    if args.allow_writes {
+        println!("Write actions: enabled");
+        match &args.trash_dir {
+            Some(dir) => println!("Trash directory: {}", dir.display()),
+            None => println!("Trash directory: none, entries can only be deleted"),
+        }
+    }
+    cleanup::configure(cleanup::CleanupConfig {
+        enabled: args.allow_writes,
+        trash_dir: args.trash_dir.clone(),
+    });
 
     // Load settings
     let settings = load_settings();
     let shared_settings: SharedSettings = Arc::new(Mutex::new(settings));
 
-    // Spawn background monitoring task
-    let monitor_settings = shared_settings.clone();
-    tokio::spawn(async move {
-        // Initial delay to let server start
-        time::sleep(Duration::from_secs(5)).await;
-        let mut disks = Disks::new_with_refreshed_list();
-        
-        loop {
-            let (enabled, paths, check_interval, alert_config) = {
-                let s = monitor_settings.lock().unwrap();
-                (
-                    s.monitoring.enabled,
-                    s.monitoring.paths.clone(),
-                    s.monitoring.check_interval_minutes,
-                    s.alerts.clone(),
-                )
-            };
-
-            if enabled {
-                disks.refresh(true);
-                for item in paths {
-                    let path = &item.path;
-                    let threshold = item.threshold_value;
-                    
-                    // We need to map UI path to system path if in docker
-                    // This logic duplicates server.rs logic, ideally should be shared but for now it's fine
-                    let system_path = if is_docker {
-                        let p = path.replace('\\', "/");
-                        let clean = p.trim_start_matches('/');
-                        if clean.is_empty() { "/host".to_string() } else { format!("/host/{}", clean) }
-                    } else {
-                        path.clone()
-                    };
-
-                    println!("Monitoring: Checking {} ({:?})", system_path, item.threshold_type);
-                    
-                    match item.threshold_type {
-                        ThresholdType::MaxUsed => {
-                            match scan::scan_path(&system_path) {
-                                Ok((_, total_size, _)) => {
-                                    let size_gb = total_size as f64 / 1_073_741_824.0;
-                                    if size_gb > threshold {
-                                        let msg = if let Some(custom) = &alert_config.custom_message {
-                                            custom.replace("{path}", path)
-                                                  .replace("{threshold}", &threshold.to_string())
-                                                  .replace("{current}", &format!("{:.2}", size_gb))
-                                        } else {
-                                            format!(
-                                                "⚠️ Volumetrik Alert: Folder '{}' size is {:.2} GB, exceeding threshold of {:.2} GB.",
-                                                path, size_gb, threshold
-                                            )
-                                        };
-                                        
-                                        println!("{}", msg);
-                                        alerts::send_alert(&alert_config, &msg);
-                                    }
-                                }
-                                Err(e) => println!("Monitoring error for {}: {}", path, e),
-                            }
-                        },
-                        ThresholdType::MinRemaining => {
-                            // Find the disk that contains this path
-                            let path_obj = std::path::Path::new(&system_path);
-                            let mut best_match_len = 0;
-                            let mut available_space = None;
-
-                            for disk in disks.list() {
-                                if path_obj.starts_with(disk.mount_point()) {
-                                    let len = disk.mount_point().as_os_str().len();
-                                    if len >= best_match_len {
-                                        best_match_len = len;
-                                        available_space = Some(disk.available_space());
-                                    }
-                                }
-                            }
-
-                            if let Some(bytes) = available_space {
-                                let free_gb = bytes as f64 / 1_073_741_824.0;
-                                if free_gb < threshold {
-                                     let msg = if let Some(custom) = &alert_config.custom_message {
-                                            custom.replace("{path}", path)
-                                                  .replace("{threshold}", &threshold.to_string())
-                                                  .replace("{current}", &format!("{:.2}", free_gb))
-                                        } else {
-                                            format!(
-                                                "⚠️ Volumetrik Alert: Volume for '{}' has {:.2} GB remaining, below threshold of {:.2} GB.",
-                                                path, free_gb, threshold
-                                            )
-                                        };
-                                    println!("{}", msg);
-                                    alerts::send_alert(&alert_config, &msg);
-                                }
-                            } else {
-                                println!("Monitoring warning: Could not determine disk for path {}", system_path);
-                            }
-                        }
-                    }
-                }
-
-                // Wait for the configured interval
-                time::sleep(Duration::from_secs(check_interval * 60)).await;
-            } else {
-                // If disabled, check again in 10 seconds
-                time::sleep(Duration::from_secs(10)).await;
-            }
-        }
-    });
+    let job_registry = jobs::JobRegistry::new();
+
+    // Spawn background monitoring scheduler
+    let monitor = MonitorService::new(shared_settings.clone(), job_registry.clone());
+    tokio::spawn(monitor.clone().run());
+
+    // Spawn scheduled digest reports
+    digest::spawn_scheduler(shared_settings.clone());
+
+    let import_store = imports::ImportStore::new();
+    let cleanup_service = cleanup::CleanupService::new();
 
     let app_settings = shared_settings.clone();
+    let app_monitor = monitor.clone();
+    let static_dir = args.static_dir.clone();
+    if !static_dir.join("index.html").exists() {
+        println!("Warning: no index.html in static directory '{}'", static_dir.display());
+    }
 
-    HttpServer::new(move || {
+    let mut http_server = HttpServer::new(move || {
         let cors = Cors::default()
             .allow_any_origin()
             .allow_any_method()
@@ -143,18 +143,91 @@ async fn main() -> std::io::Result<()> {
 
         App::new()
             .app_data(web::Data::new(app_settings.clone()))
+            .app_data(web::Data::new(app_monitor.clone()))
+            .app_data(web::Data::new(auth.clone()))
+            .app_data(web::Data::new(import_store.clone()))
+            .app_data(web::Data::new(cleanup_service.clone()))
+            .app_data(web::Data::new(job_registry.clone()))
+            .wrap(middleware::from_fn(auth::require_auth))
             .wrap(cors)
             .route("/api/scan", web::get().to(server::scan))
             .route("/api/select-folder", web::get().to(server::select_folder))
             .route("/api/browse", web::get().to(server::browse))
+            .route("/api/export", web::get().to(server::export))
+            .route("/api/treemap", web::get().to(server::treemap))
+            .route("/api/search", web::get().to(server::search))
+            .service(
+                web::resource("/api/imports")
+                    .app_data(web::PayloadConfig::new(MAX_IMPORT_SIZE))
+                    .route(web::get().to(server::list_imports))
+                    .route(web::post().to(server::create_import)),
+            )
+            .route("/api/imports/{id}", web::delete().to(server::delete_import))
+            .route("/api/imports/{id}/scan", web::get().to(server::import_scan))
+            .route("/api/imports/{id}/treemap", web::get().to(server::import_treemap))
+            .route("/api/imports/{id}/search", web::get().to(server::import_search))
+            .route("/api/disks", web::get().to(server::list_disks))
+            .route("/api/cleanup", web::get().to(server::cleanup_status))
+            .route("/api/cleanup/preview", web::post().to(server::cleanup_preview))
+            .route("/api/cleanup/execute", web::post().to(server::cleanup_execute))
+            .route("/api/cleanup/audit", web::get().to(server::cleanup_audit))
+            .route("/api/retention", web::get().to(server::retention_status))
+            .route("/api/retention/run", web::post().to(server::retention_run))
+            .route("/api/archive", web::post().to(server::archive))
+            .route("/api/jobs", web::get().to(server::list_jobs))
+            .route("/api/jobs/{id}", web::get().to(server::get_job))
+            .route("/api/jobs/{id}", web::delete().to(server::cancel_job))
             .route("/api/health", web::get().to(server::health))
+            .route("/api/auth/login", web::post().to(server::login))
+            .route("/api/auth/logout", web::post().to(server::logout))
+            .route("/api/auth/me", web::get().to(server::auth_status))
             .route("/api/settings", web::get().to(server::get_settings))
             .route("/api/settings", web::post().to(server::update_settings))
             .route("/api/layout", web::post().to(server::update_layout))
+            .route("/api/alerts/test", web::post().to(server::test_alert))
+            .route("/api/alerts/history", web::get().to(server::alert_history))
+            .route("/api/digest/preview", web::get().to(server::digest_preview))
+            .route("/api/digest/send", web::post().to(server::digest_send))
+            .route("/api/monitoring/status", web::get().to(server::monitoring_status))
+            .route("/api/monitoring/check", web::post().to(server::monitoring_check))
+            .route("/metrics", web::get().to(server::metrics))
             // Serve static files
-            .service(fs::Files::new("/", "./static").index_file("index.html"))
-    })
-    .bind(("0.0.0.0", 8080))?
-    .run()
-    .await
+            .service(fs::Files::new("/", &static_dir).index_file("index.html"))
+    });
+
+    for address in args.addresses() {
+        http_server = match address {
+            Listen::Tcp(addr) => {
+                println!("Listening on http://{}", addr);
+                http_server.bind(addr)?
+            }
+            Listen::Unix(path) => {
+                println!("Listening on unix:{}", path.display());
+                #[cfg(unix)]
+                let bound = {
+                    remove_stale_socket(&path)?;
+                    http_server.bind_uds(&path)?
+                };
+                #[cfg(not(unix))]
+                let bound = return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    format!("Unix sockets are not supported on this platform: {}", path.display()),
+                ));
+                bound
+            }
+        };
+    }
+
+    http_server.run().await
+}
+
+/// A socket left behind by a previous run would make the bind fail.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,119 @@
+use crate::alerts::DeliveryResult;
+use crate::disks::{VolumeInfo, UNKNOWN_VOLUME};
+use crate::monitor::{MonitorStatus, PathState};
+use crate::settings::Severity;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+// (channel, success) -> number of deliveries since startup
+static ALERT_DELIVERIES: Mutex<BTreeMap<(&'static str, bool), u64>> = Mutex::new(BTreeMap::new());
+
+pub fn record_delivery(result: &DeliveryResult) {
+    let mut counters = ALERT_DELIVERIES.lock().unwrap();
+    *counters.entry((result.channel.label(), result.success)).or_insert(0) += 1;
+}
+
+/// Writes metric families in the Prometheus text format, or OpenMetrics when `openmetrics` is set.
+struct Exposition {
+    out: String,
+    openmetrics: bool,
+}
+
+impl Exposition {
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        // OpenMetrics names counter families without the `_total` suffix of their samples
+        let family = if self.openmetrics && kind == "counter" {
+            name.trim_end_matches("_total")
+        } else {
+            name
+        };
+        let _ = writeln!(self.out, "# HELP {} {}", family, help);
+        let _ = writeln!(self.out, "# TYPE {} {}", family, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+            .collect();
+        if labels.is_empty() {
+            let _ = writeln!(self.out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(self.out, "{}{{{}}} {}", name, labels.join(","), value);
+        }
+    }
+}
+
+type VolumeGauge = (&'static str, &'static str, fn(&VolumeInfo) -> Option<u64>);
+type PathMetric = (&'static str, &'static str, &'static str, fn(&PathState) -> Option<f64>);
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+pub fn render(status: &MonitorStatus, volumes: &[VolumeInfo], openmetrics: bool) -> String {
+    let mut m = Exposition { out: String::new(), openmetrics };
+
+    m.family("volumetrik_monitoring_enabled", "gauge", "Whether background monitoring is enabled.");
+    m.sample("volumetrik_monitoring_enabled", &[], if status.enabled { 1.0 } else { 0.0 });
+
+    let volume_gauges: [VolumeGauge; 4] = [
+        ("volumetrik_volume_size_bytes", "Total size of the volume in bytes.", |v| Some(v.total)),
+        ("volumetrik_volume_available_bytes", "Available space on the volume in bytes.", |v| Some(v.available)),
+        ("volumetrik_volume_inodes", "Total number of inodes on the volume.", |v| v.inodes_total),
+        ("volumetrik_volume_inodes_free", "Number of free inodes on the volume.", |v| v.inodes_free),
+    ];
+    for (name, help, value) in volume_gauges {
+        m.family(name, "gauge", help);
+        for v in volumes {
+            if let Some(value) = value(v) {
+                let labels = [("mount_point", v.mount_point.as_str()), ("device", v.device.as_str()), ("fstype", v.file_system.as_str())];
+                m.sample(name, &labels, value as f64);
+            }
+        }
+    }
+
+    let path_metrics: [PathMetric; 7] = [
+        ("volumetrik_path_size_bytes", "gauge", "Size of the monitored path in bytes.", |p| p.size_bytes.map(|v| v as f64)),
+        ("volumetrik_path_files", "gauge", "Number of files under the monitored path.", |p| p.file_count.map(|v| v as f64)),
+        ("volumetrik_path_check_duration_seconds", "gauge", "Duration of the last check of the path.", |p| {
+            p.last_duration_ms.map(|ms| ms as f64 / 1000.0)
+        }),
+        ("volumetrik_path_last_check_timestamp_seconds", "gauge", "Unix time of the last check of the path.", |p| {
+            p.last_check.map(|t| t.timestamp() as f64)
+        }),
+        ("volumetrik_path_scan_errors_total", "counter", "Checks of the path that failed since startup.", |p| Some(p.scan_errors as f64)),
+        ("volumetrik_path_threshold_breached", "gauge", "Whether a threshold of the path is breached (1) or not (0).", |p| {
+            p.last_check.map(|_| if p.breached { 1.0 } else { 0.0 })
+        }),
+        ("volumetrik_path_breach_severity", "gauge", "Severity of the current breach: 0 none, 1 info, 2 warning, 3 critical.", |p| {
+            p.last_check.map(|_| match p.severity {
+                None => 0.0,
+                Some(Severity::Info) => 1.0,
+                Some(Severity::Warning) => 2.0,
+                Some(Severity::Critical) => 3.0,
+            })
+        }),
+    ];
+    for (name, kind, help, value) in path_metrics {
+        m.family(name, kind, help);
+        for p in &status.paths {
+            if let Some(value) = value(p) {
+                let mount_point = p.mount_point.as_deref().unwrap_or(UNKNOWN_VOLUME);
+                m.sample(name, &[("path", p.path.as_str()), ("mount_point", mount_point)], value);
+            }
+        }
+    }
+
+    m.family("volumetrik_alerts_sent_total", "counter", "Alert deliveries since startup by channel and result.");
+    for ((channel, success), count) in ALERT_DELIVERIES.lock().unwrap().iter() {
+        let result = if *success { "success" } else { "failure" };
+        m.sample("volumetrik_alerts_sent_total", &[("channel", channel), ("result", result)], *count as f64);
+    }
+
+    if openmetrics {
+        m.out.push_str("# EOF\n");
+    }
+    m.out
+}
@@ -1,3 +1,11 @@
+use crate::alerts::DeliveryResult;
+use crate::auth::Identity;
+use crate::cleanup::CleanupAction;
+use crate::export::ExportFormat;
+use crate::search::SearchSort;
+use crate::settings::AlertChannel;
+use crate::tree::EntryKind;
+use crate::treemap::{self, Pruning};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -15,15 +23,55 @@ pub struct ScanRequest {
     pub path: String,
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanSort {
+    Name,
+    #[default]
+    Size,
+    #[serde(alias = "files")]
+    FileCount,
+    Modified,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScanEntryType {
+    File,
+    Dir,
+}
+
+/// Scan parameters. Without any, every entry is returned largest first.
+#[derive(Debug, Deserialize, Default)]
+pub struct ScanQuery {
+    pub path: String,
+    pub sort: Option<ScanSort>,
+    /// Defaults to ascending for names, descending otherwise
+    pub order: Option<SortOrder>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    /// Leaves out entries smaller than this many bytes
+    pub min_size: Option<u64>,
+    #[serde(rename = "type")]
+    pub kind: Option<ScanEntryType>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ScanResponse {
     pub parent: Option<String>,
     pub current: String,
     pub files: Vec<FileStats>,
+    /// Size and file count of the whole directory, whatever the filters and page
     pub total_size: u64,
     pub total_files: u64,
+    /// Entries directly in the directory
+    pub total_entries: u64,
+    /// Entries left by the `min_size` and `type` filters, across all pages
+    pub matched_entries: u64,
     pub disk_total: Option<u64>,
     pub disk_available: Option<u64>,
+    /// Mount point of the volume holding the path, `None` when unknown
+    pub disk_mount_point: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -32,3 +80,156 @@ pub struct BrowseResponse {
     pub current: String,
     pub directories: Vec<String>,
 }
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct TestAlertRequest {
+    /// Only test this channel, otherwise every configured channel
+    pub channel: Option<AlertChannel>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TestAlertResponse {
+    pub success: bool,
+    pub results: Vec<DeliveryResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct CheckRequest {
+    /// Only check this monitored path, otherwise all of them
+    pub path: Option<String>,
+    /// Wait for the checks to finish before responding
+    #[serde(default)]
+    pub wait: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    pub path: String,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct TreemapQuery {
+    /// Folder at the root of the hierarchy, the root of the dump for imports
+    #[serde(default)]
+    pub path: String,
+    /// Levels of children to return
+    pub depth: Option<usize>,
+    /// Entries smaller than this many bytes are collapsed into "other"
+    pub min_size: Option<u64>,
+    /// Entries smaller than this share (0 to 1) of the root are collapsed into "other"
+    pub min_share: Option<f64>,
+}
+
+impl TreemapQuery {
+    pub fn pruning(&self) -> Pruning {
+        Pruning {
+            depth: self.depth.unwrap_or(treemap::DEFAULT_DEPTH),
+            min_size: self.min_size.unwrap_or(0),
+            min_share: self.min_share.unwrap_or(treemap::DEFAULT_MIN_SHARE),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct SearchQuery {
+    /// Folder to search below, the root of the dump for imports
+    #[serde(default)]
+    pub path: String,
+    /// Glob matched against entry names, case-insensitive
+    pub name: Option<String>,
+    /// Regex matched against entry names
+    pub regex: Option<String>,
+    /// Comma separated extensions
+    pub ext: Option<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Times are Unix seconds, dates, RFC 3339 or ages like `30d`, see `search::parse_time`
+    pub modified_after: Option<String>,
+    pub modified_before: Option<String>,
+    pub accessed_after: Option<String>,
+    pub accessed_before: Option<String>,
+    /// User name or uid
+    pub owner: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: Option<EntryKind>,
+    pub sort: Option<SearchSort>,
+    pub order: Option<SortOrder>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CleanupRequest {
+    pub action: CleanupAction,
+    pub paths: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmCleanupRequest {
+    /// Token of the preview
+    pub token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CleanupStatus {
+    /// Whether the server was started with write support
+    pub enabled: bool,
+    /// UI path of the trash directory, moving to trash is unavailable without one
+    pub trash_dir: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RetentionRunRequest {
+    /// Name of the policy
+    pub name: String,
+    /// Overrides the policy's `dry_run`
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArchiveRequest {
+    /// Directory to archive, UI path
+    pub path: String,
+    /// Keeps the directory once the archive is verified
+    #[serde(default)]
+    pub keep_original: bool,
+    /// zstd compression level, 1 to 19
+    pub level: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ImportQuery {
+    /// Shown in the list of imports, defaults to the root path of the dump
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct DisksQuery {
+    /// Include tmpfs, overlay, proc and other pseudo-filesystems
+    #[serde(default)]
+    pub pseudo: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuthStatus {
+    /// Whether the server requires authentication at all
+    pub auth_enabled: bool,
+    pub authenticated: bool,
+    pub identity: Option<Identity>,
+}
@@ -0,0 +1,415 @@
+use crate::alerts;
+use crate::disks;
+use crate::history;
+use crate::jobs::SharedJobs;
+use crate::pathmap::mapper;
+use crate::retention::RetentionService;
+use crate::scan;
+use crate::schedule;
+use crate::settings::{AlertConfig, MonitoredPath, MonitoringConfig, Severity, SharedSettings, ThresholdLevel, ThresholdType};
+use crate::templates::{AlertContext, ChildSummary};
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use sysinfo::{Disks, System};
+use tokio::task::JoinHandle;
+use tokio::time;
+
+/// Last known state of a monitored path.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct PathState {
+    pub path: String,
+    pub running: bool,
+    pub last_check: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub next_check: Option<DateTime<Utc>>,
+    pub measurements: Vec<Measurement>,
+    /// Mount point of the volume holding the path
+    pub mount_point: Option<String>,
+    /// Folder size and file count, known when a `MaxUsed` threshold required a scan
+    pub size_bytes: Option<u64>,
+    pub file_count: Option<u64>,
+    pub breached: bool,
+    pub severity: Option<Severity>,
+    pub last_alert: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub scan_errors: u64,
+    #[serde(skip)]
+    schedule_key: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Measurement {
+    pub threshold_type: ThresholdType,
+    pub value: f64,
+    pub unit: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonitorStatus {
+    pub enabled: bool,
+    /// Whether the scheduler loop has ticked recently
+    pub running: bool,
+    pub started_at: DateTime<Utc>,
+    pub last_tick: Option<DateTime<Utc>>,
+    pub paths: Vec<PathState>,
+}
+
+/// Result of one check of a monitored path.
+#[derive(Debug, Default)]
+pub struct CheckOutcome {
+    pub measurements: Vec<Measurement>,
+    pub mount_point: Option<String>,
+    pub size_bytes: Option<u64>,
+    pub file_count: Option<u64>,
+    pub breach: Option<Severity>,
+    pub alerted: bool,
+    /// Alert message of the most severe breach
+    pub message: Option<String>,
+    /// Why the alert was not delivered (quiet hours, maintenance window)
+    pub suppressed: Option<String>,
+    pub warning: Option<String>,
+    /// Why the folder could not be scanned, its `MaxUsed` levels were skipped
+    pub scan_error: Option<String>,
+}
+
+/// Background monitoring: schedules every monitored path on its own and keeps their state.
+pub struct MonitorService {
+    settings: SharedSettings,
+    /// Retention policies, evaluated on the same ticks as the monitored paths
+    pub retention: Arc<RetentionService>,
+    started_at: DateTime<Utc>,
+    last_tick: Mutex<Option<DateTime<Utc>>>,
+    states: Mutex<HashMap<String, PathState>>,
+}
+
+pub type SharedMonitor = Arc<MonitorService>;
+
+impl MonitorService {
+    pub fn new(settings: SharedSettings, jobs: SharedJobs) -> SharedMonitor {
+        Arc::new(Self {
+            settings,
+            retention: RetentionService::new(jobs),
+            started_at: Utc::now(),
+            last_tick: Mutex::new(None),
+            states: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Scheduler loop. Each path has its own schedule and its checks run in their own
+    /// blocking task, so a slow scan doesn't delay the others.
+    pub async fn run(self: Arc<Self>) {
+        // Initial delay to let server start
+        time::sleep(Duration::from_secs(5)).await;
+
+        loop {
+            let monitoring = self.settings.lock().unwrap().monitoring.clone();
+            *self.last_tick.lock().unwrap() = Some(Utc::now());
+
+            if monitoring.enabled {
+                let now = Local::now();
+
+                for item in &monitoring.paths {
+                    let key = schedule::schedule_key(item, monitoring.check_interval_minutes);
+                    // New or rescheduled paths are checked right away
+                    let due = match self.states.lock().unwrap().get(&item.path) {
+                        Some(state) => state.schedule_key != key || state.next_check.is_none_or(|next| next <= now),
+                        None => true,
+                    };
+                    if due {
+                        self.spawn_check(item.clone());
+                    }
+                }
+
+                self.states
+                    .lock()
+                    .unwrap()
+                    .retain(|path, _| monitoring.paths.iter().any(|p| &p.path == path));
+            }
+
+            let settings = self.settings.lock().unwrap().clone();
+            self.retention.tick(&settings);
+
+            time::sleep(Duration::from_secs(10)).await;
+        }
+    }
+
+    /// Starts a check of the path unless one is already running, and schedules the next one.
+    fn spawn_check(self: &Arc<Self>, item: MonitoredPath) -> Option<JoinHandle<()>> {
+        let (monitoring, alert_config) = {
+            let s = self.settings.lock().unwrap();
+            (s.monitoring.clone(), s.alerts.clone())
+        };
+
+        {
+            let mut states = self.states.lock().unwrap();
+            let state = states.entry(item.path.clone()).or_insert_with(|| PathState {
+                path: item.path.clone(),
+                ..Default::default()
+            });
+            if state.running {
+                return None;
+            }
+            state.running = true;
+            state.schedule_key = schedule::schedule_key(&item, monitoring.check_interval_minutes);
+            state.next_check = Some(
+                schedule::next_run(&item, monitoring.check_interval_minutes, Local::now()).with_timezone(&Utc),
+            );
+        }
+
+        let service = self.clone();
+        // Scans and alert delivery are blocking, keep them off the async runtime
+        Some(tokio::task::spawn_blocking(move || {
+            let started = Instant::now();
+            println!("Monitoring: Checking {} ({:?})", item.path, item.threshold_type);
+            let disks = Disks::new_with_refreshed_list();
+            let outcome = check_path(&item, &monitoring, &alert_config, &disks);
+            if let Some(message) = &outcome.message {
+                println!("{}", message);
+            }
+            if let Some(reason) = &outcome.suppressed {
+                println!("Monitoring: alert for {} suppressed ({})", item.path, reason);
+            }
+            if let Some(warning) = &outcome.warning {
+                println!("Monitoring warning: {}", warning);
+            }
+            if let Some(e) = &outcome.scan_error {
+                println!("Monitoring error for {}: {}", item.path, e);
+            }
+
+            let mut states = service.states.lock().unwrap();
+            if let Some(state) = states.get_mut(&item.path) {
+                state.running = false;
+                state.last_check = Some(Utc::now());
+                state.last_duration_ms = Some(started.elapsed().as_millis() as u64);
+                state.measurements = outcome.measurements;
+                state.mount_point = outcome.mount_point;
+                state.size_bytes = outcome.size_bytes;
+                state.file_count = outcome.file_count;
+                state.breached = outcome.breach.is_some();
+                state.severity = outcome.breach;
+                if outcome.alerted {
+                    state.last_alert = state.last_check;
+                }
+                if outcome.scan_error.is_some() {
+                    state.scan_errors += 1;
+                }
+                state.last_error = outcome.scan_error.or(outcome.warning);
+            }
+        }))
+    }
+
+    /// Runs a check right away for one path, or for all of them, even when monitoring is disabled.
+    /// Returns the paths that were started; paths already being checked are skipped.
+    pub fn trigger(self: &Arc<Self>, path: Option<&str>) -> Vec<(String, JoinHandle<()>)> {
+        let paths = self.settings.lock().unwrap().monitoring.paths.clone();
+        paths
+            .into_iter()
+            .filter(|item| path.is_none_or(|p| p == item.path))
+            .filter_map(|item| {
+                let path = item.path.clone();
+                self.spawn_check(item).map(|handle| (path, handle))
+            })
+            .collect()
+    }
+
+    pub fn status(&self) -> MonitorStatus {
+        let monitoring = self.settings.lock().unwrap().monitoring.clone();
+        let states = self.states.lock().unwrap();
+        let last_tick = *self.last_tick.lock().unwrap();
+
+        // Keep the configured order, paths never checked yet get an empty state
+        let paths = monitoring
+            .paths
+            .iter()
+            .map(|item| {
+                states.get(&item.path).cloned().unwrap_or_else(|| PathState {
+                    path: item.path.clone(),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        MonitorStatus {
+            enabled: monitoring.enabled,
+            running: last_tick.is_some_and(|t| Utc::now() - t < chrono::Duration::seconds(60)),
+            started_at: self.started_at,
+            last_tick,
+            paths,
+        }
+    }
+}
+
+/// Evaluates every threshold level of a monitored path and sends one alert for the most severe breach.
+pub fn check_path(
+    item: &MonitoredPath,
+    monitoring: &MonitoringConfig,
+    alert_config: &AlertConfig,
+    disks: &Disks,
+) -> CheckOutcome {
+    let path = &item.path;
+
+    let system_path = mapper().to_system(path);
+
+    let disk = disks::disk_for_path(disks, std::path::Path::new(&system_path));
+    let volume = disk.map(|d| (d.total_space(), d.available_space()));
+    let mount_point = disk.map(|d| mapper().to_ui(&d.mount_point().to_string_lossy()));
+
+    // Folder size is only computed when a level needs it, scans are expensive
+    let mut folder_scan = None;
+    let mut breach: Option<(ThresholdLevel, f64)> = None;
+    let mut outcome = CheckOutcome {
+        mount_point,
+        ..Default::default()
+    };
+
+    for level in item.levels() {
+        let current = match level.threshold_type {
+            ThresholdType::MaxUsed => {
+                if folder_scan.is_none() && outcome.scan_error.is_none() {
+                    match scan::scan_path(&system_path) {
+                        Ok((files, total_size, total_files)) => {
+                            outcome.size_bytes = Some(total_size);
+                            outcome.file_count = Some(total_files);
+                            folder_scan = Some((files, total_size));
+                        }
+                        Err(e) => outcome.scan_error = Some(format!("Error scanning {}: {}", system_path, e)),
+                    }
+                }
+                // The other levels, free space ones for instance, are still checked
+                let Some((_, total_size)) = &folder_scan else {
+                    continue;
+                };
+                Some(*total_size as f64 / 1_073_741_824.0)
+            }
+            ThresholdType::MinRemaining => volume.map(|(_, available)| available as f64 / 1_073_741_824.0),
+            ThresholdType::MaxUsedPercent => volume
+                .filter(|(total, _)| *total > 0)
+                .map(|(total, available)| (total - available) as f64 / total as f64 * 100.0),
+        };
+
+        let Some(current) = current else {
+            outcome.warning = Some(format!("Volume of path {} is {}", system_path, disks::UNKNOWN_VOLUME));
+            continue;
+        };
+
+        if !outcome.measurements.iter().any(|m| m.threshold_type == level.threshold_type) {
+            outcome.measurements.push(Measurement {
+                threshold_type: level.threshold_type.clone(),
+                value: current,
+                unit: unit(&level.threshold_type).to_string(),
+            });
+        }
+
+        let breached = match level.threshold_type {
+            ThresholdType::MaxUsed | ThresholdType::MaxUsedPercent => current > level.threshold_value,
+            ThresholdType::MinRemaining => current < level.threshold_value,
+        };
+
+        if breached && breach.as_ref().is_none_or(|(b, _)| level.severity > b.severity) {
+            breach = Some((level, current));
+        }
+    }
+
+    let Some((level, current)) = breach else {
+        return outcome;
+    };
+    outcome.breach = Some(level.severity);
+
+    // Only scan for the largest children when a template actually shows them
+    if folder_scan.is_none() && outcome.scan_error.is_none() && alert_config.uses_variable("top_children") {
+        folder_scan = scan::scan_path(&system_path).ok().map(|(files, total_size, _)| (files, total_size));
+    }
+    let mut top_children: Vec<ChildSummary> = folder_scan
+        .map(|(files, _)| files)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|f| ChildSummary {
+            name: f.name,
+            path: mapper().to_ui(&f.path),
+            size: f.size,
+            size_gb: f.size as f64 / 1_073_741_824.0,
+        })
+        .collect();
+    top_children.sort_by_key(|c| std::cmp::Reverse(c.size));
+    top_children.truncate(5);
+
+    let ctx = AlertContext {
+        path: path.clone(),
+        severity: level.severity.label().to_string(),
+        threshold_type: format!("{:?}", level.threshold_type),
+        threshold: level.threshold_value,
+        current,
+        unit: unit(&level.threshold_type).to_string(),
+        hostname: System::host_name().unwrap_or_default(),
+        timestamp: Local::now().to_rfc3339(),
+        volume_total_gb: volume.map(|(total, _)| total as f64 / 1_073_741_824.0),
+        volume_free_gb: volume.map(|(_, available)| available as f64 / 1_073_741_824.0),
+        percent_used: volume
+            .filter(|(total, _)| *total > 0)
+            .map(|(total, available)| (total - available) as f64 / total as f64 * 100.0),
+        top_children,
+        message: alert_message(path, &level, current),
+    };
+
+    outcome.message = Some(ctx.message.clone());
+    let suppressed = schedule::suppression_reason(monitoring, item, level.severity, Local::now());
+    let results = match &suppressed {
+        Some(_) => Vec::new(),
+        None => alerts::send_alert(alert_config, &ctx, level.severity, &level.channels),
+    };
+    outcome.alerted = !results.is_empty();
+    outcome.suppressed = suppressed.clone();
+
+    history::record(
+        &history::AlertEvent {
+            timestamp: Utc::now(),
+            path: path.clone(),
+            severity: level.severity,
+            threshold_type: level.threshold_type,
+            threshold: level.threshold_value,
+            current,
+            message: ctx.message,
+            channels: level.channels,
+            results,
+            suppressed,
+        },
+        &alert_config.history,
+    );
+
+    outcome
+}
+
+fn unit(threshold_type: &ThresholdType) -> &'static str {
+    match threshold_type {
+        ThresholdType::MaxUsedPercent => "%",
+        _ => "GB",
+    }
+}
+
+fn alert_message(path: &str, level: &ThresholdLevel, current: f64) -> String {
+    let threshold = level.threshold_value;
+
+    let icon = match level.severity {
+        Severity::Info => "ℹ️",
+        Severity::Warning => "⚠️",
+        Severity::Critical => "🚨",
+    };
+
+    match level.threshold_type {
+        ThresholdType::MaxUsed => format!(
+            "{} Volumetrik Alert: Folder '{}' size is {:.2} GB, exceeding threshold of {:.2} GB.",
+            icon, path, current, threshold
+        ),
+        ThresholdType::MinRemaining => format!(
+            "{} Volumetrik Alert: Volume for '{}' has {:.2} GB remaining, below threshold of {:.2} GB.",
+            icon, path, current, threshold
+        ),
+        ThresholdType::MaxUsedPercent => format!(
+            "{} Volumetrik Alert: Volume for '{}' is {:.1}% full, exceeding threshold of {:.1}%.",
+            icon, path, current, threshold
+        ),
+    }
+}
@@ -0,0 +1,368 @@
+use std::sync::OnceLock;
+
+/// Where the host filesystem is mounted in Docker mode.
+pub const DEFAULT_HOST_ROOT: &str = "/host";
+
+static MAPPER: OnceLock<PathMapper> = OnceLock::new();
+
+/// One system-to-display mapping, e.g. `/mnt/nas` shown as `NAS:/`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    /// Directory as seen by the process (inside the container)
+    pub system: String,
+    /// How it is shown in the UI and accepted in requests
+    pub display: String,
+}
+
+impl Mapping {
+    pub fn new(system: &str, display: &str) -> Self {
+        let mut display = display.to_string();
+        // `NAS:` is the root of its own namespace, like `C:\`
+        if display.ends_with(':') {
+            display.push('/');
+        }
+        Self {
+            system: trim_separators(system).to_string(),
+            display: trim_separators(&display).to_string(),
+        }
+    }
+
+    /// Parses `SYSTEM=DISPLAY`, e.g. `/mnt/nas=NAS:/`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        match spec.split_once('=') {
+            Some((system, display)) if !system.trim().is_empty() && !display.trim().is_empty() => {
+                Ok(Self::new(system.trim(), display.trim()))
+            }
+            _ => Err(format!("Invalid path mapping '{}', expected SYSTEM=DISPLAY", spec)),
+        }
+    }
+}
+
+/// Translates between system paths and the paths shown in the UI. Without mappings
+/// both are the same, which is the case when running directly on the host.
+#[derive(Debug, Clone, Default)]
+pub struct PathMapper {
+    mappings: Vec<Mapping>,
+}
+
+impl PathMapper {
+    pub fn new(mappings: Vec<Mapping>) -> Self {
+        Self { mappings }
+    }
+
+    pub fn mappings(&self) -> &[Mapping] {
+        &self.mappings
+    }
+
+    /// System path of a UI path. Paths outside every mapping are returned unchanged.
+    pub fn to_system(&self, ui_path: &str) -> String {
+        let matched = self
+            .mappings
+            .iter()
+            .filter_map(|m| strip_path_prefix(ui_path, &m.display).map(|rest| (m, rest)))
+            .max_by_key(|(m, _)| m.display.len());
+
+        match matched {
+            // System paths are Unix paths when mappings are used (container)
+            Some((m, rest)) => join(&m.system, &rest.replace('\\', "/"), '/'),
+            None => ui_path.to_string(),
+        }
+    }
+
+    /// UI path of a system path. Paths outside every mapping are returned unchanged.
+    pub fn to_ui(&self, system_path: &str) -> String {
+        let matched = self
+            .mappings
+            .iter()
+            .filter_map(|m| strip_path_prefix(system_path, &m.system).map(|rest| (m, rest)))
+            .max_by_key(|(m, _)| m.system.len());
+
+        match matched {
+            Some((m, rest)) => {
+                let separator = separator_of(&m.display);
+                join(&m.display, &rest.replace('/', &separator.to_string()), separator)
+            }
+            None => system_path.to_string(),
+        }
+    }
+
+    /// Parent of a UI path, `None` at the root of a mapping or of a filesystem.
+    pub fn ui_parent(&self, ui_path: &str) -> Option<String> {
+        let trimmed = trim_separators(ui_path);
+        if self.mappings.iter().any(|m| m.display == trimmed) {
+            return None;
+        }
+        parent(trimmed)
+    }
+
+    /// Child of a UI path, using the separator of the path.
+    pub fn ui_child(&self, ui_path: &str, name: &str) -> String {
+        join(trim_separators(ui_path), name, separator_of(ui_path))
+    }
+
+    /// Where browsing starts when no path is given.
+    pub fn default_ui_path(&self) -> String {
+        match self.mappings.first() {
+            Some(m) => m.display.clone(),
+            None if cfg!(target_os = "windows") => "C:\\".to_string(),
+            None => "/".to_string(),
+        }
+    }
+}
+
+/// Sets the mapper used by the whole process. Must be called before the first path is mapped.
+pub fn set_mapper(mapper: PathMapper) {
+    let _ = MAPPER.set(mapper);
+}
+
+pub fn mapper() -> &'static PathMapper {
+    MAPPER.get_or_init(PathMapper::default)
+}
+
+/// Drive letters, labels like `NAS:` and UNC shares accept backslashes as well as slashes.
+fn is_windows_path(path: &str) -> bool {
+    path.starts_with("\\\\")
+        || path.split_once(':').is_some_and(|(label, _)| !label.is_empty() && !label.contains(['/', '\\']))
+}
+
+fn is_separator(path: &str, c: char) -> bool {
+    c == '/' || (c == '\\' && is_windows_path(path))
+}
+
+fn separator_of(path: &str) -> char {
+    if is_windows_path(path) && !path.contains('/') {
+        '\\'
+    } else {
+        '/'
+    }
+}
+
+/// Whether the path is the root of a filesystem: `/`, `C:\`, `NAS:/` or `\\server\share`.
+fn is_root(path: &str) -> bool {
+    if !path.is_empty() && path.chars().all(|c| is_separator(path, c)) {
+        return true;
+    }
+    if let Some(share) = path.strip_prefix("\\\\") {
+        return share.split(['\\', '/']).filter(|c| !c.is_empty()).count() <= 2;
+    }
+    if let Some((label, rest)) = path.split_once(':') {
+        let label_ok = !label.is_empty() && !label.contains(['/', '\\']);
+        let rest_ok = rest.is_empty() || (rest.chars().count() == 1 && rest.chars().all(|c| c == '/' || c == '\\'));
+        return label_ok && rest_ok;
+    }
+    false
+}
+
+/// Removes trailing separators, except the one of a root.
+fn trim_separators(path: &str) -> &str {
+    let mut trimmed = path;
+    while !is_root(trimmed) {
+        match trimmed.chars().last() {
+            Some(c) if is_separator(path, c) => trimmed = &trimmed[..trimmed.len() - c.len_utf8()],
+            _ => break,
+        }
+    }
+    trimmed
+}
+
+/// Remainder of `path` below `prefix`, without leading separators. Only whole components match.
+fn strip_path_prefix<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let path = trim_separators(path);
+    // `NAS:` is the same root as `NAS:/`
+    if is_root(path) && prefix.len() == path.len() + 1 && prefix.starts_with(path) {
+        return Some("");
+    }
+    let rest = strip_prefix_any_separator(path, prefix)?;
+    let at_boundary = rest.is_empty()
+        || prefix.chars().last().is_some_and(|c| is_separator(prefix, c))
+        || rest.chars().next().is_some_and(|c| is_separator(path, c));
+    if !at_boundary {
+        return None;
+    }
+    Some(rest.trim_start_matches(|c| is_separator(path, c)))
+}
+
+/// `str::strip_prefix` where either separator matches the other one in Windows-style paths.
+fn strip_prefix_any_separator<'a>(path: &'a str, prefix: &str) -> Option<&'a str> {
+    let mut chars = path.char_indices();
+    for expected in prefix.chars() {
+        let (_, c) = chars.next()?;
+        let same = c == expected || (is_separator(path, c) && is_separator(prefix, expected));
+        if !same {
+            return None;
+        }
+    }
+    Some(chars.as_str())
+}
+
+fn join(base: &str, rest: &str, separator: char) -> String {
+    if rest.is_empty() {
+        base.to_string()
+    } else if base.chars().last().is_some_and(|c| is_separator(base, c)) {
+        format!("{}{}", base, rest)
+    } else {
+        format!("{}{}{}", base, separator, rest)
+    }
+}
+
+/// Lexical parent of a path, with Unix and Windows separators alike.
+/// Works the same whatever the platform, `std::path` only knows the native style.
+pub fn parent(path: &str) -> Option<String> {
+    let trimmed = trim_separators(path);
+    if is_root(trimmed) {
+        return None;
+    }
+
+    let index = trimmed.rfind(|c| is_separator(trimmed, c))?;
+    let head = &trimmed[..index];
+    if head.is_empty() || is_root(head) && !head.ends_with(['/', '\\']) && !head.starts_with("\\\\") {
+        // `/etc` -> `/`, `C:\Users` -> `C:\`
+        return Some(trimmed[..=index].to_string());
+    }
+    Some(trim_separators(head).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn docker() -> PathMapper {
+        PathMapper::new(vec![Mapping::new("/host", "/"), Mapping::new("/mnt/nas", "NAS:/")])
+    }
+
+    #[test]
+    fn parses_mappings() {
+        assert_eq!(Mapping::parse("/mnt/nas=NAS:/").unwrap(), Mapping::new("/mnt/nas", "NAS:/"));
+        assert_eq!(Mapping::parse(" /host/ = / ").unwrap(), Mapping::new("/host", "/"));
+        assert_eq!(Mapping::parse("/mnt/nas=NAS:").unwrap().display, "NAS:/");
+        assert!(Mapping::parse("/host").is_err());
+        assert!(Mapping::parse("=/").is_err());
+        assert!(Mapping::parse("/host=").is_err());
+    }
+
+    #[test]
+    fn identity_without_mappings() {
+        let mapper = PathMapper::default();
+        for path in ["/", "/var/log", "C:\\Users\\odron", "relative/dir"] {
+            assert_eq!(mapper.to_system(path), path);
+            assert_eq!(mapper.to_ui(path), path);
+        }
+    }
+
+    #[test]
+    fn maps_ui_paths_to_system() {
+        let mapper = docker();
+        assert_eq!(mapper.to_system("/"), "/host");
+        assert_eq!(mapper.to_system("/etc"), "/host/etc");
+        assert_eq!(mapper.to_system("/var/log/"), "/host/var/log");
+        assert_eq!(mapper.to_system("NAS:/"), "/mnt/nas");
+        assert_eq!(mapper.to_system("NAS:"), "/mnt/nas");
+        assert_eq!(mapper.to_system("NAS:/media/movies"), "/mnt/nas/media/movies");
+        // Backslashes only separate components of Windows-style paths
+        assert_eq!(mapper.to_system("\\Users\\odron"), "\\Users\\odron");
+        assert_eq!(mapper.to_system("NAS:\\media\\movies"), "/mnt/nas/media/movies");
+    }
+
+    #[test]
+    fn maps_system_paths_to_ui() {
+        let mapper = docker();
+        assert_eq!(mapper.to_ui("/host"), "/");
+        assert_eq!(mapper.to_ui("/host/"), "/");
+        assert_eq!(mapper.to_ui("/host/etc/hosts"), "/etc/hosts");
+        assert_eq!(mapper.to_ui("/mnt/nas"), "NAS:/");
+        assert_eq!(mapper.to_ui("/mnt/nas/media"), "NAS:/media");
+        // Only whole components match
+        assert_eq!(mapper.to_ui("/hostname/x"), "/hostname/x");
+        assert_eq!(mapper.to_ui("/mnt/nas2/x"), "/mnt/nas2/x");
+        // Outside every mapping
+        assert_eq!(mapper.to_ui("/proc/1"), "/proc/1");
+    }
+
+    #[test]
+    fn longest_mapping_wins() {
+        let mapper = PathMapper::new(vec![Mapping::new("/host", "/"), Mapping::new("/host/srv/projects", "Projects:/")]);
+        assert_eq!(mapper.to_ui("/host/srv"), "/srv");
+        assert_eq!(mapper.to_ui("/host/srv/projects/a"), "Projects:/a");
+        assert_eq!(mapper.to_system("Projects:/a"), "/host/srv/projects/a");
+        assert_eq!(mapper.to_system("/srv/projects/a"), "/host/srv/projects/a");
+    }
+
+    #[test]
+    fn round_trips() {
+        let mapper = docker();
+        for ui in ["/", "/etc", "/var/log/syslog", "NAS:/", "NAS:/media/movies"] {
+            assert_eq!(mapper.to_ui(&mapper.to_system(ui)), ui);
+        }
+    }
+
+    #[test]
+    fn windows_display_paths() {
+        let mapper = PathMapper::new(vec![Mapping::new("/mnt/c", "C:\\")]);
+        assert_eq!(mapper.to_system("C:\\"), "/mnt/c");
+        assert_eq!(mapper.to_system("C:\\Users\\odron"), "/mnt/c/Users/odron");
+        assert_eq!(mapper.to_ui("/mnt/c/Users/odron"), "C:\\Users\\odron");
+        assert_eq!(mapper.ui_parent("C:\\Users"), Some("C:\\".to_string()));
+        assert_eq!(mapper.ui_parent("C:\\"), None);
+    }
+
+    #[test]
+    fn ui_parent_stops_at_mapping_roots() {
+        let mapper = PathMapper::new(vec![Mapping::new("/host", "/"), Mapping::new("/data/projects", "/projects")]);
+        assert_eq!(mapper.ui_parent("/"), None);
+        assert_eq!(mapper.ui_parent("/etc"), Some("/".to_string()));
+        assert_eq!(mapper.ui_parent("/var/log/"), Some("/var".to_string()));
+        assert_eq!(mapper.ui_parent("/projects"), None);
+        assert_eq!(mapper.ui_parent("/projects/a"), Some("/projects".to_string()));
+        assert_eq!(docker().ui_parent("NAS:/"), None);
+        assert_eq!(docker().ui_parent("NAS:/media"), Some("NAS:/".to_string()));
+    }
+
+    // Same paths as repro_path.rs, whose output depends on the platform with std::path
+    #[test]
+    fn windows_parents() {
+        assert_eq!(parent("C:\\Users\\odron\\Documents\\"), Some("C:\\Users\\odron".to_string()));
+        assert_eq!(parent("C:\\Users\\odron\\"), Some("C:\\Users".to_string()));
+        assert_eq!(parent("C:\\Users\\"), Some("C:\\".to_string()));
+        assert_eq!(parent("C:\\"), None);
+        assert_eq!(parent("C:"), None);
+        assert_eq!(parent("c:/Users"), Some("c:/".to_string()));
+        assert_eq!(parent("D:\\data/mixed\\sep"), Some("D:\\data/mixed".to_string()));
+    }
+
+    #[test]
+    fn unc_parents() {
+        assert_eq!(parent("\\\\server\\share\\dir\\file"), Some("\\\\server\\share\\dir".to_string()));
+        assert_eq!(parent("\\\\server\\share\\dir"), Some("\\\\server\\share".to_string()));
+        assert_eq!(parent("\\\\server\\share"), None);
+    }
+
+    #[test]
+    fn unix_parents() {
+        assert_eq!(parent("/"), None);
+        assert_eq!(parent("//"), None);
+        assert_eq!(parent("/etc"), Some("/".to_string()));
+        assert_eq!(parent("/etc/"), Some("/".to_string()));
+        assert_eq!(parent("/var/log/syslog"), Some("/var/log".to_string()));
+        assert_eq!(parent("relative"), None);
+        assert_eq!(parent("relative/dir"), Some("relative".to_string()));
+        // A backslash is an ordinary character in Unix file names
+        assert_eq!(parent("/tmp/a\\b"), Some("/tmp".to_string()));
+    }
+
+    #[test]
+    fn children() {
+        let mapper = docker();
+        assert_eq!(mapper.ui_child("/", "etc"), "/etc");
+        assert_eq!(mapper.ui_child("/etc/", "ssl"), "/etc/ssl");
+        assert_eq!(mapper.ui_child("NAS:/", "media"), "NAS:/media");
+        assert_eq!(mapper.ui_child("C:\\", "Users"), "C:\\Users");
+        assert_eq!(mapper.ui_child("C:\\Users", "odron"), "C:\\Users\\odron");
+    }
+
+    #[test]
+    fn default_path_is_first_mapping() {
+        assert_eq!(docker().default_ui_path(), "/");
+        let nas_first = PathMapper::new(vec![Mapping::new("/mnt/nas", "NAS:/")]);
+        assert_eq!(nas_first.default_ui_path(), "NAS:/");
+    }
+}
@@ -0,0 +1,498 @@
+use crate::alerts::{self, DeliveryResult};
+use crate::cleanup::{self, CleanupReport, Target};
+use crate::digest::format_bytes;
+use crate::jobs::{SharedJobs, BUSY};
+use crate::pathmap::mapper;
+use crate::roots;
+use crate::schedule;
+use crate::search::glob_regex;
+use crate::settings::{AlertConfig, RetentionPolicy, Settings};
+use crate::tree::{self, TreeNode};
+use chrono::{DateTime, Local, Utc};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Entries listed in the state of a policy, the counts cover all of them
+const LISTED_ENTRIES: usize = 100;
+
+/// What a policy run removed, or would remove in dry-run mode.
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub policy: String,
+    pub path: String,
+    pub dry_run: bool,
+    /// Entries selected, oldest first
+    pub targets: Vec<Target>,
+    pub total_size: u64,
+    pub total_files: u64,
+    /// Matches left for a later run because of `max_bytes_per_run`
+    pub deferred: u64,
+    /// Matches that can't be removed, e.g. an allowed root
+    pub errors: Vec<String>,
+    /// Outcome of the removal, `None` in dry-run mode
+    pub cleanup: Option<CleanupReport>,
+    pub notifications: Vec<DeliveryResult>,
+}
+
+/// Last known state of a policy.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct PolicyState {
+    pub name: String,
+    pub path: String,
+    pub running: bool,
+    pub last_run: Option<DateTime<Utc>>,
+    pub next_run: Option<DateTime<Utc>>,
+    pub dry_run: bool,
+    /// Entries selected by the last run
+    pub matched: u64,
+    pub matched_size: u64,
+    /// Entries actually removed by the last run
+    pub removed: u64,
+    pub removed_size: u64,
+    pub failed: u64,
+    pub deferred: u64,
+    /// First selected entries of the last run
+    pub entries: Vec<Target>,
+    pub last_error: Option<String>,
+    #[serde(skip)]
+    schedule_key: String,
+}
+
+/// Schedules the retention policies and keeps their state, driven by the monitoring scheduler.
+pub struct RetentionService {
+    states: Mutex<HashMap<String, PolicyState>>,
+    /// Archive jobs, whose paths are left alone
+    jobs: SharedJobs,
+}
+
+impl RetentionService {
+    pub fn new(jobs: SharedJobs) -> Arc<Self> {
+        Arc::new(Self {
+            states: Mutex::new(HashMap::new()),
+            jobs,
+        })
+    }
+
+    /// Starts the policies that are due. Called on every tick of the monitoring scheduler.
+    pub fn tick(self: &Arc<Self>, settings: &Settings) {
+        let retention = &settings.retention;
+        if !retention.enabled {
+            return;
+        }
+        let default_interval = settings.monitoring.check_interval_minutes;
+        let now = Local::now();
+
+        let mut seen = HashSet::new();
+        for policy in &retention.policies {
+            // Settings with duplicate names are refused, but the file can be edited by hand
+            if !seen.insert(policy.name.as_str()) {
+                continue;
+            }
+            let key = schedule::schedule_key_of(policy.cron.as_deref(), policy.interval_minutes.unwrap_or(default_interval));
+            let due = match self.states.lock().unwrap().get(&policy.name) {
+                Some(state) => state.schedule_key != key || state.next_run.is_none_or(|next| next <= now),
+                None => true,
+            };
+            if due {
+                let service = self.clone();
+                let policy = policy.clone();
+                let alert_config = settings.alerts.clone();
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = service.execute(&policy, &alert_config, policy.dry_run, default_interval) {
+                        println!("Retention error for {}: {}", policy.name, e);
+                    }
+                });
+            }
+        }
+
+        self.states
+            .lock()
+            .unwrap()
+            .retain(|name, _| retention.policies.iter().any(|p| &p.name == name));
+    }
+
+    /// Runs a policy now, unless it is already running, and schedules its next run.
+    pub fn execute(
+        &self,
+        policy: &RetentionPolicy,
+        alert_config: &AlertConfig,
+        dry_run: bool,
+        default_interval: u64,
+    ) -> Result<RunReport, String> {
+        let interval = policy.interval_minutes.unwrap_or(default_interval);
+        {
+            let mut states = self.states.lock().unwrap();
+            let state = states.entry(policy.name.clone()).or_insert_with(|| PolicyState {
+                name: policy.name.clone(),
+                path: policy.path.clone(),
+                ..Default::default()
+            });
+            if state.running {
+                return Err(format!("Policy '{}' is already running", policy.name));
+            }
+            state.running = true;
+            state.dry_run = dry_run;
+            state.schedule_key = schedule::schedule_key_of(policy.cron.as_deref(), interval);
+            state.next_run = Some(
+                schedule::next_run_of(policy.cron.as_deref(), interval, &policy.name, Local::now()).with_timezone(&Utc),
+            );
+        }
+
+        println!("Retention: Running {} on {}{}", policy.name, policy.path, if dry_run { " (dry run)" } else { "" });
+        let result = run_policy(policy, alert_config, dry_run, &self.jobs);
+
+        let mut states = self.states.lock().unwrap();
+        if let Some(state) = states.get_mut(&policy.name) {
+            state.running = false;
+            state.last_run = Some(Utc::now());
+            state.path = policy.path.clone();
+            match &result {
+                Ok(report) => {
+                    // Without write support a run is a dry run whatever was asked
+                    state.dry_run = report.dry_run;
+                    state.matched = report.targets.len() as u64;
+                    state.matched_size = report.total_size;
+                    state.removed = report.cleanup.as_ref().map_or(0, |c| c.results.iter().filter(|r| r.success).count() as u64);
+                    state.removed_size = report.cleanup.as_ref().map_or(0, |c| c.total_size);
+                    state.failed = report.cleanup.as_ref().map_or(0, |c| c.results.iter().filter(|r| !r.success).count() as u64)
+                        + report.errors.len() as u64;
+                    state.deferred = report.deferred;
+                    state.entries = report.targets.iter().take(LISTED_ENTRIES).cloned().collect();
+                    state.last_error = report.errors.first().cloned();
+                }
+                Err(e) => state.last_error = Some(e.clone()),
+            }
+        }
+        result
+    }
+
+    pub fn status(&self, settings: &Settings) -> Vec<PolicyState> {
+        let states = self.states.lock().unwrap();
+        settings
+            .retention
+            .policies
+            .iter()
+            .map(|policy| {
+                states.get(&policy.name).cloned().unwrap_or_else(|| PolicyState {
+                    name: policy.name.clone(),
+                    path: policy.path.clone(),
+                    dry_run: policy.dry_run,
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+}
+
+/// A candidate for removal: UI path, system path and scanned entry.
+type Candidate<'a> = (String, PathBuf, &'a TreeNode);
+
+fn collect<'a>(
+    node: &'a TreeNode,
+    ui_path: &str,
+    system_path: &Path,
+    policy: &RetentionPolicy,
+    pattern: Option<&Regex>,
+    out: &mut Vec<Candidate<'a>>,
+) {
+    for child in &node.children {
+        let child_ui = mapper().ui_child(ui_path, &child.name);
+        let child_system = system_path.join(&child.name);
+        let kind_matches = !child.is_dir() || policy.include_dirs;
+        if kind_matches && pattern.is_none_or(|re| re.is_match(&child.name)) {
+            // A matched directory goes as a whole
+            out.push((child_ui, child_system, child));
+        } else if child.is_dir() && policy.recursive {
+            collect(child, &child_ui, &child_system, policy, pattern, out);
+        }
+    }
+}
+
+/// Age of an entry: a directory is as recent as the newest entry anywhere below it.
+fn newest_modified(node: &TreeNode) -> u64 {
+    node.children.iter().map(newest_modified).fold(node.modified, u64::max)
+}
+
+/// Entries a policy selects below the root of a scanned tree, oldest first. `now` is in Unix seconds.
+fn select<'a>(
+    tree: &'a TreeNode,
+    ui_root: &str,
+    system_root: &Path,
+    policy: &RetentionPolicy,
+    pattern: Option<&Regex>,
+    now: u64,
+) -> Vec<Candidate<'a>> {
+    let mut candidates = Vec::new();
+    collect(tree, ui_root, system_root, policy, pattern, &mut candidates);
+
+    // Newest first to keep the newest ones, then oldest first so limits remove the oldest
+    let mut candidates: Vec<(u64, Candidate)> =
+        candidates.into_iter().map(|candidate| (newest_modified(candidate.2), candidate)).collect();
+    candidates.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    let cutoff = policy.max_age_days.map(|days| now.saturating_sub(days.saturating_mul(86400)));
+    let mut selected: Vec<Candidate> = candidates
+        .into_iter()
+        .skip(policy.keep_newest.unwrap_or(0))
+        .filter(|(modified, _)| cutoff.is_none_or(|cutoff| *modified < cutoff))
+        .map(|(_, candidate)| candidate)
+        .collect();
+    selected.reverse();
+    selected
+}
+
+/// Keeps the targets, oldest first, that fit in `max_bytes` together. Returns them with
+/// the number of targets left for a later run.
+fn limit_bytes(targets: Vec<Target>, max_bytes: Option<u64>) -> (Vec<Target>, u64) {
+    let mut kept = Vec::new();
+    let mut deferred = 0;
+    let mut total_size = 0u64;
+    for target in targets {
+        if max_bytes.is_some_and(|max| total_size + target.size > max) {
+            deferred += 1;
+            continue;
+        }
+        total_size += target.size;
+        kept.push(target);
+    }
+    (kept, deferred)
+}
+
+/// Selects the entries a policy removes, oldest first, and removes them unless `dry_run`.
+/// Without `--allow-writes` every run is a dry run.
+pub fn run_policy(
+    policy: &RetentionPolicy,
+    alert_config: &AlertConfig,
+    dry_run: bool,
+    jobs: &SharedJobs,
+) -> Result<RunReport, String> {
+    if policy.max_age_days.is_none() && policy.keep_newest.is_none() {
+        return Err("A policy needs max_age_days or keep_newest".to_string());
+    }
+    let pattern = policy.pattern.as_deref().filter(|p| !p.trim().is_empty()).map(glob_regex).transpose()?;
+
+    let root = roots::resolve(&mapper().to_system(&policy.path)).map_err(|e| e.to_string())?;
+    let tree = tree::scan_tree(&root).map_err(|e| format!("Error scanning path: {}", e))?;
+    let now = Utc::now().timestamp().max(0) as u64;
+    let selected = select(&tree, &mapper().to_ui(&root.to_string_lossy()), &root, policy, pattern.as_ref(), now);
+
+    let mut resolved = Vec::new();
+    let mut errors = Vec::new();
+    for (ui_path, system_path, node) in selected {
+        if jobs.is_busy(&ui_path) {
+            errors.push(format!("{}: {}", ui_path, BUSY));
+            continue;
+        }
+        match cleanup::resolve_target(&system_path.to_string_lossy()) {
+            Ok(system_path) => resolved.push(Target {
+                path: ui_path,
+                system_path,
+                is_dir: node.is_dir(),
+                size: node.size,
+                files: node.file_count,
+            }),
+            Err(e) => errors.push(format!("{}: {}", ui_path, e)),
+        }
+    }
+    let (targets, deferred) = limit_bytes(resolved, policy.max_bytes_per_run);
+    let total_size = targets.iter().map(|t| t.size).sum();
+
+    let writes_disabled = !dry_run && !cleanup::config().enabled;
+    if writes_disabled {
+        errors.push("Write actions are disabled, start the server with --allow-writes".to_string());
+    }
+    let mut dry_run = dry_run || writes_disabled;
+    // Held until the removal is done, so no archive starts on these paths meanwhile
+    let paths: Vec<String> = targets.iter().map(|t| t.path.clone()).collect();
+    let claim = (!dry_run).then(|| jobs.claim(&paths));
+    if let Some(Err(e)) = &claim {
+        errors.push(e.clone());
+        dry_run = true;
+    }
+    let report = (!dry_run && !targets.is_empty())
+        .then(|| cleanup::run(&format!("retention:{}", policy.name), policy.action, &targets));
+    drop(claim);
+
+    let mut run = RunReport {
+        policy: policy.name.clone(),
+        path: policy.path.clone(),
+        dry_run,
+        total_files: targets.iter().map(|t| t.files).sum(),
+        total_size,
+        targets,
+        deferred,
+        errors,
+        cleanup: report,
+        notifications: Vec::new(),
+    };
+    if alert_config.enabled && (!run.targets.is_empty() || !run.errors.is_empty()) {
+        run.notifications = alerts::send_message(alert_config, "Volumetrik Retention", &report_message(&run), &policy.channels);
+    }
+    Ok(run)
+}
+
+fn report_message(run: &RunReport) -> String {
+    let mut message = match &run.cleanup {
+        Some(report) => {
+            let removed = report.results.iter().filter(|r| r.success).count();
+            let failed = report.results.len() - removed;
+            let mut message = format!(
+                "🧹 Volumetrik Retention: policy '{}' on {} {} {} entries ({}).",
+                run.policy,
+                run.path,
+                if report.action == cleanup::CleanupAction::Trash { "trashed" } else { "deleted" },
+                removed,
+                format_bytes(report.total_size)
+            );
+            if failed > 0 {
+                message.push_str(&format!(" {} failed.", failed));
+            }
+            message
+        }
+        None => format!(
+            "🧹 Volumetrik Retention (dry run): policy '{}' on {} would remove {} entries ({}).",
+            run.policy,
+            run.path,
+            run.targets.len(),
+            format_bytes(run.total_size)
+        ),
+    };
+    if run.deferred > 0 {
+        message.push_str(&format!(" {} entries left for the next run by the byte limit.", run.deferred));
+    }
+    for error in &run.errors {
+        message.push_str(&format!("\n{}", error));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: u64 = 86400;
+    const NOW: u64 = 1000 * DAY;
+
+    fn policy(extra: serde_json::Value) -> RetentionPolicy {
+        let mut value = serde_json::json!({ "name": "test", "path": "/backups" });
+        value.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// backup-N modified N days ago, plus a directory of logs.
+    fn fixture() -> TreeNode {
+        let mut children: Vec<TreeNode> = (1..=5)
+            .map(|n| TreeNode::test_file(&format!("backup-{}.tar", n), n * 100, NOW - n * DAY))
+            .collect();
+        children.push(TreeNode::test_dir(
+            "logs",
+            NOW - 50 * DAY,
+            vec![
+                TreeNode::test_file("app.log", 10, NOW),
+                TreeNode::test_file("app.log.1", 20, NOW - 40 * DAY),
+                TreeNode::test_file("app.log.2", 30, NOW - 60 * DAY),
+            ],
+        ));
+        TreeNode::test_dir("backups", NOW, children)
+    }
+
+    fn selected(tree: &TreeNode, policy: &RetentionPolicy) -> Vec<String> {
+        let pattern = policy.pattern.as_deref().map(|p| glob_regex(p).unwrap());
+        select(tree, "/backups", Path::new("/srv/backups"), policy, pattern.as_ref(), NOW)
+            .into_iter()
+            .map(|(ui_path, _, _)| ui_path)
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_newest() {
+        let tree = fixture();
+        let keep = policy(serde_json::json!({ "pattern": "backup-*", "keep_newest": 2 }));
+        assert_eq!(selected(&tree, &keep), ["/backups/backup-5.tar", "/backups/backup-4.tar", "/backups/backup-3.tar"]);
+
+        let keep_all = policy(serde_json::json!({ "pattern": "backup-*", "keep_newest": 10 }));
+        assert!(selected(&tree, &keep_all).is_empty());
+    }
+
+    #[test]
+    fn max_age_cutoff() {
+        let tree = fixture();
+        // Modified exactly 3 days ago is not older than 3 days
+        let old = policy(serde_json::json!({ "pattern": "backup-*", "max_age_days": 3 }));
+        assert_eq!(selected(&tree, &old), ["/backups/backup-5.tar", "/backups/backup-4.tar"]);
+
+        // Both limits: older than 2 days, but never one of the 4 newest
+        let both = policy(serde_json::json!({ "pattern": "backup-*", "max_age_days": 2, "keep_newest": 4 }));
+        assert_eq!(selected(&tree, &both), ["/backups/backup-5.tar"]);
+    }
+
+    #[test]
+    fn recursive_and_directories() {
+        let tree = fixture();
+        // Directories don't match unless include_dirs, nor are they entered unless recursive
+        let flat = policy(serde_json::json!({ "max_age_days": 30 }));
+        assert!(selected(&tree, &flat).is_empty());
+
+        let recursive = policy(serde_json::json!({ "pattern": "*.log.*", "max_age_days": 30, "recursive": true }));
+        assert_eq!(selected(&tree, &recursive), ["/backups/logs/app.log.2", "/backups/logs/app.log.1"]);
+
+        // The logs directory is old itself but still holds a new file
+        let dirs = policy(serde_json::json!({ "max_age_days": 30, "include_dirs": true, "recursive": true }));
+        assert!(selected(&tree, &dirs).is_empty());
+    }
+
+    #[test]
+    fn directories_age_by_their_newest_entry() {
+        let old_dir = |name: &str, newest: u64| {
+            TreeNode::test_dir(
+                name,
+                NOW - 90 * DAY,
+                vec![TreeNode::test_dir("nested", NOW - 90 * DAY, vec![TreeNode::test_file("data", 1, newest)])],
+            )
+        };
+        let tree = TreeNode::test_dir(
+            "backups",
+            NOW,
+            vec![old_dir("a", NOW - 50 * DAY), old_dir("b", NOW - 10 * DAY), old_dir("c", NOW - 70 * DAY)],
+        );
+
+        // A matched directory goes as a whole, its entries are not listed on their own
+        let dirs = policy(serde_json::json!({ "max_age_days": 30, "include_dirs": true }));
+        assert_eq!(selected(&tree, &dirs), ["/backups/c", "/backups/a"]);
+
+        // The newest entry decides which directories are the newest ones too
+        let keep = policy(serde_json::json!({ "keep_newest": 1, "include_dirs": true }));
+        assert_eq!(selected(&tree, &keep), ["/backups/c", "/backups/a"]);
+    }
+
+    fn target(name: &str, size: u64) -> Target {
+        Target {
+            path: name.to_string(),
+            system_path: PathBuf::from(name),
+            is_dir: false,
+            size,
+            files: 1,
+        }
+    }
+
+    #[test]
+    fn byte_limit_defers_the_rest() {
+        let targets = || vec![target("a", 500), target("b", 400), target("c", 100), target("d", 50)];
+        let names = |targets: &[Target]| targets.iter().map(|t| t.path.clone()).collect::<Vec<_>>();
+
+        let (kept, deferred) = limit_bytes(targets(), None);
+        assert_eq!((kept.len(), deferred), (4, 0));
+
+        // Larger entries are left for later, smaller newer ones still fit
+        let (kept, deferred) = limit_bytes(targets(), Some(600));
+        assert_eq!((names(&kept), deferred), (vec!["a".to_string(), "c".to_string()], 2));
+
+        let (kept, deferred) = limit_bytes(targets(), Some(1050));
+        assert_eq!((kept.len(), deferred), (4, 0));
+
+        let (kept, deferred) = limit_bytes(targets(), Some(0));
+        assert_eq!((kept.len(), deferred), (0, 4));
+    }
+}
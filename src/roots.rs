@@ -0,0 +1,180 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// Canonical allowed roots, empty when the whole filesystem is allowed
+static ALLOWED_ROOTS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+/// Why a requested path was refused.
+#[derive(Debug)]
+pub enum PathError {
+    NotFound(String),
+    Forbidden(String),
+}
+
+impl std::fmt::Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathError::NotFound(msg) | PathError::Forbidden(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// Canonical form of an existing path: absolute, with symlinks and `..` resolved.
+pub fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    let canonical = std::fs::canonicalize(path)?;
+    // Drop the `\\?\` verbatim prefix Windows adds, UI paths don't have it
+    #[cfg(target_os = "windows")]
+    let canonical = PathBuf::from(canonical.to_string_lossy().trim_start_matches(r"\\?\").to_string());
+    Ok(canonical)
+}
+
+/// Sets the directories requests may reach. Must be called before the first request.
+pub fn set_allowed_roots(roots: &[PathBuf]) {
+    let canonical = roots
+        .iter()
+        .filter_map(|root| match canonicalize(root) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                println!("Ignoring allowed root '{}': {}", root.display(), e);
+                None
+            }
+        })
+        .collect();
+    let _ = ALLOWED_ROOTS.set(canonical);
+}
+
+pub fn allowed_roots() -> &'static [PathBuf] {
+    ALLOWED_ROOTS.get_or_init(Vec::new)
+}
+
+/// Whether a canonical path is inside one of the allowed roots.
+pub fn is_allowed(canonical: &Path) -> bool {
+    is_allowed_in(allowed_roots(), canonical)
+}
+
+fn is_allowed_in(roots: &[PathBuf], canonical: &Path) -> bool {
+    roots.is_empty() || roots.iter().any(|root| canonical.starts_with(root))
+}
+
+/// Whether a canonical path is a parent of an allowed root, so it can be browsed to reach it.
+pub fn leads_to_allowed(canonical: &Path) -> bool {
+    leads_to_allowed_in(allowed_roots(), canonical)
+}
+
+fn leads_to_allowed_in(roots: &[PathBuf], canonical: &Path) -> bool {
+    roots.iter().any(|root| root.starts_with(canonical))
+}
+
+/// Resolves a requested system path and checks it against the allowed roots.
+/// Symlinks are resolved first, so a link pointing outside the roots is refused.
+pub fn resolve(system_path: &str) -> Result<PathBuf, PathError> {
+    resolve_in(allowed_roots(), system_path)
+}
+
+fn resolve_in(roots: &[PathBuf], system_path: &str) -> Result<PathBuf, PathError> {
+    let canonical = match canonicalize(Path::new(system_path)) {
+        Ok(path) => path,
+        Err(e) => {
+            // Don't tell whether paths outside the roots exist, the nearest existing parent
+            // may be a symlink leading out of them
+            let parent = Path::new(system_path).ancestors().skip(1).find_map(|p| canonicalize(p).ok());
+            if !is_allowed_in(roots, parent.as_deref().unwrap_or(Path::new(system_path))) {
+                return Err(PathError::Forbidden("Access to this path is not allowed".to_string()));
+            }
+            return Err(PathError::NotFound(format!("Path not found: {} ({})", system_path, e)));
+        }
+    };
+
+    if !is_allowed_in(roots, &canonical) {
+        println!("Refused access to '{}' (resolves to '{}')", system_path, canonical.display());
+        return Err(PathError::Forbidden("Access to this path is not allowed".to_string()));
+    }
+    Ok(canonical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn paths(list: &[&str]) -> Vec<PathBuf> {
+        list.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn sibling_prefixes() {
+        let roots = paths(&["/srv/a"]);
+        assert!(is_allowed_in(&roots, Path::new("/srv/a")));
+        assert!(is_allowed_in(&roots, Path::new("/srv/a/b")));
+        assert!(!is_allowed_in(&roots, Path::new("/srv/ab")));
+        assert!(!is_allowed_in(&roots, Path::new("/srv/ab/a")));
+        assert!(!is_allowed_in(&roots, Path::new("/srv")));
+
+        assert!(leads_to_allowed_in(&roots, Path::new("/")));
+        assert!(leads_to_allowed_in(&roots, Path::new("/srv")));
+        assert!(leads_to_allowed_in(&roots, Path::new("/srv/a")));
+        assert!(!leads_to_allowed_in(&roots, Path::new("/srv/ab")));
+        assert!(!leads_to_allowed_in(&roots, Path::new("/srv/a/b")));
+    }
+
+    #[test]
+    fn no_roots_allow_everything() {
+        assert!(is_allowed_in(&[], Path::new("/")));
+        assert!(is_allowed_in(&[], Path::new("/etc/passwd")));
+        // Nothing to lead to, every path is allowed already
+        assert!(!leads_to_allowed_in(&[], Path::new("/")));
+
+        let dir = canonicalize(&std::env::temp_dir()).unwrap();
+        assert_eq!(resolve_in(&[], &dir.to_string_lossy()).unwrap(), dir);
+        assert!(matches!(resolve_in(&[], "/volumetrik-missing"), Err(PathError::NotFound(_))));
+    }
+
+    fn forbidden(roots: &[PathBuf], path: &Path) -> bool {
+        matches!(resolve_in(roots, &path.to_string_lossy()), Err(PathError::Forbidden(_)))
+    }
+
+    #[test]
+    fn traversal_is_resolved_first() {
+        let base = std::env::temp_dir().join(format!("volumetrik-roots-{:08x}", rand::random::<u32>()));
+        fs::create_dir_all(base.join("a/sub")).unwrap();
+        fs::create_dir_all(base.join("ab")).unwrap();
+        let base = canonicalize(&base).unwrap();
+        let roots = vec![base.join("a")];
+
+        assert_eq!(resolve_in(&roots, &base.join("a/sub").to_string_lossy()).unwrap(), base.join("a/sub"));
+        assert_eq!(resolve_in(&roots, &base.join("a/sub/..").to_string_lossy()).unwrap(), base.join("a"));
+        assert!(forbidden(&roots, &base.join("a/..")));
+        assert!(forbidden(&roots, &base.join("a/../ab")));
+        assert!(forbidden(&roots, &base.join("a/sub/../../ab")));
+        assert!(forbidden(&roots, &base.join("ab")));
+
+        // Missing paths are only reported inside the roots
+        assert!(matches!(resolve_in(&roots, &base.join("a/missing").to_string_lossy()), Err(PathError::NotFound(_))));
+        assert!(forbidden(&roots, &base.join("ab/missing")));
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_followed() {
+        use std::os::unix::fs::symlink;
+
+        let base = std::env::temp_dir().join(format!("volumetrik-roots-{:08x}", rand::random::<u32>()));
+        fs::create_dir_all(base.join("a/sub")).unwrap();
+        fs::create_dir_all(base.join("ab")).unwrap();
+        let base = canonicalize(&base).unwrap();
+        let roots = vec![base.join("a")];
+        symlink(base.join("ab"), base.join("a/escape")).unwrap();
+        symlink("sub", base.join("a/inside")).unwrap();
+        symlink(base.join("a/sub"), base.join("ab/into-root")).unwrap();
+
+        assert!(forbidden(&roots, &base.join("a/escape")));
+        assert!(forbidden(&roots, &base.join("a/escape/anything")));
+        assert!(forbidden(&roots, &base.join("a/escape/missing/deeper")));
+        assert_eq!(resolve_in(&roots, &base.join("a/inside").to_string_lossy()).unwrap(), base.join("a/sub"));
+        // A link from outside into a root resolves to the allowed target
+        assert_eq!(resolve_in(&roots, &base.join("ab/into-root").to_string_lossy()).unwrap(), base.join("a/sub"));
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::models::FileStats;
+use crate::models::{FileStats, ScanEntryType, ScanQuery, ScanSort, SortOrder};
 use rayon::prelude::*;
 use std::fs::{self, Metadata};
 use std::path::{Path, PathBuf};
@@ -67,7 +67,8 @@ pub fn scan_path(path_str: &str) -> Result<(Vec<FileStats>, u64, u64), std::io::
             let name = entry.file_name().to_string_lossy().to_string();
             let metadata = entry.metadata().ok();
             
-            let is_dir = entry_path.is_dir();
+            // Symlinks are not followed, they could lead outside the scanned tree
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
             let mut size = 0;
             let mut file_count = 0;
             let mut modified = 0;
@@ -102,3 +103,132 @@ pub fn scan_path(path_str: &str) -> Result<(Vec<FileStats>, u64, u64), std::io::
 
     Ok((results, total_size, total_files))
 }
+
+/// Filters, sorts and paginates the entries of a scanned directory.
+/// Returns the page and the number of entries left by the filters.
+pub fn arrange(files: Vec<FileStats>, query: &ScanQuery) -> (Vec<FileStats>, u64) {
+    let mut files: Vec<FileStats> = files
+        .into_iter()
+        .filter(|f| query.min_size.is_none_or(|min| f.size >= min))
+        .filter(|f| match query.kind {
+            Some(ScanEntryType::Dir) => f.is_dir,
+            Some(ScanEntryType::File) => !f.is_dir,
+            None => true,
+        })
+        .collect();
+    let matched = files.len() as u64;
+
+    let sort = query.sort.unwrap_or_default();
+    let order = query.order.unwrap_or(if sort == ScanSort::Name { SortOrder::Asc } else { SortOrder::Desc });
+    files.sort_by(|a, b| {
+        let ordering = match sort {
+            ScanSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            ScanSort::Size => a.size.cmp(&b.size),
+            ScanSort::FileCount => a.file_count.cmp(&b.file_count),
+            ScanSort::Modified => a.modified.cmp(&b.modified),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        match order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+
+    let page = files
+        .into_iter()
+        .skip(query.offset.unwrap_or(0))
+        .take(query.limit.unwrap_or(usize::MAX))
+        .collect();
+    (page, matched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, is_dir: bool, size: u64, file_count: u64, modified: u64) -> FileStats {
+        FileStats {
+            path: format!("/data/{}", name),
+            name: name.to_string(),
+            is_dir,
+            size,
+            file_count,
+            modified,
+        }
+    }
+
+    fn fixture() -> Vec<FileStats> {
+        vec![
+            entry("beta", true, 500, 12, 30),
+            entry("Alpha.txt", false, 100, 0, 20),
+            entry("gamma.iso", false, 900, 0, 10),
+            entry("delta", true, 100, 3, 40),
+            entry("empty", true, 0, 0, 50),
+        ]
+    }
+
+    fn names(files: &[FileStats]) -> Vec<&str> {
+        files.iter().map(|f| f.name.as_str()).collect()
+    }
+
+    fn arranged(query: ScanQuery) -> (Vec<String>, u64) {
+        let (page, matched) = arrange(fixture(), &query);
+        (names(&page).into_iter().map(String::from).collect(), matched)
+    }
+
+    #[test]
+    fn largest_first_by_default() {
+        let (page, matched) = arranged(ScanQuery::default());
+        // Equal sizes fall back to the name, reversed along with the order
+        assert_eq!(page, ["gamma.iso", "beta", "delta", "Alpha.txt", "empty"]);
+        assert_eq!(matched, 5);
+    }
+
+    #[test]
+    fn sort_keys_and_orders() {
+        let by = |sort, order| arranged(ScanQuery { sort: Some(sort), order, ..Default::default() }).0;
+        // Names sort ascending by default, ignoring case
+        assert_eq!(by(ScanSort::Name, None), ["Alpha.txt", "beta", "delta", "empty", "gamma.iso"]);
+        assert_eq!(by(ScanSort::Name, Some(SortOrder::Desc)), ["gamma.iso", "empty", "delta", "beta", "Alpha.txt"]);
+        assert_eq!(by(ScanSort::Size, Some(SortOrder::Asc)), ["empty", "Alpha.txt", "delta", "beta", "gamma.iso"]);
+        assert_eq!(by(ScanSort::FileCount, None), ["beta", "delta", "gamma.iso", "empty", "Alpha.txt"]);
+        assert_eq!(by(ScanSort::Modified, None), ["empty", "delta", "beta", "Alpha.txt", "gamma.iso"]);
+    }
+
+    #[test]
+    fn filters() {
+        let (page, matched) = arranged(ScanQuery { min_size: Some(100), ..Default::default() });
+        assert_eq!(page, ["gamma.iso", "beta", "delta", "Alpha.txt"]);
+        assert_eq!(matched, 4);
+
+        let (page, matched) = arranged(ScanQuery { kind: Some(ScanEntryType::Dir), ..Default::default() });
+        assert_eq!(page, ["beta", "delta", "empty"]);
+        assert_eq!(matched, 3);
+
+        let (page, matched) = arranged(ScanQuery {
+            kind: Some(ScanEntryType::File),
+            min_size: Some(101),
+            ..Default::default()
+        });
+        assert_eq!(page, ["gamma.iso"]);
+        assert_eq!(matched, 1);
+
+        let (page, matched) = arranged(ScanQuery { min_size: Some(u64::MAX), ..Default::default() });
+        assert!(page.is_empty());
+        assert_eq!(matched, 0);
+    }
+
+    #[test]
+    fn pages() {
+        let page = |offset, limit| arranged(ScanQuery { offset, limit, ..Default::default() });
+        assert_eq!(page(None, Some(2)), (vec!["gamma.iso".to_string(), "beta".to_string()], 5));
+        assert_eq!(page(Some(2), Some(2)).0, ["delta", "Alpha.txt"]);
+        assert_eq!(page(Some(4), Some(2)).0, ["empty"]);
+        assert_eq!(page(Some(3), None).0, ["Alpha.txt", "empty"]);
+        // The match count does not depend on the page
+        assert_eq!(page(Some(5), Some(2)), (Vec::new(), 5));
+        assert_eq!(page(Some(usize::MAX), Some(usize::MAX)), (Vec::new(), 5));
+        assert_eq!(page(Some(0), Some(0)), (Vec::new(), 5));
+        assert_eq!(page(Some(0), Some(usize::MAX)).0.len(), 5);
+    }
+}
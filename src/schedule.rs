@@ -0,0 +1,255 @@
+use crate::settings::{MonitoredPath, MonitoringConfig, Severity};
+use chrono::{DateTime, Duration, Local, Utc};
+use cron::Schedule;
+use std::str::FromStr;
+
+/// Parses a cron expression. Standard 5-field expressions get a leading seconds field.
+/// Weekdays follow crontab: 0 or 7 is Sunday, 1 is Monday.
+pub fn parse_cron(expr: &str) -> Result<Schedule, String> {
+    let expr = expr.trim();
+    let mut fields: Vec<&str> = expr.split_whitespace().collect();
+    if fields.len() == 5 {
+        fields.insert(0, "0");
+    }
+    let invalid = |e: String| format!("Invalid cron expression '{}': {}", expr, e);
+    let weekdays = fields.get(5).map(|field| crate_weekdays(field)).transpose().map_err(invalid)?;
+    if let Some(weekdays) = &weekdays {
+        fields[5] = weekdays;
+    }
+    Schedule::from_str(&fields.join(" ")).map_err(|e| invalid(e.to_string()))
+}
+
+/// Rewrites a crontab weekday field to the numbering of the `cron` crate, where Sunday is 1
+/// and Saturday 7. Day names are left as they are.
+fn crate_weekdays(field: &str) -> Result<String, String> {
+    let day = |value: &str| match value.parse::<u32>() {
+        Ok(n) if n <= 7 => Ok(n % 7 + 1),
+        _ => Err(format!("invalid weekday '{}', use 0-7 or day names", value)),
+    };
+    let mut items = Vec::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (item, None),
+        };
+        let numeric = range.chars().any(|c| c.is_ascii_digit());
+        if !numeric || range == "*" || range == "?" {
+            items.push(item.to_string());
+            continue;
+        }
+        if range.chars().any(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("weekday '{}' mixes numbers and day names", item));
+        }
+        let translated = match range.split_once('-') {
+            None => day(range)?.to_string(),
+            Some((start, end)) => {
+                let (start, end_value) = (day(start)?, end.parse::<u32>().ok());
+                match end_value {
+                    // Up to Sunday as 7: through Saturday, then Sunday
+                    Some(7) if step.is_some() => {
+                        return Err(format!("weekday '{}' can't step up to 7, use day names", item))
+                    }
+                    Some(7) if start == 1 => "1-7".to_string(),
+                    Some(7) => format!("{}-7,1", start),
+                    _ => format!("{}-{}", start, day(end)?),
+                }
+            }
+        };
+        items.push(match step {
+            Some(step) => format!("{}/{}", translated, step),
+            None => translated,
+        });
+    }
+    Ok(items.join(","))
+}
+
+/// Key identifying the schedule of a path, so a changed configuration reschedules it.
+pub fn schedule_key(item: &MonitoredPath, default_interval: u64) -> String {
+    schedule_key_of(item.cron.as_deref(), item.interval_minutes.unwrap_or(default_interval))
+}
+
+pub fn schedule_key_of(cron: Option<&str>, interval_minutes: u64) -> String {
+    match cron {
+        Some(expr) if !expr.trim().is_empty() => format!("cron:{}", expr.trim()),
+        _ => format!("every:{}", interval_minutes),
+    }
+}
+
+/// Next time the path should be checked after `after`.
+/// Falls back to the interval when the cron expression is invalid.
+pub fn next_run(item: &MonitoredPath, default_interval: u64, after: DateTime<Local>) -> DateTime<Local> {
+    next_run_of(item.cron.as_deref(), item.interval_minutes.unwrap_or(default_interval), &item.path, after)
+}
+
+/// Next run of a cron expression or interval, `name` identifying the job in logs.
+pub fn next_run_of(cron: Option<&str>, interval_minutes: u64, name: &str, after: DateTime<Local>) -> DateTime<Local> {
+    if let Some(expr) = cron.filter(|e| !e.trim().is_empty()) {
+        match parse_cron(expr) {
+            Ok(schedule) => {
+                if let Some(next) = schedule.after(&after).next() {
+                    return next;
+                }
+            }
+            Err(e) => println!("Monitoring: {} for {}, using interval instead", e, name),
+        }
+    }
+
+    after + Duration::minutes(interval_minutes.max(1) as i64)
+}
+
+/// Returns why an alert for this path must not be sent right now, if it must not.
+pub fn suppression_reason(config: &MonitoringConfig, item: &MonitoredPath, severity: Severity, now: DateTime<Local>) -> Option<String> {
+    let utc = now.with_timezone(&Utc);
+    for window in &config.maintenance_windows {
+        let covers_path = window.paths.is_empty() || window.paths.contains(&item.path);
+        if covers_path && utc >= window.start && utc < window.end {
+            return Some(match &window.description {
+                Some(d) => format!("maintenance window ({})", d),
+                None => "maintenance window".to_string(),
+            });
+        }
+    }
+
+    if let Some(quiet) = item.quiet_hours.as_ref().or(config.quiet_hours.as_ref()) {
+        let critical_allowed = quiet.allow_critical && severity == Severity::Critical;
+        if quiet.contains(now.time()) && !critical_allowed {
+            return Some("quiet hours".to_string());
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::QuietHours;
+    use chrono::{NaiveTime, TimeZone};
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn local(d: u32, h: u32, m: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 1, d, h, m, 0).unwrap()
+    }
+
+    fn quiet(start: NaiveTime, end: NaiveTime, allow_critical: bool) -> QuietHours {
+        QuietHours { start, end, allow_critical }
+    }
+
+    #[test]
+    fn quiet_hours_within_a_day() {
+        let lunch = quiet(time(12, 0), time(14, 0), false);
+        assert!(!lunch.contains(time(11, 59)));
+        assert!(lunch.contains(time(12, 0)));
+        assert!(lunch.contains(time(13, 59)));
+        assert!(!lunch.contains(time(14, 0)));
+    }
+
+    #[test]
+    fn quiet_hours_past_midnight() {
+        let night = quiet(time(22, 0), time(7, 0), false);
+        assert!(night.contains(time(22, 0)));
+        assert!(night.contains(time(23, 59)));
+        assert!(night.contains(time(0, 0)));
+        assert!(night.contains(time(6, 59)));
+        assert!(!night.contains(time(7, 0)));
+        assert!(!night.contains(time(21, 59)));
+        assert!(!night.contains(time(12, 0)));
+    }
+
+    #[test]
+    fn empty_quiet_hours() {
+        let none = quiet(time(8, 0), time(8, 0), false);
+        assert!(!none.contains(time(8, 0)));
+        assert!(!none.contains(time(20, 0)));
+    }
+
+    fn monitoring(quiet_hours: Option<QuietHours>) -> (MonitoringConfig, MonitoredPath) {
+        let config: MonitoringConfig = serde_json::from_value(serde_json::json!({
+            "enabled": true,
+            "paths": [],
+            "check_interval_minutes": 60,
+        }))
+        .unwrap();
+        let item: MonitoredPath = serde_json::from_value(serde_json::json!({
+            "path": "/data",
+            "threshold_type": "MaxUsedPercent",
+            "threshold_value": 90.0,
+        }))
+        .unwrap();
+        (MonitoringConfig { quiet_hours, ..config }, item)
+    }
+
+    #[test]
+    fn suppression_by_quiet_hours() {
+        let (config, mut item) = monitoring(Some(quiet(time(22, 0), time(7, 0), true)));
+        assert!(suppression_reason(&config, &item, Severity::Warning, local(5, 23, 0)).is_some());
+        assert!(suppression_reason(&config, &item, Severity::Critical, local(5, 23, 0)).is_none());
+        assert!(suppression_reason(&config, &item, Severity::Warning, local(5, 12, 0)).is_none());
+
+        // The path's own quiet hours replace the global ones
+        item.quiet_hours = Some(quiet(time(12, 0), time(13, 0), false));
+        assert!(suppression_reason(&config, &item, Severity::Warning, local(5, 23, 0)).is_none());
+        assert!(suppression_reason(&config, &item, Severity::Critical, local(5, 12, 30)).is_some());
+    }
+
+    #[test]
+    fn parses_cron_expressions() {
+        assert!(parse_cron("*/15 * * * *").is_ok());
+        assert!(parse_cron(" 0 3 * * * ").is_ok());
+        assert!(parse_cron("30 0 3 * * *").is_ok());
+        assert!(parse_cron("every hour").is_err());
+        assert!(parse_cron("61 * * * *").is_err());
+    }
+
+    #[test]
+    fn crontab_weekdays() {
+        // 2026-01-03 is a Saturday, 2026-01-05 a Monday
+        let next = |expr: &str, after| next_run_of(Some(expr), 60, "test", after);
+        let saturday = local(3, 12, 0);
+        assert_eq!(next("0 3 * * 1-5", saturday), local(5, 3, 0));
+        assert_eq!(next("0 3 * * 1-5", local(9, 12, 0)), local(12, 3, 0));
+        assert_eq!(next("0 3 * * 0", saturday), local(4, 3, 0));
+        assert_eq!(next("0 3 * * 7", saturday), local(4, 3, 0));
+        assert_eq!(next("0 3 * * 6", local(5, 12, 0)), local(10, 3, 0));
+        assert_eq!(next("0 3 * * 5-7", saturday), local(4, 3, 0));
+        assert_eq!(next("0 3 * * 5-7", local(4, 12, 0)), local(9, 3, 0));
+        assert_eq!(next("0 3 * * 0-7", saturday), local(4, 3, 0));
+        assert_eq!(next("0 3 * * 2,4", saturday), local(6, 3, 0));
+        assert_eq!(next("0 3 * * 1/2", local(5, 12, 0)), local(7, 3, 0));
+        assert_eq!(next("0 3 * * MON-FRI", saturday), local(5, 3, 0));
+        assert_eq!(next("0 3 * * SUN", saturday), local(4, 3, 0));
+        // The optional seconds field doesn't shift the weekday
+        assert_eq!(next("30 0 3 * * 1", saturday), local(5, 3, 0) + Duration::seconds(30));
+    }
+
+    #[test]
+    fn invalid_weekdays() {
+        assert!(parse_cron("0 3 * * 8").unwrap_err().contains("invalid weekday '8'"));
+        assert!(parse_cron("0 3 * * 1-MON").is_err());
+        assert!(parse_cron("0 3 * * 1-7/2").is_err());
+        assert!(parse_cron("0 3 * * 5-1").is_err());
+    }
+
+    #[test]
+    fn next_runs() {
+        let after = local(5, 2, 30);
+        assert_eq!(next_run_of(Some("0 3 * * *"), 60, "test", after), local(5, 3, 0));
+        assert_eq!(next_run_of(Some("0 1 * * *"), 60, "test", after), local(6, 1, 0));
+        // Interval when there is no cron, or an invalid one
+        assert_eq!(next_run_of(None, 45, "test", after), local(5, 3, 15));
+        assert_eq!(next_run_of(Some("  "), 45, "test", after), local(5, 3, 15));
+        assert_eq!(next_run_of(Some("bogus"), 45, "test", after), local(5, 3, 15));
+        // At least a minute apart
+        assert_eq!(next_run_of(None, 0, "test", after), local(5, 2, 31));
+    }
+
+    #[test]
+    fn schedule_keys() {
+        assert_eq!(schedule_key_of(Some(" 0 3 * * * "), 60), "cron:0 3 * * *");
+        assert_eq!(schedule_key_of(Some(""), 60), "every:60");
+        assert_eq!(schedule_key_of(None, 15), "every:15");
+    }
+}
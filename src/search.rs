@@ -0,0 +1,392 @@
+use crate::models::{SearchQuery, SortOrder};
+use crate::pathmap::mapper;
+use crate::tree::{EntryKind, TreeNode};
+use chrono::{NaiveDate, Utc};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+pub const DEFAULT_LIMIT: usize = 100;
+pub const MAX_LIMIT: usize = 1000;
+
+// Keeps user supplied patterns from compiling into huge automatons
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchSort {
+    #[default]
+    Size,
+    Name,
+    Path,
+    Modified,
+    Accessed,
+}
+
+/// Compiles a shell glob (`*`, `?`, `[abc]`, `[!abc]`) into a case-insensitive regex matching whole names.
+pub fn glob_regex(pattern: &str) -> Result<Regex, String> {
+    let mut expr = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => expr.push_str(".*"),
+            '?' => expr.push('.'),
+            '[' => {
+                let mut class = String::new();
+                let mut closed = false;
+                if chars.peek() == Some(&'!') {
+                    chars.next();
+                    class.push('^');
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    if c == '\\' || c == '[' {
+                        class.push('\\');
+                    }
+                    class.push(c);
+                }
+                if !closed {
+                    return Err(format!("Unclosed '[' in pattern '{}'", pattern));
+                }
+                expr.push('[');
+                expr.push_str(&class);
+                expr.push(']');
+            }
+            c => expr.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    expr.push('$');
+    RegexBuilder::new(&expr)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))
+}
+
+/// Parses a point in time: Unix seconds, `YYYY-MM-DD`, RFC 3339, or an age such as `12h`, `30d`, `2w` or `1y`
+/// meaning that long ago.
+pub fn parse_time(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(seconds);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp().max(0) as u64);
+    }
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.timestamp().max(0) as u64);
+    }
+    let age = parse_age(value).ok_or_else(|| format!("Invalid time '{}'", value))?;
+    Ok((Utc::now().timestamp().max(0) as u64).saturating_sub(age))
+}
+
+/// Parses an age such as `12h`, `30d`, `2w` or `1y` into seconds.
+pub fn parse_age(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let unit = value.chars().last()?;
+    let seconds = match unit {
+        'h' => 3600,
+        'd' => 86400,
+        'w' => 7 * 86400,
+        'y' => 365 * 86400,
+        _ => return None,
+    };
+    value[..value.len() - unit.len_utf8()].trim().parse::<u64>().ok().map(|n| n.saturating_mul(seconds))
+}
+
+#[cfg(unix)]
+fn uid_of_user(name: &str) -> Option<u32> {
+    std::fs::read_to_string("/etc/passwd").ok()?.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != name {
+            return None;
+        }
+        fields.nth(1)?.parse().ok()
+    })
+}
+
+#[cfg(not(unix))]
+fn uid_of_user(_name: &str) -> Option<u32> {
+    None
+}
+
+/// Filters of a search, checked and compiled from the query.
+#[derive(Debug, Default)]
+pub struct Filter {
+    name: Option<Regex>,
+    extensions: Vec<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<u64>,
+    modified_before: Option<u64>,
+    accessed_after: Option<u64>,
+    accessed_before: Option<u64>,
+    uid: Option<u32>,
+    kind: Option<EntryKind>,
+}
+
+impl Filter {
+    pub fn from_query(query: &SearchQuery) -> Result<Self, String> {
+        let name = match (&query.name, &query.regex) {
+            (Some(_), Some(_)) => return Err("Use either name or regex, not both".to_string()),
+            (Some(glob), None) => Some(glob_regex(glob)?),
+            (None, Some(expr)) => Some(
+                RegexBuilder::new(expr)
+                    .size_limit(REGEX_SIZE_LIMIT)
+                    .build()
+                    .map_err(|e| format!("Invalid regex: {}", e))?,
+            ),
+            (None, None) => None,
+        };
+
+        let extensions = query
+            .ext
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+            .filter(|e| !e.is_empty())
+            .collect();
+
+        let time = |value: &Option<String>| value.as_deref().map(parse_time).transpose();
+
+        let uid = match query.owner.as_deref().map(str::trim) {
+            None | Some("") => None,
+            Some(owner) => match owner.parse::<u32>() {
+                Ok(uid) => Some(uid),
+                Err(_) => Some(uid_of_user(owner).ok_or_else(|| format!("Unknown user '{}'", owner))?),
+            },
+        };
+
+        Ok(Self {
+            name,
+            extensions,
+            min_size: query.min_size,
+            max_size: query.max_size,
+            modified_after: time(&query.modified_after)?,
+            modified_before: time(&query.modified_before)?,
+            accessed_after: time(&query.accessed_after)?,
+            accessed_before: time(&query.accessed_before)?,
+            uid,
+            kind: query.kind,
+        })
+    }
+
+    pub fn matches(&self, node: &TreeNode) -> bool {
+        if self.kind.is_some_and(|kind| kind != node.kind) {
+            return false;
+        }
+        if self.name.as_ref().is_some_and(|re| !re.is_match(&node.name)) {
+            return false;
+        }
+        if !self.extensions.is_empty() {
+            let extension = node.name.rsplit_once('.').filter(|(stem, _)| !stem.is_empty()).map(|(_, ext)| ext);
+            if !extension.is_some_and(|ext| self.extensions.iter().any(|e| e.eq_ignore_ascii_case(ext))) {
+                return false;
+            }
+        }
+        if self.min_size.is_some_and(|min| node.size < min) || self.max_size.is_some_and(|max| node.size > max) {
+            return false;
+        }
+        if self.modified_after.is_some_and(|t| node.modified < t) || self.modified_before.is_some_and(|t| node.modified >= t) {
+            return false;
+        }
+        if self.accessed_after.is_some_and(|t| node.accessed < t) || self.accessed_before.is_some_and(|t| node.accessed >= t) {
+            return false;
+        }
+        if self.uid.is_some() && node.uid != self.uid {
+            return false;
+        }
+        true
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub path: String,
+    pub name: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    pub file_count: u64,
+    pub modified: u64,
+    pub accessed: u64,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    pub root: String,
+    pub results: Vec<SearchResult>,
+    /// Entries matching the filters, across all pages
+    pub total_matches: u64,
+    /// Sum of the sizes of all matches. A matched directory counts with everything below it.
+    pub total_size: u64,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// Searches the entries below the root of `tree`, `root_path` being the path shown for it.
+pub fn search(tree: &TreeNode, root_path: &str, filter: &Filter, query: &SearchQuery) -> SearchResponse {
+    let mut matches: Vec<(String, &TreeNode)> = Vec::new();
+    for child in &tree.children {
+        child.walk(&mapper().ui_child(root_path, &child.name), &mut |path, node| {
+            if filter.matches(node) {
+                matches.push((path.to_string(), node));
+            }
+        });
+    }
+
+    let total_matches = matches.len() as u64;
+    let total_size = matches.iter().map(|(_, node)| node.size).sum();
+
+    let sort = query.sort.unwrap_or_default();
+    matches.sort_by(|(a_path, a), (b_path, b)| {
+        let ordering = match sort {
+            SearchSort::Size => a.size.cmp(&b.size),
+            SearchSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SearchSort::Path => Ordering::Equal,
+            SearchSort::Modified => a.modified.cmp(&b.modified),
+            SearchSort::Accessed => a.accessed.cmp(&b.accessed),
+        }
+        .then_with(|| a_path.cmp(b_path));
+        match query.order.unwrap_or(default_order(sort)) {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+    let results = matches
+        .into_iter()
+        .skip(offset)
+        .take(limit)
+        .map(|(path, node)| SearchResult {
+            path,
+            name: node.name.clone(),
+            kind: node.kind,
+            size: node.size,
+            file_count: node.file_count,
+            modified: node.modified,
+            accessed: node.accessed,
+            uid: node.uid,
+            gid: node.gid,
+        })
+        .collect();
+
+    SearchResponse {
+        root: root_path.to_string(),
+        results,
+        total_matches,
+        total_size,
+        offset,
+        limit,
+    }
+}
+
+/// Largest and most recent first, names and paths alphabetically.
+fn default_order(sort: SearchSort) -> SortOrder {
+    match sort {
+        SearchSort::Name | SearchSort::Path => SortOrder::Asc,
+        _ => SortOrder::Desc,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        glob_regex(pattern).unwrap().is_match(name)
+    }
+
+    #[test]
+    fn glob_wildcards() {
+        assert!(matches("*.log", "app.log"));
+        assert!(matches("*.log", ".log"));
+        assert!(!matches("*.log", "app.log.1"));
+        assert!(matches("app.log.?", "app.log.1"));
+        assert!(!matches("app.log.?", "app.log.10"));
+        // Regex characters are literal
+        assert!(matches("a+b (1).txt", "a+b (1).txt"));
+        assert!(!matches("a.c", "abc"));
+        assert!(matches("*", ""));
+    }
+
+    #[test]
+    fn glob_is_case_insensitive() {
+        assert!(matches("*.JPG", "photo.jpg"));
+        assert!(matches("readme*", "README.md"));
+        assert!(matches("[a-c]*", "Backup"));
+    }
+
+    #[test]
+    fn glob_classes() {
+        assert!(matches("file[0-9].txt", "file7.txt"));
+        assert!(!matches("file[0-9].txt", "filex.txt"));
+        assert!(matches("[!.]*", "visible"));
+        assert!(!matches("[!.]*", ".hidden"));
+        assert!(!matches("file[!0-9]", "file1"));
+        assert!(matches("file[!0-9]", "files"));
+        // Backslashes and brackets inside a class are literal
+        assert!(matches(r"a[\]b", r"a\b"));
+        assert!(matches("a[[]b", "a[b"));
+    }
+
+    #[test]
+    fn glob_errors() {
+        assert!(glob_regex("file[0-9").is_err());
+        assert!(glob_regex("[!").is_err());
+        assert!(glob_regex("[").is_err());
+        assert!(glob_regex("[z-a]").is_err());
+    }
+
+    #[test]
+    fn ages() {
+        assert_eq!(parse_age("12h"), Some(12 * 3600));
+        assert_eq!(parse_age(" 30d "), Some(30 * 86400));
+        assert_eq!(parse_age("2 w"), Some(14 * 86400));
+        assert_eq!(parse_age("1y"), Some(365 * 86400));
+        assert_eq!(parse_age("0d"), Some(0));
+        assert_eq!(parse_age(&format!("{}y", u64::MAX)), Some(u64::MAX));
+    }
+
+    #[test]
+    fn invalid_ages() {
+        assert_eq!(parse_age(""), None);
+        assert_eq!(parse_age("d"), None);
+        assert_eq!(parse_age("12"), None);
+        assert_eq!(parse_age("12m"), None);
+        assert_eq!(parse_age("-1d"), None);
+        assert_eq!(parse_age("1.5d"), None);
+        // Multibyte characters must not split a char boundary
+        assert_eq!(parse_age("5é"), None);
+        assert_eq!(parse_age("éh"), None);
+        assert_eq!(parse_age("5日"), None);
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(parse_time("0"), Ok(0));
+        assert_eq!(parse_time(" 1767225600 "), Ok(1_767_225_600));
+        assert_eq!(parse_time("2026-01-01"), Ok(1_767_225_600));
+        assert_eq!(parse_time("1960-01-01"), Ok(0));
+        assert_eq!(parse_time("2026-01-01T02:00:00+02:00"), Ok(1_767_225_600));
+        assert_eq!(parse_time("2026-01-01T00:00:00Z"), Ok(1_767_225_600));
+        assert!(parse_time("2026-13-01").is_err());
+        assert!(parse_time("yesterday").is_err());
+        assert!(parse_time("").is_err());
+    }
+
+    #[test]
+    fn times_from_ages() {
+        let now = Utc::now().timestamp() as u64;
+        let day_ago = parse_time("1d").unwrap();
+        assert!(day_ago <= now - 86400 && day_ago >= now - 86400 - 5);
+        assert_eq!(parse_time(&format!("{}y", u64::MAX)), Ok(0));
+    }
+}
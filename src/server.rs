@@ -1,7 +1,26 @@
-use actix_web::{web, HttpResponse, Responder};
-use crate::models::{ScanRequest, ScanResponse, BrowseResponse};
-use crate::scan::scan_path;
+use actix_web::cookie::{time::Duration as CookieDuration, Cookie, SameSite};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use crate::alerts::send_test_alert;
+use crate::archive;
+use crate::cleanup::{self, AuditQuery, CleanupAction, SharedCleanup};
+use crate::jobs::SharedJobs;
+use crate::auth::{AuthMethod, Identity, SharedAuth, SESSION_COOKIE};
+use crate::digest;
+use crate::export;
+use crate::disks;
+use crate::metrics;
+use crate::history::{self, HistoryQuery};
+use crate::imports::{self, SharedImports};
+use crate::models::{ArchiveRequest, AuthStatus, BrowseResponse, CheckRequest, CleanupRequest, CleanupStatus, ConfirmCleanupRequest, DisksQuery, ExportQuery, ImportQuery, SearchQuery, TreemapQuery, LoginRequest, RetentionRunRequest, ScanQuery, ScanRequest, ScanResponse, TestAlertRequest, TestAlertResponse};
+use crate::monitor::SharedMonitor;
+use crate::pathmap::mapper;
+use crate::roots::{self, PathError};
+use crate::scan::{self, scan_path};
+use crate::search;
+use crate::secrets;
 use crate::settings::{SharedSettings, Settings, save_settings};
+use crate::tree;
+use crate::treemap;
 use std::path::Path;
 use rfd::FileDialog;
 use std::env;
@@ -11,37 +30,6 @@ fn is_docker() -> bool {
     env::var("APP_ENV").unwrap_or_default() == "docker"
 }
 
-fn map_to_system_path(path: &str) -> String {
-    if is_docker() {
-        // Normalize path separators
-        let path = path.replace('\\', "/");
-        let clean_path = path.trim_start_matches('/');
-        
-        if clean_path.is_empty() {
-            return "/host".to_string();
-        }
-        return format!("/host/{}", clean_path);
-    }
-    path.to_string()
-}
-
-fn map_to_ui_path(path: &str) -> String {
-    if is_docker() {
-        // Normalize path separators for comparison
-        let path = path.replace('\\', "/");
-        
-        if path == "/host" || path == "/host/" {
-            return "/".to_string();
-        }
-        if path.starts_with("/host/") {
-            return path.replacen("/host", "", 1);
-        }
-        // Fallback: if path doesn't start with /host, it might be a relative path or error
-        return path;
-    }
-    path.to_string()
-}
-
 pub async fn select_folder() -> impl Responder {
     // Check if running in Docker
     if is_docker() {
@@ -63,25 +51,40 @@ pub async fn select_folder() -> impl Responder {
     }
 }
 
-pub async fn browse(info: web::Query<ScanRequest>) -> impl Responder {
+pub async fn browse(identity: Identity, info: web::Query<ScanRequest>) -> impl Responder {
     let mut ui_path = info.path.clone();
     
-    // Default logic
     if ui_path.is_empty() {
-        if is_docker() {
-            ui_path = "/".to_string();
-        } else {
-            #[cfg(target_os = "windows")]
-            { ui_path = "C:\\".to_string(); }
-            #[cfg(not(target_os = "windows"))]
-            { ui_path = "/".to_string(); }
-        }
+        ui_path = mapper().default_ui_path();
+    }
+
+    // Ancestors of the allowed prefixes can be browsed, but only show the way to them
+    let restricted = !identity.can_access(&ui_path);
+    if restricted && !identity.can_traverse(&ui_path) {
+        return HttpResponse::Forbidden().json("Access to this path is not allowed");
     }
 
-    let system_path_str = map_to_system_path(&ui_path);
+    let system_path_str = mapper().to_system(&ui_path);
     println!("Browsing UI path: '{}' -> System path: '{}'", ui_path, system_path_str);
     
     let path = Path::new(&system_path_str);
+
+    // Parents of the allowed roots can be browsed too, but only show the way to them
+    let (canonical, outside_roots) = match roots::resolve(&system_path_str) {
+        Ok(canonical) => (canonical, false),
+        Err(PathError::Forbidden(e)) => match roots::canonicalize(path) {
+            Ok(canonical) if roots::leads_to_allowed(&canonical) => (canonical, true),
+            _ => return HttpResponse::Forbidden().json(e),
+        },
+        Err(e) => return HttpResponse::NotFound().json(e.to_string()),
+    };
+    // The same applies to where the path leads
+    let canonical_ui = mapper().to_ui(&canonical.to_string_lossy());
+    let restricted = restricted || !identity.can_access(&canonical_ui);
+    if restricted && !identity.can_traverse(&canonical_ui) {
+        return HttpResponse::Forbidden().json("Access to this path is not allowed");
+    }
+
     let mut directories = Vec::new();
 
     if let Ok(entries) = std::fs::read_dir(path) {
@@ -96,32 +99,24 @@ pub async fn browse(info: web::Query<ScanRequest>) -> impl Responder {
         }
     }
     
+    if outside_roots {
+        directories.retain(|name| {
+            let child = canonical.join(name);
+            roots::is_allowed(&child) || roots::leads_to_allowed(&child)
+        });
+    }
+    if restricted {
+        directories.retain(|name| {
+            let child = mapper().ui_child(&ui_path, name);
+            let target = mapper().ui_child(&canonical_ui, name);
+            (identity.can_access(&child) || identity.can_traverse(&child))
+                && (identity.can_access(&target) || identity.can_traverse(&target))
+        });
+    }
     directories.sort_by_key(|name| name.to_lowercase());
 
-    let parent = path.parent().map(|p| {
-        let s = p.to_string_lossy().to_string();
-        // If we are at /host (system path), parent should be None for UI
-        if is_docker() && s == "/host" {
-             return "".to_string(); // Will be handled as None or empty
-        }
-        // Map back to UI path
-        map_to_ui_path(&s)
-    });
-    
-    // Filter out empty parent if it results in empty string and we are not at root
-    let parent = match parent {
-        Some(p) if p.is_empty() && ui_path != "/" => Some("/".to_string()), // Parent of /Users is /
-        Some(p) if p.is_empty() => None, // Parent of / is None
-        Some(p) => Some(p),
-        None => None,
-    };
-
-    // Special case for Docker root parent
-    let parent = if is_docker() && ui_path == "/" {
-        None
-    } else {
-        parent
-    };
+    let parent = mapper().ui_parent(&ui_path);
+    let parent = parent.filter(|p| identity.can_access(p) || identity.can_traverse(p));
 
     HttpResponse::Ok().json(BrowseResponse {
         parent,
@@ -130,98 +125,63 @@ pub async fn browse(info: web::Query<ScanRequest>) -> impl Responder {
     })
 }
 
-pub async fn scan(info: web::Query<ScanRequest>) -> impl Responder {
-    let ui_path = &info.path;
-    
+/// Whether the caller may access a resolved system path. Checked after resolving too, so that
+/// a symlink inside the caller's prefixes can't lead to another part of the allowed roots.
+fn can_access_system(identity: &Identity, system_path: &Path) -> bool {
+    identity.can_access(&mapper().to_ui(&system_path.to_string_lossy()))
+}
+
+/// Canonical system path of a UI path the caller may scan, or the error response.
+fn resolve_scan_path(identity: &Identity, ui_path: &str) -> Result<String, HttpResponse> {
     // Basic security check (very minimal)
     if ui_path.contains("..") {
-        return HttpResponse::BadRequest().json("Invalid path");
+        return Err(HttpResponse::BadRequest().json("Invalid path"));
+    }
+    if !identity.can_access(ui_path) {
+        return Err(HttpResponse::Forbidden().json("Access to this path is not allowed"));
     }
 
-    let mut system_path = map_to_system_path(ui_path);
-    
-    // If not in Docker, resolve relative paths to absolute to ensure parent navigation works
-    if !is_docker() {
-        if let Ok(p) = std::fs::canonicalize(&system_path) {
-            let s = p.to_string_lossy().to_string();
-            #[cfg(target_os = "windows")]
-            let s = s.trim_start_matches(r"\\?\").to_string();
-            #[cfg(not(target_os = "windows"))]
-            let s = s; // No-op
-            system_path = s;
+    // Resolve relative paths and symlinks to ensure parent navigation works
+    // and that the path stays inside the allowed roots
+    match roots::resolve(&mapper().to_system(ui_path)) {
+        Ok(canonical) if !can_access_system(identity, &canonical) => {
+            Err(HttpResponse::Forbidden().json("Access to this path is not allowed"))
         }
+        Ok(canonical) => Ok(canonical.to_string_lossy().to_string()),
+        Err(PathError::Forbidden(e)) => Err(HttpResponse::Forbidden().json(e)),
+        Err(e) => Err(HttpResponse::NotFound().json(e.to_string())),
     }
+}
 
-    println!("Scanning UI path: '{}' -> System path: '{}' (Docker mode: {})", ui_path, system_path, is_docker());
+pub async fn scan(identity: Identity, info: web::Query<ScanQuery>) -> impl Responder {
+    let ui_path = &info.path;
+    let system_path = match resolve_scan_path(&identity, ui_path) {
+        Ok(path) => path,
+        Err(response) => return response,
+    };
+
+    println!("Scanning UI path: '{}' -> System path: '{}'", ui_path, system_path);
 
     match scan_path(&system_path) {
         Ok((files, total_size, total_files)) => {
-            // Sort by size descending by default
-            let mut sorted_files = files;
-            sorted_files.sort_by(|a, b| b.size.cmp(&a.size));
+            let total_entries = files.len() as u64;
+            let (mut sorted_files, matched_entries) = scan::arrange(files, &info);
 
             // Map paths back to UI paths
             for file in &mut sorted_files {
-                file.path = map_to_ui_path(&file.path);
+                file.path = mapper().to_ui(&file.path);
             }
 
-            let parent = Path::new(&system_path)
-                .parent()
-                .map(|p| {
-                    let s = p.to_string_lossy().to_string();
-                    map_to_ui_path(&s)
-                });
-            
-            // Filter out empty parent if it results in empty string and we are not at root
-            let parent = match parent {
-                Some(p) if p.is_empty() && ui_path != "/" => Some("/".to_string()), // Parent of /Users is /
-                Some(p) if p.is_empty() => None, // Parent of / is None
-                Some(p) => Some(p),
-                None => None,
-            };
-
-            // Fix parent for root
-            let parent = if is_docker() && ui_path == "/" {
-                None
-            } else {
-                parent
-            };
-
-            // Get Disk Info
-            let disks = Disks::new_with_refreshed_list();
-            let mut disk_total = None;
-            let mut disk_available = None;
-
-            // Find the disk that contains the scanned path
-            // This is a simple heuristic: find the mount point that is a prefix of the path
-            // and has the longest length (most specific match)
-            let mut best_match_len = 0;
-            
-            for disk in &disks {
-                let mount_point = disk.mount_point().to_string_lossy();
-                // In Docker, we might be scanning /host/..., but disks might show / or /etc/hosts etc.
-                // If we are in docker and scanning /host, we want the disk info for /host (which is the bind mount)
-                // But sysinfo inside docker sees the container's disks.
-                // If we are scanning /host, we are actually scanning the host's disk mounted at /host.
-                // sysinfo inside container usually sees the overlayfs or the bind mount.
-                
-                // Simple check: if path starts with mount point
-                if system_path.starts_with(mount_point.as_ref()) {
-                    if mount_point.len() > best_match_len {
-                        best_match_len = mount_point.len();
-                        disk_total = Some(disk.total_space());
-                        disk_available = Some(disk.available_space());
-                    }
-                }
-            }
+            let parent = mapper().ui_parent(&mapper().to_ui(&system_path));
+            let parent = parent.filter(|p| identity.can_access(p));
+            let parent = parent.filter(|_| Path::new(&system_path).parent().is_some_and(roots::is_allowed));
 
-            // Fallback: if no match found (e.g. Windows paths vs sysinfo), try to just get the first disk or root
-            if disk_total.is_none() {
-                 if let Some(disk) = disks.iter().next() {
-                     disk_total = Some(disk.total_space());
-                     disk_available = Some(disk.available_space());
-                 }
-            }
+            // Capacity of the volume holding the scanned path, unknown rather than another disk's
+            let disks = Disks::new_with_refreshed_list();
+            let disk = disks::disk_for_path(&disks, Path::new(&system_path));
+            let disk_total = disk.map(|d| d.total_space());
+            let disk_available = disk.map(|d| d.available_space());
+            let disk_mount_point = disk.map(|d| mapper().to_ui(&d.mount_point().to_string_lossy()));
 
             HttpResponse::Ok().json(ScanResponse {
                 parent,
@@ -229,35 +189,518 @@ pub async fn scan(info: web::Query<ScanRequest>) -> impl Responder {
                 files: sorted_files,
                 total_size,
                 total_files,
+                total_entries,
+                matched_entries,
                 disk_total,
                 disk_available,
+                disk_mount_point,
             })
         }
         Err(e) => HttpResponse::InternalServerError().json(format!("Error scanning path: {}", e)),
     }
 }
 
+/// Full tree below a path as a download, in CSV, JSON Lines or ncdu format.
+pub async fn export(identity: Identity, query: web::Query<ExportQuery>) -> impl Responder {
+    let ExportQuery { path: ui_path, format } = query.into_inner();
+    let system_path = match resolve_scan_path(&identity, &ui_path) {
+        Ok(path) => path,
+        Err(response) => return response,
+    };
+
+    println!("Exporting UI path: '{}' -> System path: '{}' as {:?}", ui_path, system_path, format);
+
+    let root_path = mapper().to_ui(&system_path);
+    let task = web::block(move || -> std::io::Result<Vec<u8>> {
+        let tree = tree::scan_tree(Path::new(&system_path))?;
+        let mut body = Vec::new();
+        export::write_tree(&tree, &root_path, format, &mut body)?;
+        Ok(body)
+    })
+    .await;
+
+    match task {
+        Ok(Ok(body)) => HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"volumetrik-export.{}\"", format.extension()),
+            ))
+            .body(body),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(format!("Error exporting path: {}", e)),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Hierarchy below a path for treemaps and sunbursts, pruned to a depth and minimum size.
+pub async fn treemap(identity: Identity, query: web::Query<TreemapQuery>) -> impl Responder {
+    let system_path = match resolve_scan_path(&identity, &query.path) {
+        Ok(path) => path,
+        Err(response) => return response,
+    };
+
+    let pruning = query.pruning();
+    let root_path = mapper().to_ui(&system_path);
+    let task = web::block(move || {
+        tree::scan_tree(Path::new(&system_path)).map(|tree| treemap::build(&tree, &root_path, pruning))
+    })
+    .await;
+
+    match task {
+        Ok(Ok(hierarchy)) => HttpResponse::Ok().json(hierarchy),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(format!("Error scanning path: {}", e)),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Entries below a path matching name, size, time, owner and type filters.
+pub async fn search(identity: Identity, query: web::Query<SearchQuery>) -> impl Responder {
+    let query = query.into_inner();
+    let filter = match search::Filter::from_query(&query) {
+        Ok(filter) => filter,
+        Err(e) => return HttpResponse::BadRequest().json(e),
+    };
+    let system_path = match resolve_scan_path(&identity, &query.path) {
+        Ok(path) => path,
+        Err(response) => return response,
+    };
+
+    let root_path = mapper().to_ui(&system_path);
+    let task = web::block(move || {
+        tree::scan_tree(Path::new(&system_path)).map(|tree| search::search(&tree, &root_path, &filter, &query))
+    })
+    .await;
+
+    match task {
+        Ok(Ok(response)) => HttpResponse::Ok().json(response),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(format!("Error scanning path: {}", e)),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Whether write actions are available, for the dashboard.
+pub async fn cleanup_status() -> impl Responder {
+    let config = cleanup::config();
+    HttpResponse::Ok().json(CleanupStatus {
+        enabled: config.enabled,
+        trash_dir: config.trash_dir.as_ref().map(|d| mapper().to_ui(&d.to_string_lossy())),
+    })
+}
+
+fn check_cleanup_enabled(identity: &Identity) -> Result<(), HttpResponse> {
+    if !cleanup::config().enabled {
+        return Err(HttpResponse::Forbidden().json("Write actions are disabled, start the server with --allow-writes"));
+    }
+    // Authentication can be removed while the server runs
+    if identity.method == AuthMethod::Anonymous {
+        return Err(HttpResponse::Forbidden().json("Write actions require authentication"));
+    }
+    Ok(())
+}
+
+/// Measures what an action would remove. Nothing is touched until the returned token is confirmed.
+pub async fn cleanup_preview(
+    identity: Identity,
+    service: web::Data<SharedCleanup>,
+    request: web::Json<CleanupRequest>,
+) -> impl Responder {
+    if let Err(response) = check_cleanup_enabled(&identity) {
+        return response;
+    }
+    let CleanupRequest { action, paths } = request.into_inner();
+    if action == CleanupAction::Trash && cleanup::config().trash_dir.is_none() {
+        return HttpResponse::BadRequest().json("No trash directory configured, start the server with --trash-dir");
+    }
+    if paths.is_empty() {
+        return HttpResponse::BadRequest().json("No path given");
+    }
+
+    let mut resolved = Vec::new();
+    for ui_path in paths {
+        if ui_path.contains("..") {
+            return HttpResponse::BadRequest().json("Invalid path");
+        }
+        if !identity.can_access(&ui_path) {
+            return HttpResponse::Forbidden().json("Access to this path is not allowed");
+        }
+        match cleanup::resolve_target(&mapper().to_system(&ui_path)) {
+            Ok(system_path) if !can_access_system(&identity, &system_path) => {
+                return HttpResponse::Forbidden().json("Access to this path is not allowed");
+            }
+            Ok(system_path) => resolved.push((ui_path, system_path)),
+            Err(PathError::Forbidden(e)) => return HttpResponse::Forbidden().json(e),
+            Err(e) => return HttpResponse::NotFound().json(e.to_string()),
+        }
+    }
+    // Entries inside another target go with it
+    resolved.sort_by(|a, b| a.1.cmp(&b.1));
+    resolved.dedup_by(|b, a| b.1.starts_with(&a.1));
+
+    let measured = web::block(move || {
+        resolved
+            .into_iter()
+            .map(|(ui_path, system_path)| cleanup::measure(&ui_path, system_path))
+            .collect::<std::io::Result<Vec<_>>>()
+    })
+    .await;
+
+    match measured {
+        Ok(Ok(targets)) => HttpResponse::Ok().json(service.preview(&identity.username, action, targets)),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(format!("Error measuring path: {}", e)),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Runs a previewed action.
+pub async fn cleanup_execute(
+    identity: Identity,
+    service: web::Data<SharedCleanup>,
+    jobs: web::Data<SharedJobs>,
+    request: web::Json<ConfirmCleanupRequest>,
+) -> impl Responder {
+    if let Err(response) = check_cleanup_enabled(&identity) {
+        return response;
+    }
+    let (action, targets) = match service.confirm(&request.token, &identity.username) {
+        Ok(pending) => pending,
+        Err(e) => return HttpResponse::BadRequest().json(e),
+    };
+    if targets.iter().any(|t| !can_access_system(&identity, &t.system_path)) {
+        return HttpResponse::Forbidden().json("Access to this path is not allowed");
+    }
+
+    // Held until the removal is done, so no archive starts on these paths meanwhile
+    let claim = match jobs.claim(&targets.iter().map(|t| t.path.clone()).collect::<Vec<_>>()) {
+        Ok(claim) => claim,
+        Err(e) => return HttpResponse::Conflict().json(e),
+    };
+
+    let username = identity.username.clone();
+    match web::block(move || {
+        let _claim = claim;
+        cleanup::run(&username, action, &targets)
+    })
+    .await
+    {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+pub async fn cleanup_audit(identity: Identity, query: web::Query<AuditQuery>) -> impl Responder {
+    if !identity.is_admin() {
+        return HttpResponse::Forbidden().json("Administrator role required");
+    }
+    HttpResponse::Ok().json(cleanup::query(&query))
+}
+
+pub async fn retention_status(
+    identity: Identity,
+    data: web::Data<SharedSettings>,
+    monitor: web::Data<SharedMonitor>,
+) -> impl Responder {
+    if !identity.is_admin() {
+        return HttpResponse::Forbidden().json("Administrator role required");
+    }
+    let settings = data.lock().unwrap().clone();
+    HttpResponse::Ok().json(monitor.retention.status(&settings))
+}
+
+/// Runs a retention policy right away, in dry-run mode unless told otherwise by the policy or the request.
+pub async fn retention_run(
+    identity: Identity,
+    data: web::Data<SharedSettings>,
+    monitor: web::Data<SharedMonitor>,
+    request: web::Json<RetentionRunRequest>,
+) -> impl Responder {
+    let settings = data.lock().unwrap().clone();
+    let Some(policy) = settings.retention.policies.iter().find(|p| p.name == request.name).cloned() else {
+        return HttpResponse::NotFound().json(format!("Unknown retention policy '{}'", request.name));
+    };
+    // Even a dry run lists the entries below the policy's path
+    let resolved = roots::resolve(&mapper().to_system(&policy.path));
+    if !identity.can_access(&policy.path) || resolved.is_ok_and(|root| !can_access_system(&identity, &root)) {
+        return HttpResponse::Forbidden().json("Access to this path is not allowed");
+    }
+    let dry_run = request.dry_run.unwrap_or(policy.dry_run);
+    if !dry_run {
+        if let Err(response) = check_cleanup_enabled(&identity) {
+            return response;
+        }
+    }
+
+    let retention = monitor.retention.clone();
+    let default_interval = settings.monitoring.check_interval_minutes;
+    let task = web::block(move || retention.execute(&policy, &settings.alerts, dry_run, default_interval)).await;
+    match task {
+        Ok(Ok(report)) => HttpResponse::Ok().json(report),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(e),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Starts a job packing a directory into `<name>.tar.zst` next to it, removing the directory
+/// once the archive is verified unless `keep_original` is set.
+pub async fn archive(
+    identity: Identity,
+    jobs: web::Data<SharedJobs>,
+    request: web::Json<ArchiveRequest>,
+) -> impl Responder {
+    if let Err(response) = check_cleanup_enabled(&identity) {
+        return response;
+    }
+    let ArchiveRequest { path: ui_path, keep_original, level } = request.into_inner();
+    if ui_path.contains("..") {
+        return HttpResponse::BadRequest().json("Invalid path");
+    }
+    if !identity.can_access(&ui_path) {
+        return HttpResponse::Forbidden().json("Access to this path is not allowed");
+    }
+    let dir = match cleanup::resolve_target(&mapper().to_system(&ui_path)) {
+        Ok(dir) if !can_access_system(&identity, &dir) => {
+            return HttpResponse::Forbidden().json("Access to this path is not allowed");
+        }
+        Ok(dir) => dir,
+        Err(PathError::Forbidden(e)) => return HttpResponse::Forbidden().json(e),
+        Err(e) => return HttpResponse::NotFound().json(e.to_string()),
+    };
+    if !std::fs::symlink_metadata(&dir).is_ok_and(|m| m.is_dir()) {
+        return HttpResponse::BadRequest().json("Only directories can be archived");
+    }
+    let destination = archive::destination(&dir);
+    if std::fs::symlink_metadata(&destination).is_ok() {
+        return HttpResponse::Conflict().json(format!("{} already exists", mapper().to_ui(&destination.to_string_lossy())));
+    }
+
+    let username = identity.username.clone();
+    let path = ui_path.clone();
+    let level = level.unwrap_or(archive::DEFAULT_LEVEL);
+    match jobs.try_spawn("archive", &ui_path, &identity.username, move |handle| {
+        archive::run(handle, &username, &path, &dir, level, keep_original)
+    }) {
+        Ok(job) => HttpResponse::Accepted().json(job),
+        Err(e) => HttpResponse::Conflict().json(e),
+    }
+}
+
+/// Background jobs on paths the user can access, newest first.
+pub async fn list_jobs(identity: Identity, jobs: web::Data<SharedJobs>) -> impl Responder {
+    let visible: Vec<_> = jobs.list().into_iter().filter(|job| identity.can_access(&job.path)).collect();
+    HttpResponse::Ok().json(visible)
+}
+
+pub async fn get_job(identity: Identity, jobs: web::Data<SharedJobs>, id: web::Path<String>) -> impl Responder {
+    match jobs.get(&id) {
+        Some(job) if identity.can_access(&job.path) => HttpResponse::Ok().json(job),
+        _ => HttpResponse::NotFound().json(format!("Job '{}' not found", id)),
+    }
+}
+
+pub async fn cancel_job(identity: Identity, jobs: web::Data<SharedJobs>, id: web::Path<String>) -> impl Responder {
+    match jobs.get(&id) {
+        Some(job) if identity.can_access(&job.path) => HttpResponse::Ok().json(jobs.cancel(&id)),
+        _ => HttpResponse::NotFound().json(format!("Job '{}' not found", id)),
+    }
+}
+
+pub async fn list_imports() -> impl Responder {
+    HttpResponse::Ok().json(imports::list())
+}
+
+/// Stores an ncdu JSON dump sent as the request body.
+pub async fn create_import(query: web::Query<ImportQuery>, body: web::Bytes) -> impl Responder {
+    let name = query.into_inner().name;
+    match web::block(move || imports::save(&body, name.as_deref())).await {
+        Ok(Ok(info)) => {
+            println!("Imported '{}' ({} files) as {}", info.root, info.total_files, info.id);
+            HttpResponse::Created().json(info)
+        }
+        Ok(Err(e)) => HttpResponse::BadRequest().json(e),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+pub async fn delete_import(store: web::Data<SharedImports>, id: web::Path<String>) -> impl Responder {
+    match imports::remove(&id) {
+        Ok(true) => {
+            store.forget(&id);
+            HttpResponse::Ok().json(serde_json::json!({ "status": "success" }))
+        }
+        Ok(false) => HttpResponse::NotFound().json(format!("Import '{}' not found", id)),
+        Err(e) => HttpResponse::BadRequest().json(e),
+    }
+}
+
+/// Browses an imported tree, answering like `/api/scan`.
+pub async fn import_scan(
+    identity: Identity,
+    store: web::Data<SharedImports>,
+    id: web::Path<String>,
+    info: web::Query<ScanQuery>,
+) -> impl Responder {
+    let store = store.get_ref().clone();
+    let id = id.into_inner();
+    let tree = match web::block(move || store.tree(&id)).await {
+        Ok(Ok(tree)) => tree,
+        Ok(Err(e)) => return HttpResponse::NotFound().json(e),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let ui_path = if info.path.is_empty() { tree.name.as_str() } else { info.path.as_str() };
+    if !identity.can_access(ui_path) {
+        return HttpResponse::Forbidden().json("Access to this path is not allowed");
+    }
+
+    match imports::scan(&tree, ui_path) {
+        Some(mut response) => {
+            let (files, matched_entries) = scan::arrange(std::mem::take(&mut response.files), &info);
+            response.files = files;
+            response.matched_entries = matched_entries;
+            response.parent = response.parent.filter(|p| identity.can_access(p));
+            HttpResponse::Ok().json(response)
+        }
+        None => HttpResponse::NotFound().json(format!("Path not found in import: {}", ui_path)),
+    }
+}
+
+/// Treemap hierarchy of an imported tree, like `/api/treemap`.
+pub async fn import_treemap(
+    identity: Identity,
+    store: web::Data<SharedImports>,
+    id: web::Path<String>,
+    query: web::Query<TreemapQuery>,
+) -> impl Responder {
+    let store = store.get_ref().clone();
+    let id = id.into_inner();
+    let tree = match web::block(move || store.tree(&id)).await {
+        Ok(Ok(tree)) => tree,
+        Ok(Err(e)) => return HttpResponse::NotFound().json(e),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let path = if query.path.is_empty() { tree.name.clone() } else { query.path.clone() };
+    if !identity.can_access(&path) {
+        return HttpResponse::Forbidden().json("Access to this path is not allowed");
+    }
+    match imports::find(&tree, &path) {
+        Some(node) => HttpResponse::Ok().json(treemap::build(node, &path, query.pruning())),
+        None => HttpResponse::NotFound().json(format!("Path not found in import: {}", path)),
+    }
+}
+
+/// Search in an imported tree, like `/api/search`.
+pub async fn import_search(
+    identity: Identity,
+    store: web::Data<SharedImports>,
+    id: web::Path<String>,
+    query: web::Query<SearchQuery>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let filter = match search::Filter::from_query(&query) {
+        Ok(filter) => filter,
+        Err(e) => return HttpResponse::BadRequest().json(e),
+    };
+    let store = store.get_ref().clone();
+    let id = id.into_inner();
+    let tree = match web::block(move || store.tree(&id)).await {
+        Ok(Ok(tree)) => tree,
+        Ok(Err(e)) => return HttpResponse::NotFound().json(e),
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let path = if query.path.is_empty() { tree.name.clone() } else { query.path.clone() };
+    if !identity.can_access(&path) {
+        return HttpResponse::Forbidden().json("Access to this path is not allowed");
+    }
+    match imports::find(&tree, &path) {
+        Some(node) => HttpResponse::Ok().json(search::search(node, &path, &filter, &query)),
+        None => HttpResponse::NotFound().json(format!("Path not found in import: {}", path)),
+    }
+}
+
 pub async fn health() -> impl Responder {
     HttpResponse::Ok().body("Volumetrik is running")
 }
 
-pub async fn get_settings(data: web::Data<SharedSettings>) -> impl Responder {
+pub async fn login(req: HttpRequest, auth: web::Data<SharedAuth>, credentials: web::Json<LoginRequest>) -> impl Responder {
+    let LoginRequest { username, password } = credentials.into_inner();
+    let session_hours = auth.config().session_hours;
+
+    // Password hashing is deliberately slow
+    let service = auth.get_ref().clone();
+    let user = username.clone();
+    let session = match web::block(move || service.login(&user, &password)).await {
+        Ok(session) => session,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    let Some(session) = session else {
+        println!("Failed login for user '{}'", username);
+        return HttpResponse::Unauthorized().json("Invalid username or password");
+    };
+
+    let cookie = Cookie::build(SESSION_COOKIE, session)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .secure(req.connection_info().scheme() == "https")
+        .max_age(CookieDuration::hours(session_hours as i64))
+        .finish();
+
+    HttpResponse::Ok().cookie(cookie).json(serde_json::json!({ "username": username }))
+}
+
+pub async fn logout(req: HttpRequest, auth: web::Data<SharedAuth>) -> impl Responder {
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        auth.logout(cookie.value());
+    }
+
+    let mut removal = Cookie::build(SESSION_COOKIE, "").path("/").finish();
+    removal.make_removal();
+    HttpResponse::Ok().cookie(removal).json(serde_json::json!({ "status": "success" }))
+}
+
+pub async fn auth_status(req: HttpRequest, auth: web::Data<SharedAuth>) -> impl Responder {
+    let identity = req.extensions().get::<Identity>().cloned();
+    HttpResponse::Ok().json(AuthStatus {
+        auth_enabled: auth.config().enabled(),
+        authenticated: identity.is_some(),
+        identity,
+    })
+}
+
+pub async fn get_settings(identity: Identity, data: web::Data<SharedSettings>) -> impl Responder {
     let settings = data.lock().unwrap();
-    HttpResponse::Ok().json(settings.clone())
+    if !identity.is_admin() {
+        // Viewers only get what the dashboard itself needs
+        let mut visible = Settings {
+            language: settings.language.clone(),
+            layout: settings.layout.clone(),
+            ..Default::default()
+        };
+        visible.monitoring.paths.clear();
+        return HttpResponse::Ok().json(visible);
+    }
+    HttpResponse::Ok().json(secrets::redact(&settings))
 }
 
 pub async fn update_settings(
     data: web::Data<SharedSettings>,
     new_settings: web::Json<Settings>,
 ) -> impl Responder {
+    let mut new_settings = new_settings.into_inner();
+    if let Err(e) = new_settings.validate() {
+        return HttpResponse::BadRequest().json(e);
+    }
     let mut settings = data.lock().unwrap();
-    *settings = new_settings.into_inner();
+    secrets::restore(&mut new_settings, &settings);
+    *settings = new_settings;
     
     if let Err(e) = save_settings(&settings) {
         return HttpResponse::InternalServerError().body(format!("Failed to save settings: {}", e));
     }
 
-    HttpResponse::Ok().json(settings.clone())
+    HttpResponse::Ok().json(secrets::redact(&settings))
 }
 
 pub async fn update_layout(
@@ -284,3 +727,130 @@ pub async fn update_layout(
 
     HttpResponse::Ok().json(serde_json::json!({ "status": "success" }))
 }
+
+pub async fn test_alert(
+    data: web::Data<SharedSettings>,
+    request: Option<web::Json<TestAlertRequest>>,
+) -> impl Responder {
+    let config = data.lock().unwrap().alerts.clone();
+    let request = request.map(|r| r.into_inner()).unwrap_or_default();
+
+    // Alert delivery uses the blocking HTTP client
+    let task = web::block(move || send_test_alert(&config, request.channel, request.message)).await;
+
+    match task {
+        Ok(results) => HttpResponse::Ok().json(TestAlertResponse {
+            success: !results.is_empty() && results.iter().all(|r| r.success),
+            results,
+        }),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+pub async fn alert_history(identity: Identity, query: web::Query<HistoryQuery>) -> impl Responder {
+    HttpResponse::Ok().json(history::query(&query, |path| identity.can_access(path)))
+}
+
+/// Builds the digest report without sending it or updating the stored sizes.
+pub async fn digest_preview(data: web::Data<SharedSettings>) -> impl Responder {
+    let settings = data.lock().unwrap().clone();
+
+    let task = web::block(move || {
+        let (report, _) = digest::build_report(&settings, &digest::load_state());
+        let rendered = digest::render(&report, settings.digest.format);
+        serde_json::json!({ "report": report, "rendered": rendered })
+    })
+    .await;
+
+    match task {
+        Ok(preview) => HttpResponse::Ok().json(preview),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+/// Sends a digest report right away, whatever the schedule.
+pub async fn digest_send(data: web::Data<SharedSettings>) -> impl Responder {
+    let settings = data.lock().unwrap().clone();
+
+    match web::block(move || digest::run(&settings, true)).await {
+        Ok(results) => HttpResponse::Ok().json(TestAlertResponse {
+            success: !results.is_empty() && results.iter().all(|r| r.success),
+            results,
+        }),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+pub async fn monitoring_status(identity: Identity, monitor: web::Data<SharedMonitor>) -> impl Responder {
+    let mut status = monitor.status();
+    status.paths.retain(|p| identity.can_access(&p.path));
+    HttpResponse::Ok().json(status)
+}
+
+/// Checks one monitored path (or all of them) right away. With `wait` the response
+/// is sent once the checks finish and contains the updated status.
+pub async fn monitoring_check(
+    monitor: web::Data<SharedMonitor>,
+    request: Option<web::Json<CheckRequest>>,
+) -> impl Responder {
+    let request = request.map(|r| r.into_inner()).unwrap_or_default();
+    let started = monitor.trigger(request.path.as_deref());
+
+    if started.is_empty() {
+        if let Some(path) = &request.path {
+            let known = monitor.status().paths.iter().any(|p| &p.path == path);
+            if !known {
+                return HttpResponse::NotFound().json(format!("Path is not monitored: {}", path));
+            }
+        }
+    }
+
+    let paths: Vec<String> = started.iter().map(|(path, _)| path.clone()).collect();
+    if !request.wait {
+        return HttpResponse::Accepted().json(serde_json::json!({ "started": paths }));
+    }
+
+    for (_, handle) in started {
+        let _ = handle.await;
+    }
+    HttpResponse::Ok().json(serde_json::json!({ "started": paths, "status": monitor.status() }))
+}
+
+/// Mounted volumes, restricted to the ones holding paths the caller may access.
+pub async fn list_disks(identity: Identity, query: web::Query<DisksQuery>) -> impl Responder {
+    let mut volumes = match web::block(|| disks::within_allowed_roots(disks::list_volumes())).await {
+        Ok(volumes) => volumes,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+
+    volumes.retain(|v| {
+        (query.pseudo || !v.pseudo) && (identity.can_access(&v.mount_point) || identity.can_traverse(&v.mount_point))
+    });
+    volumes.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+
+    HttpResponse::Ok().json(volumes)
+}
+
+/// Prometheus scrape endpoint. Answers in OpenMetrics when the scraper asks for it.
+pub async fn metrics(req: HttpRequest, identity: Identity, monitor: web::Data<SharedMonitor>) -> impl Responder {
+    let openmetrics = req
+        .headers()
+        .get("Accept")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("application/openmetrics-text"));
+
+    let volumes = match web::block(|| disks::within_allowed_roots(disks::list_volumes())).await {
+        Ok(volumes) => volumes,
+        Err(_) => return HttpResponse::InternalServerError().finish(),
+    };
+    let mut status = monitor.status();
+    status.paths.retain(|p| identity.can_access(&p.path));
+    let body = metrics::render(&status, &volumes, openmetrics);
+
+    let content_type = if openmetrics {
+        "application/openmetrics-text; version=1.0.0; charset=utf-8"
+    } else {
+        "text/plain; version=0.0.4; charset=utf-8"
+    };
+    HttpResponse::Ok().content_type(content_type).body(body)
+}
@@ -1,9 +1,14 @@
+use crate::cleanup::CleanupAction;
+use crate::schedule;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 
-const CONFIG_FILE: &str = "settings/settings.json";
+pub const DEFAULT_CONFIG_FILE: &str = "settings/settings.json";
+
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AlertConfig {
@@ -23,12 +28,154 @@ pub struct AlertConfig {
     pub teams_webhook_url: Option<String>,
     pub ntfy_url: Option<String>,
     pub ntfy_token: Option<String>,
+
+    /// Per-channel message templates, overriding `custom_message`.
+    /// Templates starting with `{` or `[` are sent as the JSON body on webhook-style channels.
+    #[serde(default)]
+    pub templates: HashMap<AlertChannel, String>,
+
+    /// Request options for the generic webhook. Its body comes from `templates.Webhook`.
+    #[serde(default)]
+    pub webhook: WebhookOptions,
+
+    #[serde(default)]
+    pub history: HistoryRetention,
+}
+
+/// Limits for the alert history log, applied whenever an event is recorded.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryRetention {
+    pub max_entries: usize,
+    pub max_age_days: u64,
+}
+
+impl Default for HistoryRetention {
+    fn default() -> Self {
+        Self {
+            max_entries: 1000,
+            max_age_days: 90,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum WebhookMethod {
+    #[default]
+    Post,
+    Put,
+    Patch,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum WebhookEncoding {
+    #[default]
+    Json,
+    /// `application/x-www-form-urlencoded`, built from the top-level fields of the JSON payload
+    Form,
+    /// The rendered message as `text/plain`
+    Text,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct WebhookOptions {
+    #[serde(default)]
+    pub method: WebhookMethod,
+    #[serde(default)]
+    pub encoding: WebhookEncoding,
+    /// Extra request headers, e.g. `Authorization` or `X-Api-Key`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// When set, the body is signed with HMAC-SHA256 and sent as `sha256=<hex>`
+    #[serde(default)]
+    pub hmac_secret: Option<String>,
+    /// Header carrying the signature, defaults to `X-Volumetrik-Signature`
+    #[serde(default)]
+    pub signature_header: Option<String>,
+}
+
+impl AlertConfig {
+    /// Whether any template references the given variable, used to skip expensive lookups.
+    pub fn uses_variable(&self, name: &str) -> bool {
+        self.custom_message.iter().chain(self.templates.values()).any(|t| t.contains(name))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum AlertChannel {
+    Telegram,
+    Webhook,
+    Pushover,
+    Gotify,
+    Slack,
+    Discord,
+    Teams,
+    Ntfy,
+}
+
+impl AlertChannel {
+    pub const ALL: [AlertChannel; 8] = [
+        AlertChannel::Telegram,
+        AlertChannel::Webhook,
+        AlertChannel::Pushover,
+        AlertChannel::Gotify,
+        AlertChannel::Slack,
+        AlertChannel::Discord,
+        AlertChannel::Teams,
+        AlertChannel::Ntfy,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            AlertChannel::Telegram => "Telegram",
+            AlertChannel::Webhook => "Generic Webhook",
+            AlertChannel::Pushover => "Pushover",
+            AlertChannel::Gotify => "Gotify",
+            AlertChannel::Slack => "Slack",
+            AlertChannel::Discord => "Discord",
+            AlertChannel::Teams => "Microsoft Teams",
+            AlertChannel::Ntfy => "Ntfy",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Info => "INFO",
+            Severity::Warning => "WARNING",
+            Severity::Critical => "CRITICAL",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum ThresholdType {
+    /// Folder size in GB
     MaxUsed,
+    /// Free space in GB on the volume holding the path
     MinRemaining,
+    /// Used space in percent of the volume holding the path
+    MaxUsedPercent,
+}
+
+/// An additional threshold level on a monitored path (e.g. warning at 80%, critical at 95%).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ThresholdLevel {
+    pub threshold_type: ThresholdType,
+    pub threshold_value: f64,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Channels for this level. Empty means "use the path's channels".
+    #[serde(default)]
+    pub channels: Vec<AlertChannel>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -36,6 +183,75 @@ pub struct MonitoredPath {
     pub path: String,
     pub threshold_type: ThresholdType,
     pub threshold_value: f64,
+    #[serde(default)]
+    pub severity: Severity,
+    /// Channels to notify for this path. Empty means every configured channel.
+    #[serde(default)]
+    pub channels: Vec<AlertChannel>,
+    #[serde(default)]
+    pub thresholds: Vec<ThresholdLevel>,
+    /// Check interval for this path, defaults to `check_interval_minutes`
+    #[serde(default)]
+    pub interval_minutes: Option<u64>,
+    /// Cron expression (`min hour day month weekday`, optionally with leading seconds), overrides the interval
+    #[serde(default)]
+    pub cron: Option<String>,
+    /// Overrides the global quiet hours for this path
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl MonitoredPath {
+    /// All threshold levels of this path, the primary one first.
+    pub fn levels(&self) -> Vec<ThresholdLevel> {
+        let mut levels = vec![ThresholdLevel {
+            threshold_type: self.threshold_type.clone(),
+            threshold_value: self.threshold_value,
+            severity: self.severity,
+            channels: self.channels.clone(),
+        }];
+        for level in &self.thresholds {
+            let mut level = level.clone();
+            if level.channels.is_empty() {
+                level.channels = self.channels.clone();
+            }
+            levels.push(level);
+        }
+        levels
+    }
+}
+
+/// Daily time range (local time) during which alerts are not sent. May wrap past midnight.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuietHours {
+    pub start: chrono::NaiveTime,
+    pub end: chrono::NaiveTime,
+    /// Still deliver critical alerts during quiet hours
+    #[serde(default)]
+    pub allow_critical: bool,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: chrono::NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            // Wraps past midnight, e.g. 22:00 - 07:00
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Planned downtime during which alerts are suppressed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MaintenanceWindow {
+    pub start: chrono::DateTime<chrono::Utc>,
+    pub end: chrono::DateTime<chrono::Utc>,
+    /// Paths covered by the window. Empty means every path.
+    #[serde(default)]
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub description: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -43,6 +259,110 @@ pub struct MonitoringConfig {
     pub enabled: bool,
     pub paths: Vec<MonitoredPath>,
     pub check_interval_minutes: u64,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+    #[serde(default)]
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum DigestFrequency {
+    Daily,
+    #[default]
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum DigestFormat {
+    #[default]
+    Text,
+    Markdown,
+    Html,
+}
+
+/// Periodic usage report covering the monitored paths and their volumes.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct DigestConfig {
+    pub enabled: bool,
+    pub frequency: DigestFrequency,
+    /// Day of the week for weekly reports (monthly reports go out on the 1st)
+    pub weekday: chrono::Weekday,
+    /// Local hour of day (0-23)
+    pub hour: u32,
+    /// Channels to send the report to. Empty means every configured channel.
+    pub channels: Vec<AlertChannel>,
+    pub format: DigestFormat,
+    /// Number of fastest growing directories to list
+    pub top_growing: usize,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency: DigestFrequency::Weekly,
+            weekday: chrono::Weekday::Mon,
+            hour: 8,
+            channels: Vec::new(),
+            format: DigestFormat::Text,
+            top_growing: 5,
+        }
+    }
+}
+
+/// Automatic cleanup rules, evaluated by the monitoring scheduler.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub enabled: bool,
+    pub policies: Vec<RetentionPolicy>,
+}
+
+/// Removes the entries of a directory that are too old or beyond the newest ones,
+/// e.g. `*.log.*` older than 30 days, or all but the 10 newest backups.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetentionPolicy {
+    /// Identifies the policy in reports and the audit log
+    pub name: String,
+    pub path: String,
+    /// Glob matched against entry names, every entry when unset
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Only entries modified more than this many days ago
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+    /// Always keep this many of the most recently modified matches
+    #[serde(default)]
+    pub keep_newest: Option<usize>,
+    /// Also match entries in subdirectories
+    #[serde(default)]
+    pub recursive: bool,
+    /// Directories can match too, they are removed with their contents
+    #[serde(default)]
+    pub include_dirs: bool,
+    #[serde(default)]
+    pub action: CleanupAction,
+    /// Only report what would be removed. On by default, turn it off once the report looks right
+    #[serde(default = "default_true")]
+    pub dry_run: bool,
+    /// Stops removing entries, oldest first, before this many bytes are exceeded in one run
+    #[serde(default)]
+    pub max_bytes_per_run: Option<u64>,
+    /// Run interval, defaults to `monitoring.check_interval_minutes`
+    #[serde(default)]
+    pub interval_minutes: Option<u64>,
+    /// Cron expression, overrides the interval
+    #[serde(default)]
+    pub cron: Option<String>,
+    /// Channels the run reports go to. Empty means every configured channel.
+    #[serde(default)]
+    pub channels: Vec<AlertChannel>,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -50,6 +370,10 @@ pub struct Settings {
     pub language: String,
     pub monitoring: MonitoringConfig,
     pub alerts: AlertConfig,
+    #[serde(default)]
+    pub digest: DigestConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
     pub layout: Option<serde_json::Value>,
 }
 
@@ -63,8 +387,16 @@ impl Default for Settings {
                     path: "/".to_string(),
                     threshold_type: ThresholdType::MaxUsed,
                     threshold_value: 100.0,
+                    severity: Severity::Warning,
+                    channels: Vec::new(),
+                    thresholds: Vec::new(),
+                    interval_minutes: None,
+                    cron: None,
+                    quiet_hours: None,
                 }],
                 check_interval_minutes: 60,
+                quiet_hours: None,
+                maintenance_windows: Vec::new(),
             },
             alerts: AlertConfig {
                 enabled: false,
@@ -81,7 +413,12 @@ impl Default for Settings {
                 teams_webhook_url: None,
                 ntfy_url: None,
                 ntfy_token: None,
+                templates: HashMap::new(),
+                webhook: WebhookOptions::default(),
+                history: HistoryRetention::default(),
             },
+            digest: DigestConfig::default(),
+            retention: RetentionConfig::default(),
             layout: Some(default_layout()),
         }
     }
@@ -99,11 +436,64 @@ pub fn default_layout() -> serde_json::Value {
 
 pub type SharedSettings = Arc<Mutex<Settings>>;
 
+/// Sets the settings file location. Must be called before the settings are first loaded.
+pub fn set_config_file(path: PathBuf) {
+    let _ = CONFIG_FILE.set(path);
+}
+
+pub fn config_file() -> &'static Path {
+    CONFIG_FILE.get_or_init(|| PathBuf::from(DEFAULT_CONFIG_FILE))
+}
+
+/// Path of a data file stored next to the settings file (history, state, ...).
+pub fn data_file(name: &str) -> PathBuf {
+    config_file()
+        .parent()
+        .map(|dir| dir.join(name))
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+impl Settings {
+    /// Checks what the types can't, before settings are saved.
+    pub fn validate(&self) -> Result<(), String> {
+        // A mistyped schedule would otherwise be saved and quietly replaced by the interval
+        let cron = |expr: &Option<String>| match expr.as_deref().filter(|e| !e.trim().is_empty()) {
+            Some(expr) => schedule::parse_cron(expr).map(|_| ()),
+            None => Ok(()),
+        };
+        for item in &self.monitoring.paths {
+            cron(&item.cron).map_err(|e| format!("Monitored path '{}': {}", item.path, e))?;
+        }
+        if self.digest.hour > 23 {
+            return Err(format!("Digest hour {} is not between 0 and 23", self.digest.hour));
+        }
+
+        let mut names = std::collections::HashSet::new();
+        for policy in &self.retention.policies {
+            if policy.name.trim().is_empty() {
+                return Err("Retention policies need a name".to_string());
+            }
+            // The name identifies the state and schedule of a policy
+            if !names.insert(policy.name.as_str()) {
+                return Err(format!("Duplicate retention policy name '{}'", policy.name));
+            }
+            cron(&policy.cron).map_err(|e| format!("Retention policy '{}': {}", policy.name, e))?;
+        }
+        Ok(())
+    }
+}
+
 pub fn load_settings() -> Settings {
-    if Path::new(CONFIG_FILE).exists() {
-        match fs::read_to_string(CONFIG_FILE) {
-            Ok(content) => match serde_json::from_str(&content) {
-                Ok(settings) => return settings,
+    let path = config_file();
+    if path.exists() {
+        match fs::read_to_string(path) {
+            Ok(content) => match serde_json::from_str::<Settings>(&content) {
+                Ok(settings) => {
+                    if let Err(e) = settings.validate() {
+                        println!("Warning: {} in settings", e);
+                    }
+                    return settings;
+                }
                 Err(e) => println!("Error parsing settings: {}", e),
             },
             Err(e) => println!("Error reading settings file: {}", e),
@@ -113,16 +503,64 @@ pub fn load_settings() -> Settings {
 }
 
 pub fn save_settings(settings: &Settings) -> std::io::Result<()> {
-    if let Some(parent) = Path::new(CONFIG_FILE).parent() {
+    let path = config_file();
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
         if let Err(e) = fs::create_dir_all(parent) {
             println!("Failed to create settings directory '{}': {}", parent.display(), e);
             return Err(e);
         }
     }
     let content = serde_json::to_string_pretty(settings)?;
-    if let Err(e) = fs::write(CONFIG_FILE, content) {
-        println!("Failed to write settings file '{}': {}", CONFIG_FILE, e);
+    if let Err(e) = fs::write(path, content) {
+        println!("Failed to write settings file '{}': {}", path.display(), e);
         return Err(e);
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitored(cron: &str) -> MonitoredPath {
+        serde_json::from_value(serde_json::json!({
+            "path": "/data",
+            "threshold_type": "MaxUsedPercent",
+            "threshold_value": 90.0,
+            "cron": cron,
+        }))
+        .unwrap()
+    }
+
+    fn policy(name: &str, cron: &str) -> RetentionPolicy {
+        serde_json::from_value(serde_json::json!({ "name": name, "path": "/backups", "cron": cron })).unwrap()
+    }
+
+    #[test]
+    fn validates_schedules() {
+        let mut settings = Settings::default();
+        settings.monitoring.paths = vec![monitored("*/5 * * * *"), monitored(" ")];
+        settings.retention.policies = vec![policy("nightly", "0 3 * * 1-5"), policy("empty", "")];
+        assert_eq!(settings.validate(), Ok(()));
+
+        settings.monitoring.paths.push(monitored("*/5 * * *"));
+        assert!(settings.validate().unwrap_err().starts_with("Monitored path '/data': Invalid cron expression"));
+        settings.monitoring.paths.pop();
+
+        settings.retention.policies.push(policy("weekly", "0 3 * * 8"));
+        assert!(settings.validate().unwrap_err().starts_with("Retention policy 'weekly': Invalid cron expression"));
+        settings.retention.policies.pop();
+
+        settings.digest.hour = 24;
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn validates_policy_names() {
+        let mut settings = Settings::default();
+        settings.retention.policies = vec![policy("nightly", ""), policy(" ", "")];
+        assert_eq!(settings.validate(), Err("Retention policies need a name".to_string()));
+        settings.retention.policies = vec![policy("nightly", ""), policy("nightly", "")];
+        assert_eq!(settings.validate(), Err("Duplicate retention policy name 'nightly'".to_string()));
+    }
+}
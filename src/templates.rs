@@ -0,0 +1,203 @@
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+
+/// Variables available to alert templates.
+#[derive(Debug, Serialize, Clone)]
+pub struct AlertContext {
+    pub path: String,
+    pub severity: String,
+    pub threshold_type: String,
+    pub threshold: f64,
+    pub current: f64,
+    pub unit: String,
+    pub hostname: String,
+    pub timestamp: String,
+    pub volume_total_gb: Option<f64>,
+    pub volume_free_gb: Option<f64>,
+    pub percent_used: Option<f64>,
+    pub top_children: Vec<ChildSummary>,
+    /// The default alert text, handy to embed in structured payloads
+    pub message: String,
+}
+
+impl AlertContext {
+    /// Context for test messages, so templates can be checked along with the credentials.
+    pub fn test(message: Option<String>) -> Self {
+        let hostname = sysinfo::System::host_name().unwrap_or_default();
+        Self {
+            path: "/".to_string(),
+            severity: "INFO".to_string(),
+            threshold_type: "Test".to_string(),
+            threshold: 0.0,
+            current: 0.0,
+            unit: "GB".to_string(),
+            message: message.unwrap_or_else(|| format!("✅ Volumetrik test alert from {}", hostname)),
+            hostname,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            volume_total_gb: None,
+            volume_free_gb: None,
+            percent_used: None,
+            top_children: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ChildSummary {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    pub size_gb: f64,
+}
+
+/// Variable names understood by the legacy `{name}` placeholder syntax.
+const LEGACY_VARIABLES: [&str; 13] = [
+    "path",
+    "severity",
+    "threshold_type",
+    "threshold",
+    "current",
+    "unit",
+    "hostname",
+    "timestamp",
+    "volume_total_gb",
+    "volume_free_gb",
+    "percent_used",
+    "top_children",
+    "message",
+];
+
+/// Renders a template against the alert context.
+///
+/// Templates use Jinja syntax (`{{ path }}`, `{% for c in top_children %}`).
+/// Templates without any `{{`/`{%` are treated as legacy `custom_message` strings
+/// where `{path}`, `{threshold}` and `{current}` style placeholders are accepted.
+/// When `json` is set, string values are escaped so they can be placed inside JSON string literals.
+pub fn render(template: &str, ctx: &AlertContext, json: bool) -> Result<String, String> {
+    let source = if template.contains("{{") || template.contains("{%") {
+        template.to_string()
+    } else {
+        legacy_to_jinja(template)
+    };
+
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Lenient);
+    env.add_filter("gb", |bytes: u64| format!("{:.2}", bytes as f64 / 1_073_741_824.0));
+    env.add_filter("fixed", |value: f64, digits: Option<usize>| format!("{:.*}", digits.unwrap_or(2), value));
+    // Shortest form, `90` rather than `90.0`
+    env.add_filter("plain", |value: f64| value.to_string());
+    if json {
+        env.set_formatter(|out, state, value| {
+            if let Some(s) = value.as_str() {
+                let quoted = serde_json::to_string(s).unwrap_or_default();
+                out.write_str(&quoted[1..quoted.len() - 1])
+                    .map_err(|_| minijinja::Error::new(minijinja::ErrorKind::WriteFailure, "write failed"))
+            } else if value.is_none() {
+                out.write_str("null")
+                    .map_err(|_| minijinja::Error::new(minijinja::ErrorKind::WriteFailure, "write failed"))
+            } else {
+                minijinja::escape_formatter(out, state, value)
+            }
+        });
+    }
+
+    env.render_str(&source, ctx).map_err(|e| format!("Template error: {}", e))
+}
+
+/// Converts `{name}` placeholders into Jinja expressions, formatting numbers like the old
+/// `String::replace` based messages did.
+fn legacy_to_jinja(template: &str) -> String {
+    let mut out = template.to_string();
+    for name in LEGACY_VARIABLES {
+        let expr = match name {
+            "current" | "volume_total_gb" | "volume_free_gb" | "percent_used" => {
+                format!("{{{{ {}|fixed if {} is not none else \"?\" }}}}", name, name)
+            }
+            "threshold" => "{{ threshold|plain }}".to_string(),
+            "top_children" => "{% for c in top_children %}{{ c.name }} ({{ c.size|gb }} GB){% if not loop.last %}, {% endif %}{% endfor %}".to_string(),
+            _ => format!("{{{{ {} }}}}", name),
+        };
+        out = out.replace(&format!("{{{}}}", name), &expr);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> AlertContext {
+        AlertContext {
+            path: "/data".to_string(),
+            severity: "WARNING".to_string(),
+            threshold_type: "MaxUsedPercent".to_string(),
+            threshold: 90.0,
+            current: 93.456,
+            unit: "%".to_string(),
+            hostname: "nas".to_string(),
+            timestamp: "2026-01-05T03:00:00+00:00".to_string(),
+            volume_total_gb: Some(100.0),
+            volume_free_gb: None,
+            percent_used: Some(93.456),
+            top_children: vec![
+                ChildSummary {
+                    name: "media".to_string(),
+                    path: "/data/media".to_string(),
+                    size: 3 * 1_073_741_824,
+                    size_gb: 3.0,
+                },
+                ChildSummary {
+                    name: "backups".to_string(),
+                    path: "/data/backups".to_string(),
+                    size: 1_073_741_824 / 2,
+                    size_gb: 0.5,
+                },
+            ],
+            message: "Disk \"data\" is full".to_string(),
+        }
+    }
+
+    #[test]
+    fn converts_legacy_placeholders() {
+        assert_eq!(legacy_to_jinja("{path} on {hostname}"), "{{ path }} on {{ hostname }}");
+        assert_eq!(
+            legacy_to_jinja("{current}"),
+            "{{ current|fixed if current is not none else \"?\" }}"
+        );
+        // Unknown names and lone braces are left alone
+        assert_eq!(legacy_to_jinja("{other} { path } {"), "{other} { path } {");
+    }
+
+    #[test]
+    fn renders_legacy_messages() {
+        let ctx = context();
+        assert_eq!(
+            render("{severity}: {path} at {current}{unit} (limit {threshold})", &ctx, false).unwrap(),
+            "WARNING: /data at 93.46% (limit 90)"
+        );
+        let fractional = AlertContext { threshold: 92.5, ..context() };
+        assert_eq!(render("limit {threshold}", &fractional, false).unwrap(), "limit 92.5");
+        assert_eq!(render("free {volume_free_gb} of {volume_total_gb}", &ctx, false).unwrap(), "free ? of 100.00");
+        assert_eq!(render("{top_children}", &ctx, false).unwrap(), "media (3.00 GB), backups (0.50 GB)");
+        assert_eq!(render("{top_children}", &AlertContext { top_children: Vec::new(), ..ctx }, false).unwrap(), "");
+    }
+
+    #[test]
+    fn renders_jinja_templates() {
+        let ctx = context();
+        assert_eq!(render("{{ path }} {{ current|fixed(1) }}", &ctx, false).unwrap(), "/data 93.5");
+        // Jinja templates are not touched by the legacy conversion
+        assert_eq!(render("{{ hostname }} {path}", &ctx, false).unwrap(), "nas {path}");
+        assert_eq!(render("{{ missing }}", &ctx, false).unwrap(), "");
+        assert!(render("{% if %}", &ctx, false).is_err());
+    }
+
+    #[test]
+    fn json_escapes_strings() {
+        let ctx = context();
+        let body = render(r#"{"text": "{{ message }}", "free": {{ volume_free_gb }}}"#, &ctx, true).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(value["text"], "Disk \"data\" is full");
+        assert!(value["free"].is_null());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::pathmap::mapper;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::time::SystemTime;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    /// Devices, sockets, pipes
+    Other,
+}
+
+/// One entry of a scanned tree. Sizes and counts of directories cover everything below them.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TreeNode {
+    pub name: String,
+    pub kind: EntryKind,
+    /// Apparent size in bytes
+    pub size: u64,
+    /// Allocated size in bytes, 0 when unknown
+    pub disk_usage: u64,
+    /// Files below a directory, 1 for anything else
+    pub file_count: u64,
+    pub modified: u64,
+    pub accessed: u64,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub mode: Option<u32>,
+    pub dev: Option<u64>,
+    pub ino: Option<u64>,
+    /// The directory could not be read, its size is incomplete
+    pub read_error: bool,
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+
+    /// Entry below this one, following `relative` component by component.
+    pub fn find(&self, relative: &str) -> Option<&TreeNode> {
+        relative
+            .split(['/', '\\'])
+            .filter(|c| !c.is_empty())
+            .try_fold(self, |node, name| node.children.iter().find(|c| c.name == name))
+    }
+
+    /// Sums the sizes and counts of a directory from its children.
+    pub fn compute_totals(&mut self) {
+        if !self.is_dir() {
+            return;
+        }
+        for child in &mut self.children {
+            child.compute_totals();
+        }
+        self.size = self.children.iter().map(|c| c.size).sum();
+        self.disk_usage = self.children.iter().map(|c| c.disk_usage).sum();
+        self.file_count = self.children.iter().map(|c| c.file_count).sum();
+    }
+
+    /// Visits every entry depth-first with its path, built from `path` and the entry names.
+    pub fn walk<'a>(&'a self, path: &str, visit: &mut impl FnMut(&str, &'a TreeNode)) {
+        visit(path, self);
+        for child in &self.children {
+            child.walk(&mapper().ui_child(path, &child.name), visit);
+        }
+    }
+}
+
+#[cfg(test)]
+impl TreeNode {
+    /// A file modified at `modified`, for test trees.
+    pub fn test_file(name: &str, size: u64, modified: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: EntryKind::File,
+            size,
+            disk_usage: size,
+            file_count: 1,
+            modified,
+            accessed: modified,
+            uid: None,
+            gid: None,
+            mode: None,
+            dev: None,
+            ino: None,
+            read_error: false,
+            children: Vec::new(),
+        }
+    }
+
+    /// A directory holding `children`, with its totals computed, for test trees.
+    pub fn test_dir(name: &str, modified: u64, children: Vec<TreeNode>) -> Self {
+        let mut dir = Self {
+            kind: EntryKind::Dir,
+            file_count: 0,
+            children,
+            ..Self::test_file(name, 0, modified)
+        };
+        dir.compute_totals();
+        dir
+    }
+}
+
+fn timestamp(time: std::io::Result<SystemTime>) -> u64 {
+    time.unwrap_or(SystemTime::UNIX_EPOCH)
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn node(name: String, metadata: &Metadata) -> TreeNode {
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_dir() {
+        EntryKind::Dir
+    } else if file_type.is_symlink() {
+        EntryKind::Symlink
+    } else if file_type.is_file() {
+        EntryKind::File
+    } else {
+        EntryKind::Other
+    };
+
+    #[cfg(unix)]
+    let (disk_usage, uid, gid, mode, dev, ino) = {
+        use std::os::unix::fs::MetadataExt;
+        (
+            metadata.blocks() * 512,
+            Some(metadata.uid()),
+            Some(metadata.gid()),
+            Some(metadata.mode()),
+            Some(metadata.dev()),
+            Some(metadata.ino()),
+        )
+    };
+    #[cfg(not(unix))]
+    let (disk_usage, uid, gid, mode, dev, ino) = (metadata.len(), None, None, None, None, None);
+
+    TreeNode {
+        name,
+        kind,
+        size: metadata.len(),
+        disk_usage,
+        file_count: 1,
+        modified: timestamp(metadata.modified()),
+        accessed: timestamp(metadata.accessed()),
+        uid,
+        gid,
+        mode,
+        dev,
+        ino,
+        read_error: false,
+        children: Vec::new(),
+    }
+}
+
+fn scan_dir(path: &Path, dir: &mut TreeNode) {
+    let entries: Vec<_> = match fs::read_dir(path) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).collect(),
+        Err(_) => {
+            dir.read_error = true;
+            return;
+        }
+    };
+
+    // Symlinks are not followed, they could lead outside the scanned tree
+    dir.children = entries
+        .par_iter()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            let mut child = node(entry.file_name().to_string_lossy().to_string(), &metadata);
+            if child.is_dir() {
+                scan_dir(&entry.path(), &mut child);
+            }
+            Some(child)
+        })
+        .collect();
+}
+
+/// Scans a whole tree, keeping every entry. Uses more memory than `scan::scan_path`,
+/// which only keeps the direct children.
+pub fn scan_tree(path: &Path) -> std::io::Result<TreeNode> {
+    let metadata = fs::symlink_metadata(path)?;
+    let name = path.to_string_lossy().to_string();
+    let mut root = node(name, &metadata);
+    if root.is_dir() {
+        // Fail like `scan_path` when the root itself can't be read
+        fs::read_dir(path)?;
+        scan_dir(path, &mut root);
+    }
+    root.compute_totals();
+    Ok(root)
+}
@@ -0,0 +1,291 @@
+use crate::pathmap::mapper;
+use crate::tree::TreeNode;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub const DEFAULT_DEPTH: usize = 3;
+pub const MAX_DEPTH: usize = 16;
+/// Entries smaller than this share of the requested root are collapsed by default
+pub const DEFAULT_MIN_SHARE: f64 = 0.005;
+
+/// Name of the node grouping the entries too small to be shown on their own.
+pub const OTHER_NAME: &str = "(other)";
+
+/// File type family, used to color treemaps.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Video,
+    Audio,
+    Image,
+    Document,
+    Archive,
+    Code,
+    Executable,
+    DiskImage,
+    Database,
+    Log,
+    Other,
+}
+
+const CATEGORY_EXTENSIONS: &[(Category, &[&str])] = &[
+    (Category::Video, &["mp4", "mkv", "avi", "mov", "wmv", "flv", "webm", "m4v", "mpg", "mpeg"]),
+    (Category::Audio, &["mp3", "flac", "wav", "aac", "ogg", "m4a", "wma", "opus", "aiff"]),
+    (Category::Image, &["jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "raw", "cr2", "nef", "svg", "psd"]),
+    (Category::Document, &["pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "txt", "md", "rtf", "csv", "epub"]),
+    (Category::Archive, &["zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "lz4", "bak"]),
+    (Category::Code, &["rs", "c", "h", "cpp", "hpp", "py", "js", "ts", "java", "go", "rb", "php", "cs", "html", "css", "json", "yml", "yaml", "toml", "xml", "sh"]),
+    (Category::Executable, &["exe", "dll", "so", "dylib", "bin", "msi", "deb", "rpm", "apk", "appimage", "jar", "o", "a"]),
+    (Category::DiskImage, &["iso", "img", "vmdk", "vdi", "qcow2", "vhd", "vhdx", "dmg"]),
+    (Category::Database, &["db", "sqlite", "sqlite3", "mdb", "accdb", "frm", "ibd", "dbf"]),
+    (Category::Log, &["log", "journal", "out", "err"]),
+];
+
+/// Category of a file from its extension. Rotated logs (`app.log.1`, `app.log.2.gz`) count as logs.
+pub fn category_of(name: &str) -> Category {
+    let lower = name.to_lowercase();
+    // Hidden files without extension (`.bashrc`) have an empty stem
+    let extension = match lower.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => ext,
+        _ => return Category::Other,
+    };
+    if lower.contains(".log.") {
+        return Category::Log;
+    }
+    CATEGORY_EXTENSIONS
+        .iter()
+        .find(|(_, extensions)| extensions.contains(&extension))
+        .map(|(category, _)| *category)
+        .unwrap_or(Category::Other)
+}
+
+#[derive(Debug, Serialize)]
+pub struct TreemapNode {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub file_count: u64,
+    /// Bytes per file category below this node
+    pub categories: BTreeMap<Category, u64>,
+    /// Number of entries collapsed into this node, set on the "other" nodes only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collapsed: Option<u64>,
+    pub children: Vec<TreemapNode>,
+}
+
+/// Limits applied when building the hierarchy.
+#[derive(Debug, Clone, Copy)]
+pub struct Pruning {
+    pub depth: usize,
+    /// Entries smaller than this many bytes are collapsed
+    pub min_size: u64,
+    /// Entries smaller than this share (0 to 1) of the root are collapsed
+    pub min_share: f64,
+}
+
+/// Builds the hierarchy below `tree`, `path` being the path shown for it and the name of the root.
+pub fn build(tree: &TreeNode, path: &str, pruning: Pruning) -> TreemapNode {
+    let threshold = pruning.min_size.max((tree.size as f64 * pruning.min_share.clamp(0.0, 1.0)).ceil() as u64);
+    let mut root = build_node(tree, path, pruning.depth.min(MAX_DEPTH), threshold);
+    root.name = path.to_string();
+    root
+}
+
+fn build_node(node: &TreeNode, path: &str, depth: usize, threshold: u64) -> TreemapNode {
+    let mut result = TreemapNode {
+        name: node.name.clone(),
+        path: path.to_string(),
+        is_dir: node.is_dir(),
+        size: node.size,
+        file_count: node.file_count,
+        categories: BTreeMap::new(),
+        collapsed: None,
+        children: Vec::new(),
+    };
+
+    if !node.is_dir() || depth == 0 {
+        add_categories(node, &mut result.categories);
+        return result;
+    }
+
+    let mut other: Option<TreemapNode> = None;
+    for child in &node.children {
+        let child_path = mapper().ui_child(path, &child.name);
+        if child.size >= threshold && child.size > 0 {
+            let built = build_node(child, &child_path, depth - 1, threshold);
+            merge(&mut result.categories, &built.categories);
+            result.children.push(built);
+            continue;
+        }
+
+        let group = other.get_or_insert_with(|| TreemapNode {
+            name: OTHER_NAME.to_string(),
+            path: path.to_string(),
+            is_dir: false,
+            size: 0,
+            file_count: 0,
+            categories: BTreeMap::new(),
+            collapsed: Some(0),
+            children: Vec::new(),
+        });
+        group.size += child.size;
+        group.file_count += child.file_count;
+        group.collapsed = group.collapsed.map(|n| n + 1);
+        add_categories(child, &mut group.categories);
+    }
+
+    result.children.sort_by_key(|c| std::cmp::Reverse(c.size));
+    if let Some(other) = other {
+        merge(&mut result.categories, &other.categories);
+        result.children.push(other);
+    }
+    result
+}
+
+fn add_categories(node: &TreeNode, categories: &mut BTreeMap<Category, u64>) {
+    if node.is_dir() {
+        for child in &node.children {
+            add_categories(child, categories);
+        }
+    } else if node.size > 0 {
+        *categories.entry(category_of(&node.name)).or_default() += node.size;
+    }
+}
+
+fn merge(into: &mut BTreeMap<Category, u64>, from: &BTreeMap<Category, u64>) {
+    for (category, size) in from {
+        *into.entry(*category).or_default() += size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_pruning(depth: usize) -> Pruning {
+        Pruning { depth, min_size: 0, min_share: 0.0 }
+    }
+
+    fn names(node: &TreemapNode) -> Vec<&str> {
+        node.children.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn categories_by_extension() {
+        assert_eq!(category_of("movie.MKV"), Category::Video);
+        assert_eq!(category_of("backup.tar.gz"), Category::Archive);
+        assert_eq!(category_of("disk.qcow2"), Category::DiskImage);
+        assert_eq!(category_of("Makefile"), Category::Other);
+        assert_eq!(category_of("file."), Category::Other);
+        assert_eq!(category_of("notes.unknown"), Category::Other);
+    }
+
+    #[test]
+    fn rotated_logs() {
+        assert_eq!(category_of("syslog.log"), Category::Log);
+        assert_eq!(category_of("app.log.1"), Category::Log);
+        assert_eq!(category_of("app.log.2.gz"), Category::Log);
+        assert_eq!(category_of("APP.LOG.3"), Category::Log);
+        assert_eq!(category_of("catalog.1"), Category::Other);
+    }
+
+    #[test]
+    fn hidden_files() {
+        assert_eq!(category_of(".bashrc"), Category::Other);
+        assert_eq!(category_of(".json"), Category::Other);
+        assert_eq!(category_of(".cache.db"), Category::Database);
+        assert_eq!(category_of(".hidden.log.1"), Category::Log);
+    }
+
+    fn fixture() -> TreeNode {
+        TreeNode::test_dir(
+            "data",
+            0,
+            vec![
+                TreeNode::test_dir(
+                    "media",
+                    0,
+                    vec![
+                        TreeNode::test_file("movie.mkv", 600, 0),
+                        TreeNode::test_dir("music", 0, vec![TreeNode::test_file("song.mp3", 200, 0)]),
+                    ],
+                ),
+                TreeNode::test_file("disk.iso", 150, 0),
+                TreeNode::test_file("app.log.1", 30, 0),
+                TreeNode::test_file("notes.txt", 15, 0),
+                TreeNode::test_file("empty.txt", 0, 0),
+            ],
+        )
+    }
+
+    #[test]
+    fn sorted_with_categories() {
+        let map = build(&fixture(), "/data", no_pruning(DEFAULT_DEPTH));
+        assert_eq!(map.name, "/data");
+        assert_eq!(map.size, 995);
+        // Empty entries have nothing to draw and always end up in "(other)"
+        assert_eq!(names(&map), ["media", "disk.iso", "app.log.1", "notes.txt", OTHER_NAME]);
+        assert_eq!(map.children[0].path, "/data/media");
+        assert_eq!(map.children[4].collapsed, Some(1));
+        assert_eq!(map.categories[&Category::Video], 600);
+        assert_eq!(map.categories[&Category::Audio], 200);
+        assert_eq!(map.categories[&Category::Log], 30);
+        assert!(!map.categories.contains_key(&Category::Other));
+    }
+
+    #[test]
+    fn small_entries_are_grouped() {
+        // 5% of 995 bytes rounds up to 50, the log and the notes fall below
+        let map = build(&fixture(), "/data", Pruning { depth: DEFAULT_DEPTH, min_size: 0, min_share: 0.05 });
+        assert_eq!(names(&map), ["media", "disk.iso", OTHER_NAME]);
+        let other = &map.children[2];
+        assert_eq!(other.size, 45);
+        assert_eq!(other.file_count, 3);
+        assert_eq!(other.collapsed, Some(3));
+        assert_eq!(other.path, "/data");
+        assert_eq!(other.categories[&Category::Log], 30);
+        assert_eq!(other.categories[&Category::Document], 15);
+        assert_eq!(map.categories[&Category::Log], 30);
+
+        // The threshold is the larger of the two limits, and is inclusive
+        let map = build(&fixture(), "/data", Pruning { depth: DEFAULT_DEPTH, min_size: 150, min_share: 0.05 });
+        assert_eq!(names(&map), ["media", "disk.iso", OTHER_NAME]);
+        assert_eq!(names(&map.children[0]), ["movie.mkv", "music"]);
+        let map = build(&fixture(), "/data", Pruning { depth: DEFAULT_DEPTH, min_size: 151, min_share: 0.0 });
+        assert_eq!(names(&map), ["media", OTHER_NAME]);
+        assert_eq!(names(&map.children[0]), ["movie.mkv", "music"]);
+    }
+
+    #[test]
+    fn share_of_the_root_only() {
+        // The share is taken from the requested root, not from each directory
+        let map = build(&fixture(), "/data", Pruning { depth: DEFAULT_DEPTH, min_size: 0, min_share: 0.25 });
+        assert_eq!(names(&map), ["media", OTHER_NAME]);
+        assert_eq!(names(&map.children[0]), ["movie.mkv", OTHER_NAME]);
+        assert_eq!(map.children[1].collapsed, Some(4));
+
+        // Shares above 1 are clamped, everything is grouped
+        let map = build(&fixture(), "/data", Pruning { depth: DEFAULT_DEPTH, min_size: 0, min_share: 2.0 });
+        assert_eq!(names(&map), [OTHER_NAME]);
+        assert_eq!(map.children[0].collapsed, Some(5));
+        assert_eq!(map.children[0].size, map.size);
+    }
+
+    #[test]
+    fn depth_limit() {
+        let map = build(&fixture(), "/data", no_pruning(0));
+        assert!(map.children.is_empty());
+        assert_eq!(map.categories[&Category::Audio], 200);
+
+        let map = build(&fixture(), "/data", no_pruning(1));
+        let media = &map.children[0];
+        assert!(media.children.is_empty());
+        assert_eq!(media.categories[&Category::Video], 600);
+        assert_eq!(media.categories[&Category::Audio], 200);
+
+        let map = build(&fixture(), "/data", no_pruning(2));
+        assert_eq!(names(&map.children[0]), ["movie.mkv", "music"]);
+        assert!(map.children[0].children[1].children.is_empty());
+    }
+}
@@ -1,11 +1,24 @@
 const API_URL = '/api';
 
+// Show the login form whenever the API asks for authentication
+const originalFetch = window.fetch.bind(window);
+window.fetch = async (...args) => {
+    const response = await originalFetch(...args);
+    if (response.status === 401 && !String(args[0]).startsWith(`${API_URL}/auth/`)) {
+        showLogin();
+    }
+    return response;
+};
+
 let typesBarChart = null;
 let typesDoughnutChart = null;
 let currentData = null;
 let abortController = null;
 let currentSort = { column: 'size', direction: 'desc' };
 let grid = null;
+let canEdit = true; // false for read-only viewers
+let currentImport = null; // imported dump being browsed instead of the live filesystem
+let cleanupStatus = { enabled: false, trash_dir: null }; // write actions, opt-in on the server
 
 const translations = {
     en: {
@@ -42,6 +55,11 @@ const translations = {
         enable_monitoring_desc: "Enable background storage analysis",
         monitored_paths: "Watch List",
         max_used: "Max Allocation",
+        last_check: "Last check",
+        max_used_percent: "Max Volume Usage (%)",
+        severity_info: "Info",
+        severity_warning: "Warning",
+        severity_critical: "Critical",
         min_remaining: "Min Free Space",
         add: "Append",
         interval_minutes: "Polling Interval (min)",
@@ -50,7 +68,7 @@ const translations = {
         enable_alerts: "Activate Alerting",
         enable_alerts_desc: "Dispatch events on trigger",
         custom_alert_message: "Custom Payload Template",
-        available_variables: "Variables: {path}, {threshold}",
+        available_variables: "Variables: {path}, {threshold}, {current}, {severity}, {hostname}, {timestamp}, {percent_used}, {top_children}",
         bot_token: "API Token",
         chat_id: "Channel ID",
         user_key: "User Key",
@@ -77,6 +95,41 @@ const translations = {
         path_already_monitored: "Path already monitored",
         invalid_path_threshold: "Please enter a valid path and threshold value",
         settings_saved_success: "Settings saved successfully!",
+        send_test_alert: "Send Test Alert",
+        send_test_alert_desc: "Uses the saved settings, even when alerts are disabled.",
+        test_alert_none: "No alert channel is configured.",
+        test_alert_result: "Test alert: ",
+        login_title: "Sign In",
+        username: "Username",
+        password: "Password",
+        login: "Sign in",
+        logout: "Sign out",
+        login_failed: "Invalid username or password.",
+        export_import: "Export / Import",
+        export: "Export",
+        export_desc: "Full tree of the scanned folder, including every subfolder.",
+        export_no_path: "Scan a folder first.",
+        export_started: "Exporting ",
+        export_failed: "Export failed: ",
+        imports: "Imported dumps",
+        imports_desc: "Trees captured with ncdu -o on other machines.",
+        import: "Import",
+        import_done: "Imported ",
+        import_failed: "Import failed: ",
+        open_import: "Browse",
+        confirm_delete_import: "Delete the imported dump ",
+        viewing_import: "Viewing imported dump",
+        exit_import: "Back to live data",
+        cleanup_delete: "Delete",
+        cleanup_trash: "Move to trash",
+        confirm_cleanup_delete: "Permanently delete ",
+        confirm_cleanup_trash: "Move to trash ",
+        cleanup_done: "Removed: ",
+        cleanup_failed: "Cleanup failed: ",
+        cleanup_archive: "Archive",
+        confirm_cleanup_archive: "Archive and remove ",
+        archive_started: "Archiving started: ",
+        archive_done: "Archived: ",
         settings_save_failed: "Failed to save settings: ",
         layout_saved: "Layout saved",
         error_saving_layout: "Error saving layout: ",
@@ -125,6 +178,11 @@ const translations = {
         enable_monitoring_desc: "Activer l'analyse en arrière-plan",
         monitored_paths: "Liste de Supervision",
         max_used: "Allocation Max",
+        last_check: "Dernière vérification",
+        max_used_percent: "Utilisation Max du Volume (%)",
+        severity_info: "Info",
+        severity_warning: "Avertissement",
+        severity_critical: "Critique",
         min_remaining: "Espace Libre Min",
         add: "Ajouter Entrée",
         interval_minutes: "Intervalle de Polling (min)",
@@ -133,7 +191,7 @@ const translations = {
         enable_alerts: "Activer Alertes",
         enable_alerts_desc: "Expédier les événements sur déclenchement",
         custom_alert_message: "Modèle de Charge Utile",
-        available_variables: "Variables : {path}, {threshold}",
+        available_variables: "Variables : {path}, {threshold}, {current}, {severity}, {hostname}, {timestamp}, {percent_used}, {top_children}",
         bot_token: "Token API",
         chat_id: "ID Canal",
         user_key: "Clé Utilisateur",
@@ -160,6 +218,41 @@ const translations = {
         path_already_monitored: "Chemin déjà surveillé",
         invalid_path_threshold: "Veuillez entrer un chemin et un seuil valides",
         settings_saved_success: "Paramètres enregistrés avec succès !",
+        send_test_alert: "Envoyer une alerte de test",
+        send_test_alert_desc: "Utilise les paramètres enregistrés, même si les alertes sont désactivées.",
+        test_alert_none: "Aucun canal d'alerte n'est configuré.",
+        test_alert_result: "Alerte de test : ",
+        login_title: "Connexion",
+        username: "Nom d'utilisateur",
+        password: "Mot de passe",
+        login: "Se connecter",
+        logout: "Se déconnecter",
+        login_failed: "Nom d'utilisateur ou mot de passe incorrect.",
+        export_import: "Export / Import",
+        export: "Exporter",
+        export_desc: "Arborescence complète du dossier analysé, sous-dossiers compris.",
+        export_no_path: "Analysez d'abord un dossier.",
+        export_started: "Export de ",
+        export_failed: "Échec de l'export : ",
+        imports: "Dumps importés",
+        imports_desc: "Arborescences capturées avec ncdu -o sur d'autres machines.",
+        import: "Importer",
+        import_done: "Importé : ",
+        import_failed: "Échec de l'import : ",
+        open_import: "Parcourir",
+        confirm_delete_import: "Supprimer le dump importé ",
+        viewing_import: "Consultation d'un dump importé",
+        exit_import: "Revenir aux données réelles",
+        cleanup_delete: "Supprimer",
+        cleanup_trash: "Mettre à la corbeille",
+        confirm_cleanup_delete: "Supprimer définitivement ",
+        confirm_cleanup_trash: "Mettre à la corbeille ",
+        cleanup_done: "Supprimé : ",
+        cleanup_failed: "Échec du nettoyage : ",
+        cleanup_archive: "Archiver",
+        confirm_cleanup_archive: "Archiver et supprimer ",
+        archive_started: "Archivage lancé : ",
+        archive_done: "Archivé : ",
         settings_save_failed: "Échec de l'enregistrement des paramètres : ",
         layout_saved: "Disposition enregistrée",
         error_saving_layout: "Erreur lors de l'enregistrement de la disposition : ",
@@ -208,6 +301,11 @@ const translations = {
         enable_monitoring_desc: "Habilitar análisis en segundo plano",
         monitored_paths: "Lista de Supervisión",
         max_used: "Asignación Máx",
+        last_check: "Última comprobación",
+        max_used_percent: "Uso Máx del Volumen (%)",
+        severity_info: "Info",
+        severity_warning: "Advertencia",
+        severity_critical: "Crítico",
         min_remaining: "Espacio Libre Mín",
         add: "Añadir Entrada",
         interval_minutes: "Intervalo de Polling (min)",
@@ -216,7 +314,7 @@ const translations = {
         enable_alerts: "Activar Alertas",
         enable_alerts_desc: "Despachar eventos al disparar",
         custom_alert_message: "Plantilla de Carga Útil",
-        available_variables: "Variables: {path}, {threshold}",
+        available_variables: "Variables: {path}, {threshold}, {current}, {severity}, {hostname}, {timestamp}, {percent_used}, {top_children}",
         bot_token: "Token API",
         chat_id: "ID Canal",
         user_key: "Clave Usuario",
@@ -243,6 +341,41 @@ const translations = {
         path_already_monitored: "Ruta ya monitoreada",
         invalid_path_threshold: "Por favor ingrese una ruta y un valor de umbral válidos",
         settings_saved_success: "¡Configuración guardada exitosamente!",
+        send_test_alert: "Enviar alerta de prueba",
+        send_test_alert_desc: "Usa la configuración guardada, incluso con las alertas desactivadas.",
+        test_alert_none: "No hay ningún canal de alerta configurado.",
+        test_alert_result: "Alerta de prueba: ",
+        login_title: "Iniciar sesión",
+        username: "Usuario",
+        password: "Contraseña",
+        login: "Entrar",
+        logout: "Cerrar sesión",
+        login_failed: "Usuario o contraseña incorrectos.",
+        export_import: "Exportar / Importar",
+        export: "Exportar",
+        export_desc: "Árbol completo de la carpeta analizada, con todas sus subcarpetas.",
+        export_no_path: "Analice primero una carpeta.",
+        export_started: "Exportando ",
+        export_failed: "Error al exportar: ",
+        imports: "Volcados importados",
+        imports_desc: "Árboles capturados con ncdu -o en otras máquinas.",
+        import: "Importar",
+        import_done: "Importado: ",
+        import_failed: "Error al importar: ",
+        open_import: "Explorar",
+        confirm_delete_import: "¿Eliminar el volcado importado ",
+        viewing_import: "Viendo un volcado importado",
+        exit_import: "Volver a los datos reales",
+        cleanup_delete: "Eliminar",
+        cleanup_trash: "Mover a la papelera",
+        confirm_cleanup_delete: "¿Eliminar definitivamente ",
+        confirm_cleanup_trash: "¿Mover a la papelera ",
+        cleanup_done: "Eliminado: ",
+        cleanup_failed: "Error de limpieza: ",
+        cleanup_archive: "Archivar",
+        confirm_cleanup_archive: "¿Archivar y eliminar ",
+        archive_started: "Archivado iniciado: ",
+        archive_done: "Archivado: ",
         settings_save_failed: "Error al guardar la configuración: ",
         layout_saved: "Diseño guardado",
         error_saving_layout: "Error al guardar el diseño: ",
@@ -289,6 +422,11 @@ const translations = {
         enable_monitoring_desc: "Hintergrundanalyse aktivieren",
         monitored_paths: "Überwachungsliste",
         max_used: "Max Zuweisung",
+        last_check: "Letzte Prüfung",
+        max_used_percent: "Max Volumenbelegung (%)",
+        severity_info: "Info",
+        severity_warning: "Warnung",
+        severity_critical: "Kritisch",
         min_remaining: "Min Freier Speicher",
         add: "Eintrag Hinzufügen",
         interval_minutes: "Polling-Intervall (Min)",
@@ -297,7 +435,7 @@ const translations = {
         enable_alerts: "Warnungen Aktivieren",
         enable_alerts_desc: "Ereignisse bei Auslösung versenden",
         custom_alert_message: "Nutzlast-Vorlage",
-        available_variables: "Variablen: {path}, {threshold}",
+        available_variables: "Variablen: {path}, {threshold}, {current}, {severity}, {hostname}, {timestamp}, {percent_used}, {top_children}",
         bot_token: "API Token",
         chat_id: "Kanal ID",
         user_key: "Benutzerschlüssel",
@@ -324,6 +462,41 @@ const translations = {
         path_already_monitored: "Pfad wird bereits überwacht",
         invalid_path_threshold: "Bitte geben Sie einen gültigen Pfad und Schwellenwert ein",
         settings_saved_success: "Einstellungen erfolgreich gespeichert!",
+        send_test_alert: "Testalarm senden",
+        send_test_alert_desc: "Verwendet die gespeicherten Einstellungen, auch wenn Alarme deaktiviert sind.",
+        test_alert_none: "Kein Alarmkanal ist konfiguriert.",
+        test_alert_result: "Testalarm: ",
+        login_title: "Anmelden",
+        username: "Benutzername",
+        password: "Passwort",
+        login: "Anmelden",
+        logout: "Abmelden",
+        login_failed: "Ungültiger Benutzername oder Passwort.",
+        export_import: "Export / Import",
+        export: "Exportieren",
+        export_desc: "Vollständiger Baum des analysierten Ordners mit allen Unterordnern.",
+        export_no_path: "Bitte zuerst einen Ordner analysieren.",
+        export_started: "Exportiere ",
+        export_failed: "Export fehlgeschlagen: ",
+        imports: "Importierte Dumps",
+        imports_desc: "Mit ncdu -o auf anderen Rechnern erfasste Bäume.",
+        import: "Importieren",
+        import_done: "Importiert: ",
+        import_failed: "Import fehlgeschlagen: ",
+        open_import: "Durchsuchen",
+        confirm_delete_import: "Importierten Dump löschen: ",
+        viewing_import: "Importierter Dump",
+        exit_import: "Zurück zu den Live-Daten",
+        cleanup_delete: "Löschen",
+        cleanup_trash: "In den Papierkorb",
+        confirm_cleanup_delete: "Endgültig löschen: ",
+        confirm_cleanup_trash: "In den Papierkorb verschieben: ",
+        cleanup_done: "Entfernt: ",
+        cleanup_failed: "Bereinigung fehlgeschlagen: ",
+        cleanup_archive: "Archivieren",
+        confirm_cleanup_archive: "Archivieren und entfernen: ",
+        archive_started: "Archivierung gestartet: ",
+        archive_done: "Archiviert: ",
         settings_save_failed: "Fehler beim Speichern der Einstellungen: ",
         layout_saved: "Layout gespeichert",
         error_saving_layout: "Fehler beim Speichern des Layouts: ",
@@ -370,6 +543,11 @@ const translations = {
         enable_monitoring_desc: "Abilita analisi in background",
         monitored_paths: "Lista monitoraggio",
         max_used: "Allocazione Max",
+        last_check: "Ultimo controllo",
+        max_used_percent: "Utilizzo Max Volume (%)",
+        severity_info: "Info",
+        severity_warning: "Avviso",
+        severity_critical: "Critico",
         min_remaining: "Spazio libero Min",
         add: "Aggiungi",
         interval_minutes: "Intervallo polling (min)",
@@ -378,7 +556,7 @@ const translations = {
         enable_alerts: "Attiva avvisi",
         enable_alerts_desc: "Invia eventi su trigger",
         custom_alert_message: "Modello payload personalizzato",
-        available_variables: "Variabili: {path}, {threshold}",
+        available_variables: "Variabili: {path}, {threshold}, {current}, {severity}, {hostname}, {timestamp}, {percent_used}, {top_children}",
         bot_token: "Token API",
         chat_id: "ID Canale",
         user_key: "Chiave Utente",
@@ -405,6 +583,41 @@ const translations = {
         path_already_monitored: "Percorso già monitorato",
         invalid_path_threshold: "Inserisci un percorso e una soglia validi",
         settings_saved_success: "Impostazioni salvate con successo!",
+        send_test_alert: "Invia avviso di prova",
+        send_test_alert_desc: "Usa le impostazioni salvate, anche con gli avvisi disattivati.",
+        test_alert_none: "Nessun canale di avviso configurato.",
+        test_alert_result: "Avviso di prova: ",
+        login_title: "Accesso",
+        username: "Nome utente",
+        password: "Password",
+        login: "Accedi",
+        logout: "Esci",
+        login_failed: "Nome utente o password non validi.",
+        export_import: "Esporta / Importa",
+        export: "Esporta",
+        export_desc: "Albero completo della cartella analizzata, con tutte le sottocartelle.",
+        export_no_path: "Analizza prima una cartella.",
+        export_started: "Esportazione di ",
+        export_failed: "Esportazione non riuscita: ",
+        imports: "Dump importati",
+        imports_desc: "Alberi acquisiti con ncdu -o su altre macchine.",
+        import: "Importa",
+        import_done: "Importato: ",
+        import_failed: "Importazione non riuscita: ",
+        open_import: "Sfoglia",
+        confirm_delete_import: "Eliminare il dump importato ",
+        viewing_import: "Visualizzazione di un dump importato",
+        exit_import: "Torna ai dati reali",
+        cleanup_delete: "Elimina",
+        cleanup_trash: "Sposta nel cestino",
+        confirm_cleanup_delete: "Eliminare definitivamente ",
+        confirm_cleanup_trash: "Spostare nel cestino ",
+        cleanup_done: "Rimosso: ",
+        cleanup_failed: "Pulizia non riuscita: ",
+        cleanup_archive: "Archivia",
+        confirm_cleanup_archive: "Archiviare ed eliminare ",
+        archive_started: "Archiviazione avviata: ",
+        archive_done: "Archiviato: ",
         settings_save_failed: "Salvataggio impostazioni fallito: ",
         layout_saved: "Layout salvato",
         error_saving_layout: "Errore salvataggio layout: ",
@@ -546,6 +759,15 @@ document.addEventListener('DOMContentLoaded', () => {
     
     // Load initial settings to apply language
     loadSettings();
+    checkAuth();
+
+    document.getElementById('dataBtn').addEventListener('click', openDataModal);
+    document.getElementById('exportBtn').addEventListener('click', exportCurrent);
+    document.getElementById('importBtn').addEventListener('click', uploadImport);
+    document.getElementById('exitImportBtn').addEventListener('click', exitImport);
+    document.querySelector('.close-data-modal').addEventListener('click', () => dataModal.style.display = 'none');
+    document.getElementById('loginForm').addEventListener('submit', login);
+    document.getElementById('logoutBtn').addEventListener('click', logout);
     
     // Load palette
     const savedPalette = localStorage.getItem('palette') || 'default';
@@ -588,15 +810,21 @@ async function scan(path) {
     showToast(getTranslation('scan_started') + path, 'info');
 
     try {
-        const response = await fetch(`${API_URL}/scan?path=${encodeURIComponent(path)}`, {
+        const endpoint = currentImport ? `imports/${encodeURIComponent(currentImport.id)}/scan` : 'scan';
+        // Sorted server-side, header clicks then re-sort the loaded entries
+        const params = new URLSearchParams({ path, sort: scanSortParam(currentSort.column), order: currentSort.direction });
+        const response = await fetch(`${API_URL}/${endpoint}?${params}`, {
             signal: abortController.signal
         });
-        if (!response.ok) throw new Error('Scan failed');
+        if (!response.ok) {
+            // Errors such as a refused path come back as a JSON string
+            const message = await response.json().catch(() => null);
+            throw new Error(typeof message === 'string' ? message : 'Scan failed');
+        }
         
         const data = await response.json();
         currentData = data;
         
-        sortFiles();
         renderDashboard(data);
     } catch (error) {
         if (error.name === 'AbortError') {
@@ -614,6 +842,15 @@ async function scan(path) {
     }
 }
 
+function scanSortParam(column) {
+    switch(column) {
+        case 'name': return 'name';
+        case 'files': return 'file_count';
+        case 'modified': return 'modified';
+        default: return 'size';
+    }
+}
+
 function sortFiles() {
     if (!currentData || !currentData.files) return;
     
@@ -729,7 +966,8 @@ function renderDiskSpace(data) {
     const availText = document.getElementById('diskAvailable');
     const totalText = document.getElementById('diskTotal');
 
-    if (data.disk_total && data.disk_available) {
+    // A full disk has 0 bytes available, only null means the volume is unknown
+    if (data.disk_total && data.disk_available != null) {
         const total = data.disk_total;
         const available = data.disk_available;
         const used = total - available;
@@ -738,7 +976,9 @@ function renderDiskSpace(data) {
         bar.style.width = `${percent}%`;
         usedText.textContent = `Used: ${formatBytes(used)}`;
         availText.textContent = `Available: ${formatBytes(available)}`;
-        totalText.textContent = `Total: ${formatBytes(total)}`;
+        totalText.textContent = data.disk_mount_point
+            ? `Total: ${formatBytes(total)} (${data.disk_mount_point})`
+            : `Total: ${formatBytes(total)}`;
     } else {
         // Fallback if disk info is not available
         bar.style.width = '0%';
@@ -917,10 +1157,105 @@ function renderFileBrowser(files, totalSize) {
             tr.addEventListener('click', () => scan(file.path));
         }
 
+        if (cleanupStatus.enabled && canEdit && !currentImport) {
+            const actions = document.createElement('span');
+            actions.className = 'cleanup-actions';
+            const buttons = cleanupStatus.trash_dir ? [['trash', 'fa-box-archive'], ['delete', 'fa-trash']] : [['delete', 'fa-trash']];
+            if (file.is_dir) buttons.unshift(['archive', 'fa-file-zipper']);
+            buttons.forEach(([action, icon]) => {
+                const btn = document.createElement('button');
+                btn.className = 'cleanup-btn';
+                btn.innerHTML = `<i class="fas ${icon}"></i>`;
+                btn.title = getTranslation(`cleanup_${action}`);
+                btn.onclick = (event) => {
+                    event.stopPropagation();
+                    if (action === 'archive') archiveEntry(file);
+                    else cleanupEntry(file, action);
+                };
+                actions.appendChild(btn);
+            });
+            tr.firstElementChild.appendChild(actions);
+        }
+
         tbody.appendChild(tr);
     });
 }
 
+async function loadCleanupStatus() {
+    try {
+        const response = await fetch(`${API_URL}/cleanup`);
+        if (response.ok) cleanupStatus = await response.json();
+    } catch (error) {
+        console.error('Error loading cleanup status:', error);
+    }
+}
+
+// Every action is previewed first, the server only runs it with the preview's token
+async function cleanupEntry(file, action) {
+    try {
+        const response = await fetch(`${API_URL}/cleanup/preview`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ action, paths: [file.path] })
+        });
+        const preview = await response.json();
+        if (!response.ok) throw new Error(typeof preview === 'string' ? preview : response.statusText);
+
+        const summary = `${file.path} (${formatBytes(preview.total_size)}, ${preview.total_files.toLocaleString()} ${getTranslation('files')})`;
+        if (!confirm(getTranslation(`confirm_cleanup_${action}`) + summary + '?')) return;
+
+        const result = await fetch(`${API_URL}/cleanup/execute`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ token: preview.token })
+        });
+        const report = await result.json();
+        if (!result.ok) throw new Error(typeof report === 'string' ? report : result.statusText);
+        const failure = report.results.find(r => !r.success);
+        if (failure) throw new Error(failure.error);
+
+        showToast(getTranslation('cleanup_done') + formatBytes(report.total_size), 'success');
+        scan(currentData.current);
+    } catch (error) {
+        console.error(error);
+        showToast(getTranslation('cleanup_failed') + error.message, 'error');
+    }
+}
+
+// Archiving runs as a server job, polled until it finishes
+async function archiveEntry(file) {
+    try {
+        const summary = `${file.path} (${formatBytes(file.size)}, ${file.file_count.toLocaleString()} ${getTranslation('files')})`;
+        if (!confirm(getTranslation('confirm_cleanup_archive') + summary + '?')) return;
+
+        const response = await fetch(`${API_URL}/archive`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ path: file.path })
+        });
+        let job = await response.json();
+        if (!response.ok) throw new Error(typeof job === 'string' ? job : response.statusText);
+        showToast(getTranslation('archive_started') + file.path, 'success');
+
+        while (job.state === 'running') {
+            await new Promise(resolve => setTimeout(resolve, 2000));
+            const poll = await fetch(`${API_URL}/jobs/${job.id}`);
+            if (!poll.ok) throw new Error(poll.statusText);
+            job = await poll.json();
+        }
+        if (job.state !== 'succeeded') throw new Error(job.error || job.state);
+
+        const report = job.result;
+        const sizes = `${formatBytes(report.before.size)} → ${formatBytes(report.after.size)}`;
+        showToast(getTranslation('archive_done') + `${report.archive} (${sizes})`, report.warning ? 'error' : 'success');
+        if (report.warning) console.warn(report.warning);
+        if (currentData && !currentImport) scan(currentData.current);
+    } catch (error) {
+        console.error(error);
+        showToast(getTranslation('cleanup_failed') + error.message, 'error');
+    }
+}
+
 function formatBytes(bytes, decimals = 2) {
     if (bytes === 0) return '0 Bytes';
     const k = 1024;
@@ -1021,6 +1356,9 @@ async function loadDirectories(path) {
                 };
                 folderList.appendChild(li);
             });
+        } else if (response.status === 403 || response.status === 404) {
+            const message = await response.json().catch(() => null);
+            showToast(typeof message === 'string' ? message : response.statusText, 'error');
         }
     } catch (error) {
         console.error('Error browsing:', error);
@@ -1035,6 +1373,128 @@ function joinPath(base, part) {
     return base + separator + part;
 }
 
+// --- Export / Import ---
+const dataModal = document.getElementById('dataModal');
+
+function openDataModal() {
+    dataModal.style.display = 'block';
+    // Only administrators can upload dumps
+    document.getElementById('importUpload').style.display = canEdit ? '' : 'none';
+    document.getElementById('exportBtn').disabled = !!currentImport;
+    loadImports();
+}
+
+async function exportCurrent() {
+    const path = currentData ? currentData.current : document.getElementById('pathInput').value;
+    if (!path) {
+        showToast(getTranslation('export_no_path'), 'error');
+        return;
+    }
+    const format = document.getElementById('exportFormat').value;
+    showToast(getTranslation('export_started') + path, 'info');
+
+    try {
+        const response = await fetch(`${API_URL}/export?path=${encodeURIComponent(path)}&format=${format}`);
+        if (!response.ok) {
+            const message = await response.json().catch(() => null);
+            throw new Error(typeof message === 'string' ? message : response.statusText);
+        }
+        const blob = await response.blob();
+        const link = document.createElement('a');
+        link.href = URL.createObjectURL(blob);
+        link.download = `volumetrik-export.${format === 'ncdu' ? 'json' : format}`;
+        link.click();
+        URL.revokeObjectURL(link.href);
+    } catch (error) {
+        showToast(getTranslation('export_failed') + error.message, 'error');
+    }
+}
+
+async function loadImports() {
+    const list = document.getElementById('importList');
+    try {
+        const response = await fetch(`${API_URL}/imports`);
+        if (!response.ok) return;
+        const imports = await response.json();
+        list.innerHTML = '';
+        imports.forEach(item => {
+            const li = document.createElement('li');
+            li.className = 'path-item';
+            const label = document.createElement('span');
+            label.textContent = `${item.name} (${formatBytes(item.total_size)}, ${new Date(item.imported_at).toLocaleDateString()})`;
+            label.title = item.root;
+            const actions = document.createElement('div');
+            actions.className = 'import-item-actions';
+
+            const openBtn = document.createElement('button');
+            openBtn.className = 'add-path-btn';
+            openBtn.innerHTML = '<i class="fas fa-folder-open"></i>';
+            openBtn.title = getTranslation('open_import');
+            openBtn.onclick = () => openImport(item);
+            actions.appendChild(openBtn);
+
+            if (canEdit) {
+                const deleteBtn = document.createElement('button');
+                deleteBtn.className = 'remove-path-btn';
+                deleteBtn.innerHTML = '<i class="fas fa-trash"></i>';
+                deleteBtn.onclick = () => deleteImport(item);
+                actions.appendChild(deleteBtn);
+            }
+
+            li.appendChild(label);
+            li.appendChild(actions);
+            list.appendChild(li);
+        });
+    } catch (error) {
+        console.error('Error loading imports:', error);
+    }
+}
+
+async function uploadImport() {
+    const input = document.getElementById('importFile');
+    const file = input.files[0];
+    if (!file) return;
+
+    try {
+        const response = await fetch(`${API_URL}/imports?name=${encodeURIComponent(file.name)}`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: file
+        });
+        const result = await response.json().catch(() => null);
+        if (!response.ok) {
+            throw new Error(typeof result === 'string' ? result : response.statusText);
+        }
+        input.value = '';
+        showToast(getTranslation('import_done') + result.root);
+        loadImports();
+    } catch (error) {
+        showToast(getTranslation('import_failed') + error.message, 'error');
+    }
+}
+
+async function deleteImport(item) {
+    if (!confirm(getTranslation('confirm_delete_import') + item.name)) return;
+    await fetch(`${API_URL}/imports/${encodeURIComponent(item.id)}`, { method: 'DELETE' });
+    if (currentImport && currentImport.id === item.id) exitImport();
+    loadImports();
+}
+
+function openImport(item) {
+    currentImport = item;
+    document.getElementById('importBannerName').textContent = `${item.name} (${item.root})`;
+    document.getElementById('importBanner').style.display = '';
+    dataModal.style.display = 'none';
+    scan(item.root);
+}
+
+function exitImport() {
+    currentImport = null;
+    currentData = null;
+    document.getElementById('importBanner').style.display = 'none';
+    renderDashboard({ parent: null, current: '', files: [], total_size: 0, total_files: 0 });
+}
+
 // --- Settings Modal Logic ---
 const settingsBtn = document.getElementById('settingsBtn');
 const settingsModal = document.getElementById('settingsModal');
@@ -1050,11 +1510,15 @@ const resetLayoutBtn = document.getElementById('resetLayoutBtn');
 
 let monitoredPaths = [];
 let initialLanguage = 'en';
+// Last settings received from the server, so fields without a form control survive a save
+let loadedSettings = {};
+// Per-path monitoring state from /api/monitoring/status, keyed by path
+let monitorStatus = {};
 
 if (settingsBtn) {
     settingsBtn.onclick = () => {
         settingsModal.style.display = 'block';
-        loadSettings();
+        loadSettings().then(loadMonitoringStatus);
         // Load current palette into select
         if (paletteSelect) {
             paletteSelect.value = localStorage.getItem('palette') || 'default';
@@ -1093,7 +1557,8 @@ if (addPathBtn) {
                 monitoredPaths.push({
                     path: path,
                     threshold_type: type,
-                    threshold_value: value
+                    threshold_value: value,
+                    severity: document.getElementById('newMonitorSeverity').value
                 });
                 renderMonitoredPaths();
                 newMonitorPathInput.value = '';
@@ -1115,7 +1580,8 @@ function renderMonitoredPaths() {
         li.innerHTML = `
             <div style="display: flex; flex-direction: column; gap: 2px;">
                 <span style="font-weight: bold;">${item.path}</span>
-                <span style="font-size: 0.8em; color: #7f8c8d;">${item.threshold_type === 'MaxUsed' ? 'Max Used' : 'Min Remaining'}: ${item.threshold_value} GB</span>
+                <span style="font-size: 0.8em; color: #7f8c8d;">${describeThreshold(item)}</span>
+                ${describeStatus(monitorStatus[item.path])}
             </div>
             <button type="button" class="remove-path-btn" onclick="removePath(${index})"><i class="fas fa-trash"></i></button>
         `;
@@ -1123,6 +1589,43 @@ function renderMonitoredPaths() {
     });
 }
 
+function describeStatus(state) {
+    if (!state || !state.last_check) {
+        return '';
+    }
+    const when = new Date(state.last_check).toLocaleString();
+    const values = state.measurements.map(m => `${m.value.toFixed(2)} ${m.unit}`).join(' · ');
+    const color = state.last_error ? '#e67e22' : (state.breached ? '#e74c3c' : '#27ae60');
+    const detail = state.last_error || values;
+    return `<span style="font-size: 0.8em; color: ${color};">${getTranslation('last_check')}: ${when}${detail ? ' · ' + detail : ''}</span>`;
+}
+
+async function loadMonitoringStatus() {
+    try {
+        const response = await fetch(`${API_URL}/monitoring/status`);
+        if (response.ok) {
+            const status = await response.json();
+            monitorStatus = {};
+            status.paths.forEach(state => { monitorStatus[state.path] = state; });
+            renderMonitoredPaths();
+        }
+    } catch (error) {
+        console.error('Error loading monitoring status:', error);
+    }
+}
+
+function describeThreshold(item) {
+    const levels = [item, ...(item.thresholds || [])];
+    return levels.map(level => {
+        const severity = getTranslation('severity_' + (level.severity || item.severity || 'Warning').toLowerCase());
+        if (level.threshold_type === 'MaxUsedPercent') {
+            return `${getTranslation('max_used_percent')}: ${level.threshold_value}% (${severity})`;
+        }
+        const label = level.threshold_type === 'MaxUsed' ? 'Max Used' : 'Min Remaining';
+        return `${label}: ${level.threshold_value} GB (${severity})`;
+    }).join(' · ');
+}
+
 window.removePath = function(index) {
     monitoredPaths.splice(index, 1);
     renderMonitoredPaths();
@@ -1159,6 +1662,11 @@ window.addEventListener('click', (event) => {
     }
 });
 
+const testAlertBtn = document.getElementById('testAlertBtn');
+if (testAlertBtn) {
+    testAlertBtn.onclick = sendTestAlert;
+}
+
 if (saveSettingsBtn) {
     saveSettingsBtn.onclick = saveSettings;
 }
@@ -1180,6 +1688,7 @@ async function loadSettings() {
         const response = await fetch(`${API_URL}/settings`);
         if (response.ok) {
             const settings = await response.json();
+            loadedSettings = settings;
             
             // General
             initialLanguage = settings.language || 'en';
@@ -1255,14 +1764,17 @@ async function saveSettings() {
     const currentPalette = document.getElementById('paletteSelect').value;
 
     const settings = {
+        ...loadedSettings,
         language: currentLanguage,
         monitoring: {
+            ...loadedSettings.monitoring,
             enabled: document.getElementById('monitorEnabled').checked,
             paths: monitoredPaths,
             // threshold_gb: parseFloat(document.getElementById('monitorThreshold').value) || 100.0, // Removed
             check_interval_minutes: parseInt(document.getElementById('monitorInterval').value) || 60
         },
         alerts: {
+            ...loadedSettings.alerts,
             enabled: document.getElementById('alertsEnabled').checked,
             custom_message: document.getElementById('customAlertMessage').value || null,
             telegram_bot_token: document.getElementById('telegramToken').value || null,
@@ -1293,6 +1805,7 @@ async function saveSettings() {
         });
 
         if (response.ok) {
+            loadedSettings = await response.json();
             showToast(getTranslation('settings_saved_success'), 'success');
             settingsModal.style.display = 'none';
             
@@ -1314,7 +1827,31 @@ async function saveSettings() {
     }
 }
 
+async function sendTestAlert() {
+    try {
+        const response = await fetch(`${API_URL}/alerts/test`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({})
+        });
+        const data = await response.json();
+        if (!data.results || data.results.length === 0) {
+            showToast(getTranslation('test_alert_none'), 'error');
+            return;
+        }
+        const summary = data.results
+            .map(r => `${r.channel} ${r.success ? '✓' : '✗' + (r.status ? ' ' + r.status : '')}`)
+            .join(', ');
+        showToast(getTranslation('test_alert_result') + summary, data.success ? 'success' : 'error');
+        data.results.filter(r => !r.success).forEach(r => console.error(`Test alert via ${r.channel} failed:`, r.status, r.error));
+    } catch (error) {
+        console.error('Error sending test alert:', error);
+        showToast(error.message, 'error');
+    }
+}
+
 async function saveLayout() {
+    if (!canEdit) return;
     const layout = [];
     if (grid && grid.engine) {
         grid.engine.nodes.forEach(node => {
@@ -1341,6 +1878,72 @@ async function saveLayout() {
     }
 }
 
+async function checkAuth() {
+    try {
+        const response = await fetch(`${API_URL}/auth/me`);
+        if (!response.ok) return;
+        const status = await response.json();
+        if (status.auth_enabled && !status.authenticated) {
+            showLogin();
+            return;
+        }
+        if (status.identity && status.identity.role !== 'Admin') {
+            // Settings and the shared layout can only be changed by administrators
+            canEdit = false;
+            document.getElementById('settingsBtn').style.display = 'none';
+            if (grid) grid.setStatic(true);
+        }
+        if (canEdit) loadCleanupStatus();
+        if (status.identity && status.identity.method === 'Session') {
+            const logoutBtn = document.getElementById('logoutBtn');
+            logoutBtn.title = `${getTranslation('logout')} (${status.identity.username})`;
+            logoutBtn.style.display = '';
+        }
+    } catch (error) {
+        console.error('Error checking authentication:', error);
+    }
+}
+
+function showLogin() {
+    const modal = document.getElementById('loginModal');
+    if (modal.style.display === 'block') return;
+    document.getElementById('loginError').style.display = 'none';
+    modal.style.display = 'block';
+    document.getElementById('loginUsername').focus();
+}
+
+async function login(event) {
+    event.preventDefault();
+    const error = document.getElementById('loginError');
+    try {
+        const response = await fetch(`${API_URL}/auth/login`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({
+                username: document.getElementById('loginUsername').value,
+                password: document.getElementById('loginPassword').value
+            })
+        });
+        if (response.ok) {
+            // Reload so every panel fetches its data with the new session
+            window.location.reload();
+            return;
+        }
+        error.textContent = getTranslation('login_failed');
+    } catch (e) {
+        error.textContent = e.message;
+    }
+    error.style.display = 'block';
+}
+
+async function logout() {
+    try {
+        await fetch(`${API_URL}/auth/logout`, { method: 'POST' });
+    } finally {
+        window.location.reload();
+    }
+}
+
 function showToast(message, type = 'success') {
     const toast = document.getElementById('toast');
     toast.textContent = message;